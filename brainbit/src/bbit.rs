@@ -1,10 +1,19 @@
-pub(crate) mod control;
+pub mod control;
+pub mod battery;
 pub mod device;
+pub mod discovery;
 pub mod errors;
-pub(crate) mod internals;
+pub mod internals;
+pub mod packet_loss;
+pub mod policy;
+pub mod profile;
+pub mod record;
 pub mod resist;
 pub mod responses;
 pub mod results;
-pub(crate) mod sealed;
+pub mod sealed;
+pub mod session;
+pub mod stream;
 pub mod traits;
+pub mod transport;
 pub mod uuids;
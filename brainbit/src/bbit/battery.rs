@@ -0,0 +1,315 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::bbit::responses::DeviceStatusData;
+
+/// Battery level in % raising [`BatteryAlert::Low`] by default
+pub const DEFAULT_LOW_BATTERY_LEVEL: f32 = 20.0;
+
+/// Battery level in % raising [`BatteryAlert::Critical`] by default
+pub const DEFAULT_CRITICAL_BATTERY_LEVEL: f32 = 5.0;
+
+/// Weight of a new battery level in the smoothed one by default
+pub const DEFAULT_BATTERY_SMOOTHING: f32 = 0.2;
+
+/// Discharge shorter than this is not used to estimate remaining time, device reports
+/// level in steps of more than 1%
+const MIN_ESTIMATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Battery level threshold crossed by discharging device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BatteryAlert {
+    /// Level fell to [`BatteryConfig::low_level`]
+    Low,
+    /// Level fell to [`BatteryConfig::critical_level`], the device is going to power down soon
+    Critical,
+}
+
+/// Thresholds and smoothing of battery monitoring done by the event loop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryConfig {
+    /// Level in % raising [`BatteryAlert::Low`]
+    pub low_level: f32,
+    /// Level in % raising [`BatteryAlert::Critical`]
+    pub critical_level: f32,
+    /// Weight of a new level in the smoothed one, 0.0..=1.0, 1.0 disables smoothing.
+    /// [`BatteryMonitor`] clamps it like [`BatteryConfig::smoothing`] does.
+    pub smoothing: f32,
+    /// Stop running measurement on [`BatteryAlert::Critical`], before the device powers down
+    pub stop_on_critical: bool,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            low_level: DEFAULT_LOW_BATTERY_LEVEL,
+            critical_level: DEFAULT_CRITICAL_BATTERY_LEVEL,
+            smoothing: DEFAULT_BATTERY_SMOOTHING,
+            stop_on_critical: false,
+        }
+    }
+}
+
+impl BatteryConfig {
+    /// Set levels in % raising low and critical alerts
+    pub fn thresholds(mut self, low_level: f32, critical_level: f32) -> Self {
+        self.low_level = low_level;
+        self.critical_level = critical_level;
+        self
+    }
+
+    /// Set weight of a new level in the smoothed one, it's clamped to `f32::EPSILON..=1.0`,
+    /// NaN is replaced with [`DEFAULT_BATTERY_SMOOTHING`]
+    pub fn smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = if smoothing.is_nan() {
+            DEFAULT_BATTERY_SMOOTHING
+        } else {
+            smoothing.clamp(f32::EPSILON, 1.0)
+        };
+        self
+    }
+
+    /// Stop running measurement on critical battery level
+    pub fn stop_on_critical(mut self, enabled: bool) -> Self {
+        self.stop_on_critical = enabled;
+        self
+    }
+}
+
+/// Battery state passed to [`crate::bbit::traits::EventHandler::battery_update`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryStatus {
+    /// Level in % reported by the last device status
+    pub level: f32,
+    /// Exponentially smoothed level in %
+    pub smoothed_level: f32,
+    /// Device is charging
+    pub charging: bool,
+    /// Discharge speed in % per hour, `None` until it's measurable or while charging
+    pub discharge_rate: Option<f32>,
+    /// Estimated time until [`BatteryConfig::critical_level`] is reached
+    pub remaining: Option<Duration>,
+    /// Threshold crossed by this update
+    pub alert: Option<BatteryAlert>,
+}
+
+/// Tracks battery level from device statuses, reports changes and crossed thresholds
+#[derive(Debug, Clone)]
+pub struct BatteryMonitor {
+    config: BatteryConfig,
+    smoothed_level: Option<f32>,
+    charging: bool,
+    /// Start of the current discharge: time and smoothed level
+    discharge_start: Option<(Instant, f32)>,
+    /// The most severe alert raised since the last charging
+    raised: Option<BatteryAlert>,
+    /// Rounded level and charging state of the last reported status
+    reported: Option<(i32, bool)>,
+}
+
+impl BatteryMonitor {
+    pub fn new(config: BatteryConfig) -> Self {
+        // the field may be set bypassing the builder clamp
+        let smoothing = config.smoothing;
+        let config = config.smoothing(smoothing);
+        if config.smoothing != smoothing {
+            tracing::warn!(
+                "Battery smoothing {smoothing} is out of range, {} is used",
+                config.smoothing
+            );
+        }
+        Self {
+            config,
+            smoothed_level: None,
+            charging: false,
+            discharge_start: None,
+            raised: None,
+            reported: None,
+        }
+    }
+
+    pub fn config(&self) -> &BatteryConfig {
+        &self.config
+    }
+
+    /// Register device status received at `now`, returns battery status when the rounded
+    /// level or charging state is changed or a threshold is crossed
+    pub fn update(&mut self, status: &DeviceStatusData, now: Instant) -> Option<BatteryStatus> {
+        let level = status.get_battery_charge_level();
+        let charging = status.is_charging();
+
+        let smoothed_level = match self.smoothed_level {
+            Some(smoothed) if charging == self.charging => {
+                smoothed + self.config.smoothing * (level - smoothed)
+            }
+            _ => {
+                // smoothing across plugging or unplugging the charger only delays the trend
+                self.discharge_start = None;
+                level
+            }
+        };
+        self.smoothed_level = Some(smoothed_level);
+        self.charging = charging;
+
+        let discharge_rate = if charging {
+            self.raised = None;
+            self.discharge_start = None;
+            None
+        } else {
+            self.discharge_rate(smoothed_level, now)
+        };
+        let remaining = discharge_rate.map(|rate| {
+            let left = (smoothed_level - self.config.critical_level).max(0.0);
+            Duration::from_secs_f32(left / rate * 3600.0)
+        });
+        let alert = self.check_thresholds(smoothed_level);
+
+        let reported = Some((smoothed_level.round() as i32, charging));
+        if alert.is_none() && reported == self.reported {
+            return None;
+        }
+        self.reported = reported;
+        Some(BatteryStatus {
+            level,
+            smoothed_level,
+            charging,
+            discharge_rate,
+            remaining,
+            alert,
+        })
+    }
+
+    /// Discharge speed in % per hour since the start of discharge
+    fn discharge_rate(&mut self, smoothed_level: f32, now: Instant) -> Option<f32> {
+        let (start, start_level) = *self.discharge_start.get_or_insert((now, smoothed_level));
+        if smoothed_level > start_level {
+            // level recovered after a load peak, start over
+            self.discharge_start = Some((now, smoothed_level));
+            return None;
+        }
+        let elapsed = now.duration_since(start);
+        let dropped = start_level - smoothed_level;
+        if elapsed < MIN_ESTIMATE_WINDOW || dropped <= 0.0 {
+            return None;
+        }
+        Some(dropped / elapsed.as_secs_f32() * 3600.0)
+    }
+
+    /// Alert more severe than already raised one, alerts are raised again after charging
+    fn check_thresholds(&mut self, smoothed_level: f32) -> Option<BatteryAlert> {
+        if self.charging {
+            return None;
+        }
+        let alert = if smoothed_level <= self.config.critical_level {
+            BatteryAlert::Critical
+        } else if smoothed_level <= self.config.low_level {
+            BatteryAlert::Low
+        } else {
+            return None;
+        };
+        if self.raised >= Some(alert) {
+            return None;
+        }
+        self.raised = Some(alert);
+        Some(alert)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbit::responses::{BATTERY_CHARGING_FLAG, MAX_BATTERY_LEVEL};
+
+    fn status(battery_level: u8) -> DeviceStatusData {
+        DeviceStatusData {
+            battery_level,
+            ..DeviceStatusData::default()
+        }
+    }
+
+    #[test]
+    fn test_smoothing_is_clamped() {
+        for (smoothing, expected) in [(0.0, f32::EPSILON), (2.0, 1.0), (f32::NAN, 0.2)] {
+            let config = BatteryConfig {
+                smoothing,
+                ..BatteryConfig::default()
+            };
+            assert_eq!(expected, BatteryMonitor::new(config).config().smoothing);
+        }
+    }
+
+    #[test]
+    fn test_battery_monitor() {
+        let config = BatteryConfig::default().thresholds(50.0, 10.0);
+        let mut monitor = BatteryMonitor::new(config);
+        let start = Instant::now();
+
+        let first = monitor.update(&status(MAX_BATTERY_LEVEL), start).unwrap();
+        assert_eq!(100.0, first.smoothed_level);
+        assert_eq!(None, first.alert);
+        assert_eq!(None, first.remaining);
+        // the same level is not reported again
+        assert!(monitor.update(&status(MAX_BATTERY_LEVEL), start).is_none());
+
+        // a single low reading is smoothed
+        let dip = monitor
+            .update(&status(0x2B), start + Duration::from_secs(1))
+            .unwrap();
+        assert!(dip.smoothed_level > 80.0 && dip.level < 50.0);
+        assert_eq!(None, dip.alert);
+
+        let mut alerts = vec![];
+        let mut last = dip;
+        for minute in 1..=30 {
+            let now = start + Duration::from_secs(minute * 60);
+            if let Some(battery) = monitor.update(&status(0x05), now) {
+                alerts.extend(battery.alert);
+                last = battery;
+            }
+        }
+        assert_eq!(vec![BatteryAlert::Low, BatteryAlert::Critical], alerts);
+        assert!(last.discharge_rate.unwrap() > 0.0);
+        assert!(last.smoothed_level < 10.0);
+        assert_eq!(Some(Duration::ZERO), last.remaining);
+
+        // charging resets alerts and the estimate
+        let charging = monitor
+            .update(
+                &status(BATTERY_CHARGING_FLAG | 0x05),
+                start + Duration::from_secs(3600),
+            )
+            .unwrap();
+        assert!(charging.charging);
+        assert_eq!(None, charging.alert);
+        assert_eq!(None, charging.discharge_rate);
+        let unplugged = monitor
+            .update(&status(0x05), start + Duration::from_secs(3601))
+            .unwrap();
+        assert_eq!(Some(BatteryAlert::Critical), unplugged.alert);
+    }
+
+    #[test]
+    fn test_remaining_estimate() {
+        let config = BatteryConfig::default().smoothing(1.0);
+        let mut monitor = BatteryMonitor::new(config);
+        let start = Instant::now();
+        monitor.update(&status(MAX_BATTERY_LEVEL), start);
+        // the first step of the level
+        let half_hour = monitor
+            .update(
+                &status(MAX_BATTERY_LEVEL - 1),
+                start + Duration::from_secs(1800),
+            )
+            .unwrap();
+        let level = half_hour.smoothed_level;
+        let rate = half_hour.discharge_rate.unwrap();
+        assert!((rate - (100.0 - level) * 2.0).abs() < 1e-3, "{rate} %/h");
+        let remaining = half_hour.remaining.unwrap().as_secs_f32();
+        let expected = (level - DEFAULT_CRITICAL_BATTERY_LEVEL) / rate * 3600.0;
+        assert!(
+            (remaining - expected).abs() < 1.0,
+            "{remaining} != {expected}"
+        );
+    }
+}
@@ -1,9 +1,15 @@
+use crate::bbit::errors::Error;
+use crate::bbit::responses::Nss2Status;
 use crate::bbit::results::BBitResult;
-use crate::bbit::uuids::WRITE_COMMAN_UUID;
-use crate::{find_characteristic, Error};
-use btleplug::api::{Characteristic, Peripheral as _, WriteType};
-use btleplug::platform::Peripheral;
+use crate::bbit::transport::Transport;
+use crate::find_characteristic;
+use btleplug::api::Characteristic;
+use std::time::Duration;
 use tracing::debug;
+use uuid::Uuid;
+
+/// Default time to wait for device status confirming a command
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Struct that has access to command point.
 #[derive(Debug, PartialEq, Eq)]
@@ -12,29 +18,30 @@ pub struct ControlPoint {
 }
 
 impl ControlPoint {
-    /// Create new [`ControlPoint`].
-    pub async fn new(device: &Peripheral) -> BBitResult<Self> {
-        let control_point = find_characteristic(device, WRITE_COMMAN_UUID).await?;
+    /// Create new [`ControlPoint`] writing to command characteristic `uuid`.
+    pub async fn new<T: Transport>(device: &T, uuid: Uuid) -> BBitResult<Self> {
+        let control_point = find_characteristic(device, uuid).await?;
 
         Ok(Self { control_point })
     }
 
     /// Send command to [`ControlPoint`] waiting for a response from device.
-    pub async fn send_command(&self, device: &Peripheral, data: &[u8]) -> BBitResult<()> {
+    pub async fn send_command<T: Transport>(&self, device: &T, data: &[u8]) -> BBitResult<()> {
         debug!("Send command to sensor: {:02X?}", data);
         self.write(device, data).await?;
         Ok(())
     }
 
     /// Send command to [`ControlPoint`] waiting for a response from device.
-    pub async fn send_control_command_enum(
+    pub async fn send_control_command_enum<T: Transport>(
         &self,
-        device: &Peripheral,
+        device: &T,
         command: ControlPointCommand,
     ) -> BBitResult<()> {
         debug!("Send control enum command to sensor: {command:?}");
-        let command_as_bytes: Vec<u8> =
-            <ControlPointCommand as TryInto<Vec<u8>>>::try_into(command).unwrap();
+        let command_as_bytes: Vec<u8> = command
+            .try_into()
+            .map_err(|e: &str| Error::InvalidData(e.to_string()))?;
         self.write(device, command_as_bytes.as_slice()).await?;
         debug!(
             "Written control enum command to sensor: {:02X?}",
@@ -44,15 +51,14 @@ impl ControlPoint {
     }
 
     /// Send command to [`ControlPoint`] without a response.
-    async fn write(&self, device: &Peripheral, data: &[u8]) -> BBitResult<()> {
+    async fn write<T: Transport>(&self, device: &T, data: &[u8]) -> BBitResult<()> {
         debug!("Write data command to sensor: {:02X?}", data);
-        device
-            .write(&self.control_point, data, WriteType::WithResponse)
-            .await
-            .map_err(Error::BleError)
+        device.write(&self.control_point, data).await
     }
 }
 
+/// Command code written as the first byte to command characteristic, see
+/// `docs/dev_info.md`. Code of every start command equals [`Nss2Status`] the device switches to.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ControlCommandType {
     /// Impossible command
@@ -67,6 +73,39 @@ pub enum ControlCommandType {
     StartDfu = 0x04,
 }
 
+impl ControlCommandType {
+    /// Command code on the wire
+    pub fn code(&self) -> u8 {
+        self.clone() as u8
+    }
+
+    /// Device status reported when the command is executed successfully
+    pub fn expected_status(&self) -> Option<Nss2Status> {
+        match self {
+            ControlCommandType::Invalid => None,
+            ControlCommandType::StopAll => Some(Nss2Status::Stopped),
+            ControlCommandType::StartEegSignal => Some(Nss2Status::EegTransmission),
+            ControlCommandType::StartResist => Some(Nss2Status::ResistTransmission),
+            ControlCommandType::StartDfu => Some(Nss2Status::DfuBootLoderMode),
+        }
+    }
+}
+
+impl TryFrom<u8> for ControlCommandType {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Self::Invalid),
+            0x01 => Ok(Self::StopAll),
+            0x02 => Ok(Self::StartEegSignal),
+            0x03 => Ok(Self::StartResist),
+            0x04 => Ok(Self::StartDfu),
+            _ => Err("ControlCommandType is unknown"),
+        }
+    }
+}
+
 /// Command enum stores internal u8 array with config data.
 #[derive(Clone, Debug)]
 pub struct ControlPointCommand {
@@ -78,10 +117,7 @@ pub struct ControlPointCommand {
 
 impl ControlPointCommand {
     pub fn new(cmd_type: ControlCommandType, data: Option<Vec<u8>>) -> Self {
-        Self {
-            cmd_type: cmd_type,
-            data: data,
-        }
+        Self { cmd_type, data }
     }
 }
 
@@ -89,21 +125,21 @@ impl TryInto<Vec<u8>> for ControlPointCommand {
     type Error = &'static str;
 
     fn try_into(self) -> Result<Vec<u8>, Self::Error> {
+        let mut cmd = Vec::from([self.cmd_type.code()]);
         match self.cmd_type {
-            ControlCommandType::Invalid => Ok(Vec::from([0x00])),
-            ControlCommandType::StopAll => Ok(Vec::from([0x01])),
-            ControlCommandType::StartEegSignal => {
-                let mut cmd = Vec::from([0x02]);
-                cmd.extend(self.data.unwrap());
-                Ok(cmd)
-            }
-            ControlCommandType::StartResist => {
-                let mut cmd = Vec::from([0x03]);
-                cmd.extend(self.data.unwrap());
-                Ok(cmd)
-            }
-            ControlCommandType::StartDfu => Ok(Vec::from([0x01])),
+            ControlCommandType::Invalid
+            | ControlCommandType::StopAll
+            | ControlCommandType::StartDfu => {}
+            ControlCommandType::StartEegSignal => cmd.extend(
+                self.data
+                    .ok_or("StartEegSignal command without channels config")?,
+            ),
+            ControlCommandType::StartResist => cmd.extend(
+                self.data
+                    .ok_or("StartResist command without channels config")?,
+            ),
         }
+        Ok(cmd)
     }
 }
 
@@ -111,39 +147,21 @@ impl TryFrom<&[u8]> for ControlPointCommand {
     type Error = &'static str;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        match value {
-            [0] => Ok(ControlPointCommand::new(ControlCommandType::Invalid, None)),
-            [1] => Ok(ControlPointCommand::new(ControlCommandType::StopAll, None)),
-            [2, signal_config_ch1, signal_config_ch2, signal_config_ch3, signal_config_ch4] => {
-                let data_array = [
-                    *signal_config_ch1,
-                    *signal_config_ch2,
-                    *signal_config_ch3,
-                    *signal_config_ch4,
-                ];
-                Ok(ControlPointCommand::new(
-                    ControlCommandType::StartEegSignal,
-                    Some(Vec::from(data_array)),
-                ))
+        let Some((&code, data)) = value.split_first() else {
+            return Err("ControlPointCommand is empty");
+        };
+        match (ControlCommandType::try_from(code)?, data) {
+            (
+                cmd_type @ (ControlCommandType::Invalid
+                | ControlCommandType::StopAll
+                | ControlCommandType::StartDfu),
+                [],
+            ) => Ok(ControlPointCommand::new(cmd_type, None)),
+            (cmd_type @ ControlCommandType::StartEegSignal, [_, _, _, _])
+            | (cmd_type @ ControlCommandType::StartResist, [_, _, _, _, _, _, _]) => {
+                Ok(ControlPointCommand::new(cmd_type, Some(data.to_vec())))
             }
-            [4, resist_config_ch1, resist_config_ch2, resist_config_ch3, resist_config_ch4, resist_sensp, resist_sensn, resist_flipp] =>
-            {
-                let data_array = [
-                    *resist_config_ch1,
-                    *resist_config_ch2,
-                    *resist_config_ch3,
-                    *resist_config_ch4,
-                    *resist_sensp,
-                    *resist_sensn,
-                    *resist_flipp,
-                ];
-                Ok(ControlPointCommand::new(
-                    ControlCommandType::StartResist,
-                    Some(Vec::from(data_array)),
-                ))
-            }
-            [5] => Ok(ControlPointCommand::new(ControlCommandType::StartDfu, None)),
-            _ => Err("ControlPointCommand is unknown inside array[u8]"),
+            _ => Err("ControlPointCommand has unexpected length"),
         }
     }
 }
@@ -156,10 +174,10 @@ mod tests {
     #[test]
     fn test_resist_command_layout() {
         let cmd_data = [
-            ADS1294ChannelInput::PowerDownGain3.into(),
-            ADS1294ChannelInput::PowerUpGain1.into(),
-            ADS1294ChannelInput::PowerUpGain1.into(),
-            ADS1294ChannelInput::PowerUpGain1.into(),
+            ADS1294ChannelInput::PowerDownGain1Shorted.into(),
+            ADS1294ChannelInput::PowerUpGain4.into(),
+            ADS1294ChannelInput::PowerUpGain4.into(),
+            ADS1294ChannelInput::PowerUpGain4.into(),
             0x00,
             0x00,
             0x00,
@@ -208,4 +226,59 @@ mod tests {
 
         assert_eq!(&expected, command_as_bytes.as_slice())
     }
+
+    #[test]
+    fn test_command_bytes_round_trip() {
+        let commands = [
+            ControlPointCommand::new(ControlCommandType::StopAll, None),
+            ControlPointCommand::new(ControlCommandType::StartEegSignal, Some(vec![0x00; 4])),
+            ControlPointCommand::new(
+                ControlCommandType::StartResist,
+                Some(vec![0x91, 0x48, 0x48, 0x48, 0x01, 0x01, 0x00]),
+            ),
+            ControlPointCommand::new(ControlCommandType::StartDfu, None),
+        ];
+        for command in commands {
+            let command_as_bytes: Vec<u8> =
+                <ControlPointCommand as TryInto<Vec<u8>>>::try_into(command.clone()).unwrap();
+            let parsed = ControlPointCommand::try_from(command_as_bytes.as_slice()).unwrap();
+            assert_eq!(command.cmd_type, parsed.cmd_type);
+            assert_eq!(command.data, parsed.data);
+        }
+    }
+
+    #[test]
+    fn test_command_wire_bytes() {
+        // written to command characteristic, see docs/dev_info.md
+        let wire: [(&[u8], ControlCommandType); 4] = [
+            (&[0x01], ControlCommandType::StopAll),
+            (
+                &[0x02, 0x00, 0x00, 0x00, 0x00],
+                ControlCommandType::StartEegSignal,
+            ),
+            (
+                &[0x03, 0x91, 0x48, 0x48, 0x48, 0x01, 0x01, 0x00],
+                ControlCommandType::StartResist,
+            ),
+            (&[0x04], ControlCommandType::StartDfu),
+        ];
+        for (bytes, cmd_type) in wire {
+            let command = ControlPointCommand::try_from(bytes).unwrap();
+            assert_eq!(cmd_type, command.cmd_type);
+            assert_eq!(Some(cmd_type.code()), bytes.first().copied());
+            let command_as_bytes: Vec<u8> = command.try_into().unwrap();
+            assert_eq!(bytes, command_as_bytes.as_slice());
+        }
+        // unknown code and wrong config length
+        assert!(ControlPointCommand::try_from([0x05].as_slice()).is_err());
+        assert!(ControlPointCommand::try_from([0x04, 0x00].as_slice()).is_err());
+        assert!(ControlPointCommand::try_from([0x03, 0x00, 0x00, 0x00, 0x00].as_slice()).is_err());
+        assert!(ControlPointCommand::try_from([].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_command_without_config() {
+        let command = ControlPointCommand::new(ControlCommandType::StartEegSignal, None);
+        assert!(<ControlPointCommand as TryInto<Vec<u8>>>::try_into(command).is_err());
+    }
 }
@@ -1,42 +1,55 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, VecDeque};
 use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
-use btleplug::{
-    api::{Central, Characteristic, Manager as _, Peripheral as _, ScanFilter},
-    platform::{Manager, Peripheral},
-};
+use btleplug::api::{Characteristic, ValueNotification};
 use futures::stream::StreamExt;
-use tokio::sync::{mpsc, oneshot, watch};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tracing::{debug, instrument};
 use uuid::Uuid;
 
+use crate::bbit::battery::{BatteryAlert, BatteryConfig, BatteryMonitor};
 use crate::bbit::control::{ControlCommandType, ControlPoint, ControlPointCommand};
-use crate::bbit::internals::{ADS1294ChannelInput, ChannelType, MeasurementType};
-use crate::bbit::responses::{DeviceInfo, DeviceStatusData};
+use crate::bbit::discovery::{self, connect_target, ConnectTarget, DiscoveredDevice};
+use crate::bbit::internals::{ADS1294ChannelInput, ChannelType, EegConfig, MeasurementType};
+use crate::bbit::packet_loss::{GapFill, PacketGapKind, PacketTracker};
+use crate::bbit::policy::ConnectPolicy;
+use crate::bbit::profile::{BrainBitProfile, DeviceProfile};
+use crate::bbit::resist::{ResistState, ResistanceSweep, ResistanceSweepConfig, SweepStep};
+use crate::bbit::responses::{
+    CommandExecutionState, DeviceInfo, DeviceStatusData, EegDecoder, EegPacket, Nss2Status,
+    ResistanceDecoder, ResistanceReading, DEFAULT_LEAD_OFF_CURRENT_A,
+};
 use crate::bbit::results::BBitResult;
 use crate::bbit::sealed::{Bluetooth, Configure, Connected, EventLoop, Level};
-use crate::bbit::traits::EventHandler;
-use crate::bbit::uuids::{
-    EventType, NotifyStream, NotifyUuid, FIRMWARE_REVISION_STRING_UUID,
-    HARDWARE_REVISION_STRING_UUID, MODEL_NUMBER_STRING_UUID, NSS2_SERVICE_UUID,
-    SERIAL_NUMBER_STRING_UUID,
+use crate::bbit::stream::{
+    EventSubscriber, FanoutHandler, SensorEvent, SensorStream, StreamHandler, SENSOR_EVENT_BUFFER,
+    SUBSCRIBER_BUFFER,
 };
+use crate::bbit::traits::{EventHandler, HandlerError, HandlerErrorPolicy, HandlerResult};
+use crate::bbit::transport::{self, AdapterInfo, AdapterSelector, BtleplugTransport, Transport};
+use crate::bbit::uuids::{EventType, NotifyStream, StringUuid};
 use crate::{find_characteristic, Error};
 
-/// Structure to contain EEG data and interval.
+/// Number of notifications buffered for the event task, newer ones are dropped when it's busy
+const BLUETOOTH_EVENT_BUFFER: usize = 1024;
+
+/// Control command written to device, passed to [`EventHandler::send_command`]
 #[derive(Debug, Clone)]
 pub struct CommandData {
-    data: i16,
-    cmd_type: ControlPointCommand,
+    /// Written command
+    pub command: ControlPointCommand,
+    /// Device status confirmed the command, it's awaited only when [`EventType::State`] is
+    /// listened
+    pub confirmed: bool,
 }
 
 /// The core sensor manager
-pub struct BBitSensor<L: Level> {
-    /// BLE connection manager
-    ble_manager: Manager,
-    /// Connected and controlled device
-    ble_device: Option<Peripheral>,
+pub struct BBitSensor<L: Level, T: Transport = BtleplugTransport> {
+    /// BLE link to connected and controlled device
+    transport: T,
     /// BLE event types subscribed and processed
     pub subscribed_data_event_types: Vec<EventType>,
     /// Device manage and send commands
@@ -44,63 +57,77 @@ pub struct BBitSensor<L: Level> {
     pub level: L,
     /// Common device information like model, serial numbers, HW, SW revisions
     pub device_info: OnceLock<DeviceInfo>,
+    /// GATT layout and data format of the device family
+    profile: Arc<dyn DeviceProfile>,
 }
 
 impl BBitSensor<Bluetooth> {
     /// Construct a BleSensor
     pub async fn new() -> BBitResult<Self> {
-        Ok(Self {
-            ble_manager: Manager::new().await?,
-            ble_device: None,
+        Ok(Self::with_transport(BtleplugTransport::new().await?))
+    }
+
+    /// Construct a BleSensor using selected Bluetooth adapter
+    pub async fn with_adapter(adapter: AdapterSelector) -> BBitResult<Self> {
+        Ok(Self::with_transport(
+            BtleplugTransport::with_adapter(adapter).await?,
+        ))
+    }
+
+    /// List Bluetooth adapters available for [`BBitSensor::with_adapter`]
+    pub async fn adapters() -> BBitResult<Vec<AdapterInfo>> {
+        transport::list_adapters().await
+    }
+}
+
+impl<T: Transport> BBitSensor<Bluetooth, T> {
+    /// Construct a BleSensor working over specified [`Transport`]
+    pub fn with_transport(transport: T) -> Self {
+        Self {
+            transport,
             subscribed_data_event_types: vec![],
             control_point: None,
-            level: Bluetooth,
+            level: Bluetooth::default(),
             device_info: OnceLock::new(),
-        })
+            profile: Arc::new(BrainBitProfile),
+        }
     }
 
-    /// Connect to a device. Blocks until a connection is found
+    /// Connect to a device of another Neurotech family, [`BrainBitProfile`] is used by default
     #[instrument(skip(self))]
-    pub async fn block_connect(mut self, device_name: &str) -> BBitResult<BBitSensor<Configure>> {
-        let mut error_on_connect_max_attempts_count = 20; // error attempts
+    pub fn with_profile<P: DeviceProfile + 'static>(mut self, profile: P) -> Self {
+        self.profile = Arc::new(profile);
+        self
+    }
 
-        while !self.is_connected().await {
-            // try to do specified connect attempts
-            match self.try_connect(device_name).await {
-                Err(e @ Error::NoBleAdaptor) => {
+    /// Replace default [`ConnectPolicy`] used by connection methods and reconnection
+    #[instrument(skip(self))]
+    pub fn connect_policy(mut self, policy: ConnectPolicy) -> Self {
+        self.level.connect_policy = policy;
+        self
+    }
+
+    /// Connect to a device. Retries according to [`ConnectPolicy`] until a connection is found
+    #[instrument(skip(self))]
+    pub async fn block_connect(self, device_name: &str) -> BBitResult<BBitSensor<Configure, T>> {
+        self.map_connect(device_name, |r| {
+            match r {
+                Err(e @ (Error::NoBleAdaptor | Error::AdapterNotFound(_))) => {
                     tracing::error!("No bluetooth adaptors found");
                     return Err(e);
                 }
-                Err(e) => {
-                    error_on_connect_max_attempts_count -= 1;
-                    tracing::warn!("Could not connect to '{device_name}' on attempt = '{error_on_connect_max_attempts_count}', error: {}", e);
-                    if error_on_connect_max_attempts_count <= 0 {
-                        tracing::error!("Stopped connecting attempts after limit !");
-                        return Err(e);
-                    }
-                    tokio::time::sleep(Duration::from_secs(2)).await;
-                }
-                Ok(_) => {
-                    debug!("BLE '{device_name}' is connected...");
-                }
+                Err(e) => tracing::warn!("Could not connect to '{device_name}', error: {e}"),
+                Ok(_) => debug!("BLE '{device_name}' is connected..."),
             }
-        }
-
-        let new_self: BBitSensor<Configure> = BBitSensor {
-            ble_manager: self.ble_manager,
-            ble_device: self.ble_device,
-            control_point: self.control_point,
-            subscribed_data_event_types: self.subscribed_data_event_types,
-            level: Configure::default(),
-            device_info: self.device_info,
-        };
-
-        Ok(new_self)
+            Ok(())
+        })
+        .await
     }
 
     /// Connect to a device, but override the behavior after each attempted connect
     /// Return [`Ok`] from the closure to continue trying to connect or [`Err`]
-    /// give up and return.
+    /// give up and return. Attempts, delays between them, deadline and cancellation
+    /// follow [`ConnectPolicy`].
     ///
     /// ## Examples
     ///
@@ -129,89 +156,115 @@ impl BBitSensor<Bluetooth> {
     pub async fn map_connect<F>(
         mut self,
         device_id: &str,
+        f: F,
+    ) -> BBitResult<BBitSensor<Configure, T>>
+    where
+        F: FnMut(BBitResult<()>) -> BBitResult<()>,
+    {
+        let target = ConnectTarget::Name(device_id.to_string());
+        let policy = self.level.connect_policy.clone();
+        let res = tokio::select! {
+            res = self.retry_connect(&target, &policy, f) => res,
+            _ = policy.cancel.cancelled() => Err(Error::ConnectCancelled),
+            _ = sleep_until_deadline(policy.deadline) => Err(Error::ConnectTimeout),
+        };
+        if let Err(e) = res {
+            if self.is_connected().await {
+                // connection was interrupted after the link is established
+                let _ = self.transport.disconnect().await;
+            }
+            return Err(e);
+        }
+        Ok(self.into_configure(target))
+    }
+
+    /// Make attempts until connected, `f` is called with every attempt result
+    async fn retry_connect<F>(
+        &mut self,
+        target: &ConnectTarget,
+        policy: &ConnectPolicy,
         mut f: F,
-    ) -> BBitResult<BBitSensor<Configure>>
+    ) -> BBitResult<()>
     where
         F: FnMut(BBitResult<()>) -> BBitResult<()>,
     {
+        let mut attempt = 0;
         while !self.is_connected().await {
-            if let Err(e) = f(self.try_connect(device_id).await) {
-                return Err(e);
+            attempt += 1;
+            debug!("Connection attempt #{attempt}...");
+            f(self.try_connect(target, policy.scan_duration).await)?;
+            if self.is_connected().await {
+                break;
+            }
+            if !policy.has_attempts_after(attempt) {
+                tracing::error!("Stopped connecting attempts after limit !");
+                return Err(Error::ConnectAttemptsExhausted(attempt));
             }
+            tokio::time::sleep(policy.backoff.delay(attempt)).await;
         }
-        let new_self: BBitSensor<Configure> = BBitSensor {
-            ble_manager: self.ble_manager,
-            ble_device: self.ble_device,
+        Ok(())
+    }
+
+    fn into_configure(self, target: ConnectTarget) -> BBitSensor<Configure, T> {
+        BBitSensor {
+            transport: self.transport,
             control_point: self.control_point,
             subscribed_data_event_types: self.subscribed_data_event_types,
-            level: Configure::default(),
+            level: Configure::new(target, self.level.connect_policy),
             device_info: self.device_info,
-        };
-
-        Ok(new_self)
+            profile: self.profile,
+        }
     }
 
     async fn is_connected(&self) -> bool {
-        if let Some(device) = &self.ble_device {
-            if let Ok(v) = device.is_connected().await {
-                return v;
-            }
-        }
-        false
+        self.transport.is_connected().await
     }
 
-    /// Try to connect to a device. Implements the [`crate::BleSensor::connect`] function
+    /// List nearby devices of the profile family without connecting to them
     #[instrument(skip(self))]
-    async fn try_connect(&mut self, device_name: &str) -> BBitResult<()> {
-        debug!("trying to connect to '{device_name}'...");
-        let adapters = self
-            .ble_manager
-            .adapters()
-            .await
-            .map_err(|_| Error::NoBleAdaptor)?;
-        let Some(central) = adapters.first() else {
-            tracing::error!("No ble adaptor found");
-            return Err(Error::NoBleAdaptor);
-        };
-
-        debug!("Start scanning for 2 sec...");
-        let mut scan_filter = ScanFilter::default();
-        scan_filter.services.push(NSS2_SERVICE_UUID);
-        central.start_scan(scan_filter).await?;
-        tokio::time::sleep(Duration::from_secs(2)).await;
-
-        for p in central.peripherals().await? {
-            if p.properties()
-                .await?
-                .unwrap()
-                .local_name
-                .iter()
-                .any(|name| name.starts_with(device_name))
-            {
-                self.ble_device = Some(p);
-                break;
-            }
-        }
+    pub async fn scan(&self, duration: Duration) -> BBitResult<Vec<DiscoveredDevice>> {
+        discovery::scan(&self.transport, self.profile.as_ref(), duration).await
+    }
 
-        let Some(device) = &self.ble_device else {
-            tracing::warn!("Device '{device_name}' is not found !");
-            return Err(Error::NoDevice);
-        };
-        debug!("BLE '{device_name}' is found, try to connect...");
+    /// Connect to a specific device found by [`BBitSensor::scan`], makes one attempt
+    /// scanning for [`ConnectPolicy::scan_duration`]
+    #[instrument(skip(self))]
+    pub async fn connect_to(
+        mut self,
+        target: ConnectTarget,
+    ) -> BBitResult<BBitSensor<Configure, T>> {
+        let scan_duration = self.level.connect_policy.scan_duration;
+        self.try_connect(&target, scan_duration).await?;
+        Ok(self.into_configure(target))
+    }
 
-        device.connect().await?;
-        debug!("Try to discover...");
-        device.discover_services().await?;
+    /// Try to connect to a device. Implements the [`crate::BleSensor::connect`] function
+    #[instrument(skip(self))]
+    async fn try_connect(
+        &mut self,
+        target: &ConnectTarget,
+        scan_duration: Duration,
+    ) -> BBitResult<()> {
+        debug!("trying to connect to '{target:?}'...");
+        connect_target(
+            &self.transport,
+            target,
+            self.profile.as_ref(),
+            scan_duration,
+        )
+        .await?;
 
-        let controller = ControlPoint::new(device).await?;
-        self.control_point = Some(controller);
+        self.control_point = match self.profile.command_uuid() {
+            Some(uuid) => Some(ControlPoint::new(&self.transport, uuid).await?),
+            None => None,
+        };
 
         Ok(())
     }
 }
 
 /// Assign configurable parameters for BBit device
-impl BBitSensor<Configure> {
+impl<T: Transport> BBitSensor<Configure, T> {
     /// Add a data type to listen to
     #[instrument(skip(self))]
     pub fn listen(mut self, event_type: EventType) -> Self {
@@ -236,165 +289,213 @@ impl BBitSensor<Configure> {
         self
     }
 
+    /// Replace EEG packets dropped by BLE link with synthesized ones
+    #[instrument(skip(self))]
+    pub fn fill_gaps(mut self, gap_fill: GapFill) -> Self {
+        self.level.gap_fill = gap_fill;
+        self
+    }
+
+    /// Lead-off current used to compute electrode impedance, amperes,
+    /// [`DEFAULT_LEAD_OFF_CURRENT_A`] by default
+    #[instrument(skip(self))]
+    pub fn lead_off_current(mut self, lead_off_current_a: f64) -> Self {
+        self.level.lead_off_current_a = lead_off_current_a;
+        self
+    }
+
+    /// Reconnect automatically when BLE link is lost during the event loop, enabled by default
+    #[instrument(skip(self))]
+    pub fn reconnect(mut self, enabled: bool) -> Self {
+        self.level.reconnect = enabled;
+        self
+    }
+
+    /// What the event loop does when handler returns error, errors are logged by default
+    #[instrument(skip(self))]
+    pub fn on_handler_error(mut self, policy: HandlerErrorPolicy) -> Self {
+        self.level.handler_error_policy = policy;
+        self
+    }
+
+    /// Shut down the event loop on Ctrl-C, so the device is not left streaming when the
+    /// app is interrupted. Disabled by default, as Ctrl-C doesn't terminate the process
+    /// once it's handled.
+    #[instrument(skip(self))]
+    pub fn stop_on_ctrl_c(mut self, enabled: bool) -> Self {
+        self.level.stop_on_ctrl_c = enabled;
+        self
+    }
+
+    /// Battery thresholds reported to [`EventHandler::battery_update`], smoothing of the level
+    /// and stopping measurement on critical level. Requires [`EventType::State`] to be listened.
+    #[instrument(skip(self))]
+    pub fn battery(mut self, config: BatteryConfig) -> Self {
+        self.level.battery = config;
+        self
+    }
+
+    /// Maximum time to wait for device status confirming a command sent by the event loop
+    #[instrument(skip(self))]
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.level.command_timeout = timeout;
+        self
+    }
+
     /// Produce the sensor ready for build
     #[instrument(skip(self))]
-    pub async fn build(self) -> BBitResult<BBitSensor<EventLoop>> {
+    pub async fn build(mut self) -> BBitResult<BBitSensor<EventLoop, T>> {
         tracing::info!(
             "Building sensor... Make sure measurements from previous connections are stopped."
         );
-        self.stop_measurement().await?;
+        match self.stop_measurement().await {
+            Err(Error::NotSupported(what)) => debug!("Measurement is not stopped, no {what}"),
+            res => res?,
+        }
+        // measurements are checked against device capabilities
+        if let Err(error) = self.device_info().await {
+            tracing::warn!("Could not read device info: {error}");
+        }
         if self.level.eeg_rate {
             debug!("Will subscribe to Resist event...");
             self.subscribe(EventType::EegOrResistance.into()).await?;
         }
         if self.level.device_status {
             debug!("Will subscribe to DeviceStatus event...");
-            self.subscribe_device_status_change().await?;
+            // families without status stream run the event loop with data stream only
+            match self.subscribe_device_status_change().await {
+                Err(Error::NotSupported(what)) => {
+                    tracing::warn!("Device status is not listened, no {what}");
+                    self.subscribed_data_event_types
+                        .retain(|event_type| *event_type != EventType::State);
+                }
+                res => res?,
+            }
         }
 
         Ok(BBitSensor {
-            ble_manager: self.ble_manager,
-            ble_device: self.ble_device,
+            transport: self.transport,
             control_point: self.control_point,
             subscribed_data_event_types: self.subscribed_data_event_types,
-            level: EventLoop,
+            level: EventLoop {
+                gap_fill: self.level.gap_fill,
+                command_timeout: self.level.command_timeout,
+                target: self.level.target,
+                reconnect: self.level.reconnect,
+                connect_policy: self.level.connect_policy,
+                handler_error_policy: self.level.handler_error_policy,
+                stop_on_ctrl_c: self.level.stop_on_ctrl_c,
+                battery: self.level.battery,
+                lead_off_current_a: self.level.lead_off_current_a,
+            },
             device_info: self.device_info,
+            profile: self.profile,
         })
     }
 }
 
-impl BBitSensor<EventLoop> {
+impl<T: Transport> BBitSensor<EventLoop, T> {
+    /// Connect to the device again after BLE link is lost, using the same target as the
+    /// first connection, and subscribe listened streams
+    #[instrument(skip(self))]
+    async fn reconnect(&self) -> BBitResult<()> {
+        let target = &self.level.target;
+        debug!("trying to reconnect to '{target:?}'...");
+        connect_target(
+            &self.transport,
+            target,
+            self.profile.as_ref(),
+            self.level.connect_policy.scan_duration,
+        )
+        .await?;
+        self.subscribe_listened().await;
+        Ok(())
+    }
+
+    /// Start the event loop and receive device events as a [`futures::Stream`]
+    /// instead of [`EventHandler`] callbacks
+    #[instrument(skip_all)]
+    pub async fn into_stream(self) -> SensorStream {
+        let (tx, rx) = mpsc::channel(SENSOR_EVENT_BUFFER);
+        let handle = self.event_loop(StreamHandler::new(tx)).await;
+        SensorStream::new(handle, rx)
+    }
+
     /// Start the event loop
     #[instrument(skip_all)]
-    pub async fn event_loop<H>(
-        self,
-        mut handler: H,
-    ) -> BleHandle where H: EventHandler + Sync + Send + 'static, {
+    pub async fn event_loop<H>(self, handler: H) -> BleHandle
+    where
+        H: EventHandler + Sync + Send + 'static,
+    {
         tracing::info!(
             "starting event_loop... we have event list to subscribe to: {:?}",
             &self.subscribed_data_event_types
         );
 
-        // look for subscribed events
-        for event_type in &self.subscribed_data_event_types {
-            use EventType::*;
-            if let State = event_type {
-                let _ = self.subscribe_device_status_change().await;
-            }
-            if let EegOrResistance = event_type {
-                let _ = self
-                    .subscribe(NotifyStream::EegOrResistanceMeasurement)
-                    .await;
-            }
-        }
+        self.subscribe_listened().await;
         let bt_sensor = Arc::new(self);
         let event_sensor = Arc::clone(&bt_sensor);
 
         tracing::info!("loop - starting bluetooth task");
-        let (bt_tx, mut bt_rx) = mpsc::channel(128);
-        let (pause_tx, pause_rx) = watch::channel(false);
-
-        tokio::task::spawn(async move {
-            let device = bt_sensor.ble_device.as_ref().unwrap();
-            let mut notification_stream = device.notifications().await?;
-
-            while let Some(data) = notification_stream.next().await {
-                tracing::trace!("loop - received bluetooth data: {:02X?}", data);
-                if *pause_rx.borrow() {
-                    debug!("loop paused: ignoring data all data");
-                    continue;
-                }
-                if data.uuid == NotifyUuid::DeviceStateChange.into() {
-                    let result = DeviceStatusData::try_from(data.value);
-                    tracing::trace!("loop - received DeviceStatusData: {result:?}");
-                    match result {
-                        Ok(status_data) => {
-                            let Ok(_) = bt_tx.send(BluetoothEvent::DeviceStatus(status_data)).await
-                            else {
-                                break;
-                            };
-                        }
-                        Err(error) => {
-                            debug!("Error receiving Device Status data: {error:?}");
-                        }
-                    }
-                } else if data.uuid == NotifyUuid::EegOrResistanceMeasurementChange.into() {
-                    let eeg_or_resist_data = data.value;
-                    tracing::trace!(
-                        "loop - received eeg-resist_data: {:02X?}",
-                        eeg_or_resist_data
-                    );
-                    let Ok(_) = bt_tx
-                        .send(BluetoothEvent::EggOrResistanceData(eeg_or_resist_data))
-                        .await
-                    else {
-                        break;
-                    };
-                }
-            }
+        let (bt_tx, bt_rx) = mpsc::channel(BLUETOOTH_EVENT_BUFFER);
+        let pause_tx = Arc::new(watch::channel(false).0);
+        let (hub, _) = broadcast::channel(SUBSCRIBER_BUFFER);
+        let (outcome_tx, outcome_rx) = watch::channel(None);
 
-            Ok::<_, Error>(())
-        });
+        let bt_task = tokio::task::spawn(bluetooth_task(bt_sensor, bt_tx));
 
         tracing::info!("starting event task");
-        let (event_tx, mut event_rx) = mpsc::channel(4);
+        let (event_tx, event_rx) = mpsc::channel(4);
+        let event_task = EventTask {
+            acknowledge: event_sensor
+                .subscribed_data_event_types
+                .contains(&EventType::State),
+            command_timeout: event_sensor.level.command_timeout,
+            handler_error_policy: event_sensor.level.handler_error_policy,
+            stop_on_ctrl_c: event_sensor.level.stop_on_ctrl_c,
+            bt_task: Some(bt_task),
+            pause: Arc::clone(&pause_tx),
+            outcome: None,
+            measurement: MeasurementState::new(
+                event_sensor.level.gap_fill,
+                Arc::clone(&event_sensor.profile),
+            )
+            .with_lead_off_current(event_sensor.level.lead_off_current_a),
+            battery: BatteryMonitor::new(event_sensor.level.battery),
+            sensor: event_sensor,
+            handler: FanoutHandler::new(handler, hub.clone()),
+            bt_rx,
+            sweep: None,
+            readings: VecDeque::new(),
+            last_status: None,
+            battery_stop: false,
+            restore_measurement: false,
+        };
         tokio::task::spawn(async move {
-            loop {
-                // either BLE messages or commands comes
-                tokio::select! {
-                    Some(data) = bt_rx.recv() => {
-                        debug!("received bt channel message: {:02X?}", data);
-                        use BluetoothEvent::*;
-                        match data {
-                            DeviceStatus(status_data) => handler.device_status_update(status_data).await,
-                            EggOrResistanceData(eeg_data) => handler.eeg_update(eeg_data).await,
-                        }
-                    }
-                    Some(event) = event_rx.recv() => {
-                        debug!("received event: {:02x?}", event);
-                        match event {
-                            BleDeviceEvent::Stop => {
-                                let res = event_sensor.stop_measurement().await;
-                                debug!("Stop Signal?: {res:?}");
-                                break;
-                            },
-                            BleDeviceEvent::StartSignal{ret} => {
-                                let res = event_sensor.start_measurement(MeasurementType::Eeg).await;
-                                debug!("Started Signal Measurement?: {res:?}");
-                                let _ = ret.send(res);
-                            },
-                            BleDeviceEvent::StartResistance{channel_type, ret} => {
-                                let res = event_sensor.start_measurement(
-                                    MeasurementType::Resistance(channel_type)).await;
-                                debug!("Started Resists Measurement?: {res:?}");
-                                let _ = ret.send(res);
-                            },
-                        }
-                    }
-                    else => {
-                        break;
-                    }
-                }
-            }
+            let outcome = event_task.run(event_rx).await;
+            tracing::info!("event loop is finished: {outcome:?}");
+            let _ = outcome_tx.send(Some(outcome));
         });
 
-        BleHandle::new(event_tx, pause_tx)
+        BleHandle::new(event_tx, pause_tx, hub, outcome_rx)
     }
 }
 
-impl<L: Level + Connected> BBitSensor<L> {
+impl<L: Level + Connected, T: Transport> BBitSensor<L, T> {
+    /// Characteristic of the stream in the profile of device
+    fn notify_uuid(&self, notify_stream: NotifyStream) -> BBitResult<Uuid> {
+        self.profile.notify_uuid(notify_stream).ok_or_else(|| {
+            Error::NotSupported(format!("{notify_stream:?} of {}", self.profile.name()))
+        })
+    }
+
     #[instrument(skip(self))]
     async fn subscribe(&self, notify_stream: NotifyStream) -> BBitResult<()> {
         tracing::info!("subscribing to stream of '{:#?}' type...", notify_stream);
-        let device = self.ble_device.as_ref().expect("device already connected");
-
-        let characteristics = device.characteristics();
-        let characteristic = characteristics
-            .iter()
-            .find(|c| c.uuid == notify_stream.into())
-            .ok_or(Error::CharacteristicNotFound)?;
+        let uuid = self.notify_uuid(notify_stream)?;
+        let characteristic = find_characteristic(&self.transport, uuid).await?;
 
-        device.subscribe(&characteristic).await?;
+        self.transport.subscribe(&characteristic).await?;
         debug!("DONE, subscribed to stream of '{:?}' type", notify_stream);
         Ok(())
     }
@@ -402,15 +503,10 @@ impl<L: Level + Connected> BBitSensor<L> {
     #[instrument(skip(self))]
     async fn unsubscribe(&self, notify_stream: NotifyStream) -> BBitResult<()> {
         tracing::info!("unsubscribing from stream of '{notify_stream:?} type...'");
-        let device = self.ble_device.as_ref().unwrap();
-
-        let characteristics = device.characteristics();
-        let characteristic = characteristics
-            .iter()
-            .find(|c| c.uuid == notify_stream.into())
-            .ok_or(Error::CharacteristicNotFound)?;
+        let uuid = self.notify_uuid(notify_stream)?;
+        let characteristic = find_characteristic(&self.transport, uuid).await?;
 
-        device.unsubscribe(&characteristic).await?;
+        self.transport.unsubscribe(&characteristic).await?;
         debug!(
             "DONE, unsubscribed from stream of '{:?}' type",
             notify_stream
@@ -419,27 +515,45 @@ impl<L: Level + Connected> BBitSensor<L> {
         Ok(())
     }
 
+    /// GATT layout and data format of the connected device family
+    pub fn profile(&self) -> &dyn DeviceProfile {
+        self.profile.as_ref()
+    }
+
     /// Fetch all characteristics of the device
     pub fn characteristics(&self) -> BTreeSet<Characteristic> {
-        let device = self.ble_device.as_ref().unwrap();
-        device.characteristics()
+        self.transport.characteristics()
+    }
+
+    /// Subscribe all streams added by [`BBitSensor::listen`]
+    async fn subscribe_listened(&self) {
+        for event_type in &self.subscribed_data_event_types {
+            use EventType::*;
+            if let State = event_type {
+                let _ = self.subscribe_device_status_change().await;
+            }
+            if let EegOrResistance = event_type {
+                let _ = self
+                    .subscribe(NotifyStream::EegOrResistanceMeasurement)
+                    .await;
+            }
+        }
+    }
+
+    /// Unsubscribe streams of listened event types, errors are logged
+    async fn unsubscribe_listened(&self) {
+        for event_type in &self.subscribed_data_event_types {
+            if let Err(error) = self.unsubscribe(NotifyStream::from(*event_type)).await {
+                tracing::warn!("Could not unsubscribe '{event_type:?}': {error}");
+            }
+        }
     }
 
     /// Read the battery level of the device
     #[instrument(skip_all)]
     pub async fn subscribe_device_status_change(&self) -> BBitResult<()> {
         tracing::info!("Subscribe device status changes, including cmd error, battery level");
-        let device = self.ble_device.as_ref().unwrap();
-
-        let characteristics = device.characteristics();
-        let characteristic = characteristics
-            .iter()
-            .find(|c| c.uuid == NotifyStream::from(EventType::State).into())
-            .ok_or(Error::CharacteristicNotFound)?;
-
-        device.subscribe(&characteristic).await?;
-
-        Ok(())
+        self.subscribe(NotifyStream::DeviceState).await
     }
 
     /// Read the internal device info - model, serial, SW, HW revision
@@ -448,10 +562,10 @@ impl<L: Level + Connected> BBitSensor<L> {
         tracing::info!("fetching device info...");
         // on time initialization
         if self.device_info.get().is_none() {
-            let model_number = self.read_string(MODEL_NUMBER_STRING_UUID).await?;
-            let serial_number = self.read_string(SERIAL_NUMBER_STRING_UUID).await?;
-            let hardware_revision = self.read_string(HARDWARE_REVISION_STRING_UUID).await?;
-            let firmware_revision = self.read_string(FIRMWARE_REVISION_STRING_UUID).await?;
+            let model_number = self.read_info(StringUuid::ModelNumber).await?;
+            let serial_number = self.read_info(StringUuid::SerialNumber).await?;
+            let hardware_revision = self.read_info(StringUuid::HardwareRevision).await?;
+            let firmware_revision = self.read_info(StringUuid::FirmwareRevision).await?;
             let device_info = DeviceInfo::new(
                 model_number,
                 serial_number,
@@ -464,6 +578,14 @@ impl<L: Level + Connected> BBitSensor<L> {
         Ok(self.device_info.get().unwrap().clone())
     }
 
+    /// Read device information string, it's empty when the profile doesn't provide it
+    async fn read_info(&self, string: StringUuid) -> BBitResult<String> {
+        match self.profile.string_uuid(string) {
+            Some(uuid) => self.read_string(uuid).await,
+            None => Ok(String::new()),
+        }
+    }
+
     /// low level reading bytes as String
     async fn read_string(&self, uuid: Uuid) -> BBitResult<String> {
         let data = self.read(uuid).await?;
@@ -473,10 +595,8 @@ impl<L: Level + Connected> BBitSensor<L> {
     }
 
     async fn read(&self, uuid: Uuid) -> BBitResult<Vec<u8>> {
-        let device = self.ble_device.as_ref().unwrap();
-        // let device = self.device().await?;
-        if let Ok(char) = find_characteristic(device, uuid).await {
-            return device.read(&char).await.map_err(Error::BleError);
+        if let Ok(char) = find_characteristic(&self.transport, uuid).await {
+            return self.transport.read(&char).await;
         }
         Err(Error::CharacteristicNotFound)
     }
@@ -484,97 +604,698 @@ impl<L: Level + Connected> BBitSensor<L> {
     /// Send command as enum to [`ControlPoint`].
     #[instrument(skip(self))]
     pub async fn send_command(&self, command: ControlPointCommand) -> BBitResult<()> {
-        let control_point = self.control_point.as_ref().unwrap();
-        let device = self.ble_device.as_ref().unwrap();
-
-        control_point
-            .send_control_command_enum(device, command)
-            .await?;
-        Ok(())
+        let control_point = self
+            .control_point
+            .as_ref()
+            .ok_or_else(|| Error::NotSupported(format!("commands of {}", self.profile.name())))?;
+        let data = self.profile.encode_command(&command)?;
+        debug!("Send control command to sensor: {command:?}");
+        control_point.send_command(&self.transport, &data).await
     }
 
     /// Stop any type of possible measurement
     #[instrument(skip(self))]
     async fn stop_measurement(&self) -> BBitResult<()> {
         debug!("Stopping any measurement...");
-        let controller = self.control_point.as_ref().unwrap();
-        let device = self.ble_device.as_ref().unwrap();
         let command = ControlPointCommand::new(ControlCommandType::StopAll, None);
-        controller
-            .send_control_command_enum(&device, command)
-            .await?;
-        Ok(())
+        self.send_command(command).await
     }
+}
 
-    /// We start measurement (resistance OR eeg) by sending command for one EEG channel and collecting
-    /// returned data.
-    #[instrument(skip(self))]
-    async fn start_measurement(&self, measure_type: MeasurementType) -> BBitResult<()> {
+/// Processing of EEG/resistance packets inside the event loop and session replay
+pub(crate) struct MeasurementState {
+    /// How dropped EEG packets are replaced
+    gap_fill: GapFill,
+    /// Decoding of the device family packets
+    profile: Arc<dyn DeviceProfile>,
+    /// Decoder of EEG packets, it's present while EEG measurement is running
+    eeg_decoder: Option<EegDecoder>,
+    /// Decoder of resistance packets, it's present while resistance measurement is running
+    resistance_decoder: Option<ResistanceDecoder>,
+    /// Lead-off current used to compute electrode impedance
+    lead_off_current_a: f64,
+    /// Running measurement, it's restarted after reconnection
+    measure_type: Option<MeasurementType>,
+    packet_tracker: PacketTracker,
+    /// Last decoded packet, used for gap interpolation
+    last_packet: Option<EegPacket>,
+}
+
+impl MeasurementState {
+    pub(crate) fn new(gap_fill: GapFill, profile: Arc<dyn DeviceProfile>) -> Self {
+        Self {
+            gap_fill,
+            profile,
+            eeg_decoder: None,
+            resistance_decoder: None,
+            lead_off_current_a: DEFAULT_LEAD_OFF_CURRENT_A,
+            measure_type: None,
+            packet_tracker: PacketTracker::new(),
+            last_packet: None,
+        }
+    }
+
+    /// Compute electrode impedance for specified lead-off current
+    pub(crate) fn with_lead_off_current(mut self, lead_off_current_a: f64) -> Self {
+        self.lead_off_current_a = lead_off_current_a;
+        self
+    }
+
+    /// Forget previous measurement data, called when a measurement is started or stopped
+    fn restart(&mut self, measure_type: Option<MeasurementType>) {
+        self.measure_type = measure_type;
+        let command = measure_type.map(measurement_command);
+        self.reset_decoders(command.as_ref());
+    }
+
+    /// Prepare decoding of packets transmitted after the command, `None` stops decoding
+    pub(crate) fn reset_decoders(&mut self, command: Option<&ControlPointCommand>) {
+        self.eeg_decoder = command.and_then(|command| EegDecoder::from_command(command).ok());
+        self.resistance_decoder = command
+            .and_then(|command| ResistanceDecoder::from_command(command).ok())
+            .map(|decoder| decoder.with_lead_off_current(self.lead_off_current_a));
+        self.packet_tracker.reset();
+        self.last_packet = None;
+    }
+
+    /// Track, decode and pass received packet to handler, returns decoded resistance reading.
+    /// Duplicated packets, and reordered ones whose place was already filled, are not passed as
+    /// decoded EEG packets. Handler error interrupts passing of the packet.
+    pub(crate) async fn dispatch<H: EventHandler + Send>(
+        &mut self,
+        handler: &mut H,
+        data: Vec<u8>,
+    ) -> Result<Option<ResistanceReading>, HandlerError> {
+        let gap = self
+            .profile
+            .packet_counter(&data)
+            .ok()
+            .and_then(|counter| self.packet_tracker.track(counter));
+        let packet = self
+            .eeg_decoder
+            .as_ref()
+            .map(|decoder| self.profile.decode_eeg(decoder, &data));
+        let reading = self
+            .resistance_decoder
+            .as_ref()
+            .map(|decoder| self.profile.decode_resistance(decoder, &data));
+        handler.eeg_update(data).await?;
+        if let Some(gap) = gap {
+            tracing::warn!("EEG packets sequence gap: {gap:?}");
+            handler.packet_loss_update(gap).await?;
+        }
+        // duplicates and packets already replaced by a fill would repeat data downstream
+        let repeated = gap.is_some_and(|gap| match gap.kind {
+            PacketGapKind::Dropped => false,
+            PacketGapKind::Duplicated => true,
+            PacketGapKind::Reordered => self.gap_fill != GapFill::None,
+        });
+        match packet {
+            Some(Ok(_)) if repeated => debug!("Skipping repeated EEG packet: {gap:?}"),
+            Some(Ok(packet)) => {
+                if let Some(gap) = gap.filter(|gap| gap.kind == PacketGapKind::Dropped) {
+                    let filled =
+                        self.gap_fill
+                            .fill(self.last_packet.as_ref(), &packet, gap.lost_packets);
+                    for filled_packet in filled {
+                        handler.eeg_packet_update(filled_packet).await?;
+                    }
+                }
+                self.last_packet = Some(packet.clone());
+                handler.eeg_packet_update(packet).await?;
+            }
+            Some(Err(error)) => debug!("Error decoding EEG packet: {error:?}"),
+            None => {}
+        }
+        match reading {
+            Some(Ok(reading)) => {
+                handler.resistance_update(reading).await?;
+                Ok(Some(reading))
+            }
+            Some(Err(error)) => {
+                debug!("Error decoding resistance packet: {error:?}");
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Wait for Ctrl-C if `enabled`, never wakes up otherwise
+async fn wait_ctrl_c(enabled: bool) {
+    if !enabled || tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Sleep until `deadline` passes from now, never wakes up without deadline
+async fn sleep_until_deadline(deadline: Option<Duration>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Forward notifications to the event task. When BLE link is lost and reconnection is enabled,
+/// it supervises the reconnection and restores subscriptions.
+async fn bluetooth_task<T: Transport>(
+    sensor: Arc<BBitSensor<EventLoop, T>>,
+    bt_tx: mpsc::Sender<BluetoothEvent>,
+) -> BBitResult<()> {
+    let mut dropped = 0u64;
+    loop {
+        let mut disconnections = sensor.transport.disconnections().await?;
+        let mut notification_stream = sensor.transport.notifications().await?;
+        loop {
+            tokio::select! {
+                data = notification_stream.next() => {
+                    let Some(data) = data else {
+                        break;
+                    };
+                    tracing::trace!("loop - received bluetooth data: {:02X?}", data);
+                    let Some(event) = BluetoothEvent::from_notification(data, sensor.profile()) else {
+                        continue;
+                    };
+                    match bt_tx.try_send(event) {
+                        Ok(()) => {}
+                        Err(mpsc::error::TrySendError::Full(_)) => {
+                            // don't block notifications, lost EEG packets are reported by counter
+                            dropped += 1;
+                            tracing::warn!("loop - event task is busy, dropped {dropped} notifications");
+                        }
+                        Err(mpsc::error::TrySendError::Closed(_)) => return Ok(()),
+                    }
+                }
+                Some(_) = disconnections.next() => break,
+            }
+        }
+        if bt_tx.is_closed() {
+            return Ok(());
+        }
+        if !sensor.level.reconnect {
+            tracing::warn!("loop - BLE link is lost");
+            let _ = bt_tx.send(BluetoothEvent::Disconnected).await;
+            return Ok(());
+        }
+
+        tracing::warn!("loop - BLE link is lost, reconnecting...");
+        if bt_tx.send(BluetoothEvent::Disconnected).await.is_err() {
+            return Ok(());
+        }
+        let policy = &sensor.level.connect_policy;
+        let res = tokio::select! {
+            res = retry_reconnect(&sensor) => res,
+            _ = policy.cancel.cancelled() => Err(Error::ConnectCancelled),
+            _ = sleep_until_deadline(policy.deadline) => Err(Error::ConnectTimeout),
+            _ = bt_tx.closed() => return Ok(()),
+        };
+        if let Err(error) = res {
+            tracing::error!("loop - gave up reconnecting: {error}");
+            let _ = bt_tx.send(BluetoothEvent::ReconnectFailed).await;
+            return Err(error);
+        }
+        tracing::info!("loop - BLE link is restored");
+        if bt_tx.send(BluetoothEvent::Reconnected).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Make reconnection attempts until connected or [`ConnectPolicy`] attempts are exhausted
+async fn retry_reconnect<T: Transport>(sensor: &BBitSensor<EventLoop, T>) -> BBitResult<()> {
+    let policy = &sensor.level.connect_policy;
+    let mut attempt = 0;
+    while let Err(error) = sensor.reconnect().await {
+        attempt += 1;
+        tracing::warn!("loop - could not reconnect on attempt #{attempt}, error: {error}");
+        if !policy.has_attempts_after(attempt) {
+            return Err(Error::ConnectAttemptsExhausted(attempt));
+        }
+        tokio::time::sleep(policy.backoff.delay(attempt)).await;
+    }
+    Ok(())
+}
+
+/// Resistance sweep requested by [`BleHandle::measure_resistance`] and run by the event loop
+struct RunningSweep {
+    sweep: ResistanceSweep,
+    /// Time when waiting for the current channel readings is aborted
+    deadline: Instant,
+    /// channel to receive sweep result
+    ret: oneshot::Sender<BBitResult<ResistState>>,
+}
+
+/// Task owning the handler: it dispatches bluetooth data and executes [`BleHandle`] requests
+struct EventTask<T: Transport, H> {
+    sensor: Arc<BBitSensor<EventLoop, T>>,
+    handler: H,
+    bt_rx: mpsc::Receiver<BluetoothEvent>,
+    measurement: MeasurementState,
+    sweep: Option<RunningSweep>,
+    /// Resistance readings for the running sweep, they also come while a command is executed
+    readings: VecDeque<ResistanceReading>,
+    /// Last received device status
+    last_status: Option<DeviceStatusData>,
+    /// Battery level trend and thresholds
+    battery: BatteryMonitor,
+    /// Battery level is critical, running measurement should be stopped
+    battery_stop: bool,
+    /// BLE link is restored, running measurement should be started again
+    restore_measurement: bool,
+    /// Commands are confirmed by device status, it requires subscription to status changes
+    acknowledge: bool,
+    /// Maximum time to wait for command confirmation
+    command_timeout: Duration,
+    /// What to do when handler returns error
+    handler_error_policy: HandlerErrorPolicy,
+    /// Pause of handling bluetooth events shared with [`BleHandle`]
+    pause: Arc<watch::Sender<bool>>,
+    /// Shut down on Ctrl-C
+    stop_on_ctrl_c: bool,
+    /// Bluetooth task forwarding notifications, it's finished on shutdown
+    bt_task: Option<JoinHandle<BBitResult<()>>>,
+    /// Set when the loop should finish
+    outcome: Option<LoopOutcome>,
+}
+
+impl<T: Transport, H: EventHandler + Send + Sync> EventTask<T, H> {
+    async fn run(mut self, mut event_rx: mpsc::Receiver<BleDeviceEvent>) -> LoopOutcome {
+        let ctrl_c = wait_ctrl_c(self.stop_on_ctrl_c);
+        tokio::pin!(ctrl_c);
+        loop {
+            if self.outcome.is_none() && !self.handler.should_continue().await {
+                tracing::info!("Handler finished the event loop");
+                self.outcome = Some(LoopOutcome::Finished);
+            }
+            if let Some(outcome) = self.outcome.take() {
+                self.shutdown().await;
+                return outcome;
+            }
+            self.advance_sweep().await;
+            if std::mem::take(&mut self.restore_measurement) {
+                self.restore_measurement().await;
+            }
+            if std::mem::take(&mut self.battery_stop) && self.measurement.measure_type.is_some() {
+                tracing::warn!("Battery level is critical, stopping measurement");
+                self.sweep = None;
+                let res = self.stop_measurement().await;
+                debug!("Stopped measurement on critical battery?: {res:?}");
+            }
+            let sweep_deadline = self
+                .sweep
+                .as_ref()
+                .map_or_else(Instant::now, |running| running.deadline);
+            // either BLE messages or commands comes
+            tokio::select! {
+                Some(data) = self.bt_rx.recv() => {
+                    debug!("received bt channel message: {:02X?}", data);
+                    self.on_bluetooth(data).await;
+                }
+                _ = tokio::time::sleep_until(sweep_deadline), if self.sweep.is_some() => {
+                    let channel_type = self.sweep.as_ref().unwrap().sweep.channel();
+                    tracing::warn!("No resistance data on {channel_type:?}, sweep is aborted");
+                    self.finish_sweep(Err(Error::NoResistanceData(channel_type))).await;
+                }
+                event = event_rx.recv() => {
+                    debug!("received event: {:02x?}", event);
+                    let Some(event) = event else {
+                        debug!("all handles are dropped, shutting down");
+                        self.outcome = Some(LoopOutcome::Stopped);
+                        continue;
+                    };
+                    if !self.on_event(event).await {
+                        self.outcome = Some(LoopOutcome::Stopped);
+                    }
+                }
+                _ = &mut ctrl_c => {
+                    tracing::info!("Ctrl-C is received, shutting down");
+                    self.outcome = Some(LoopOutcome::Interrupted);
+                }
+            }
+        }
+    }
+
+    /// Stop measurement, unsubscribe listened streams and close BLE connection, then flush
+    /// the handler. Bluetooth task is finished before disconnection, so it doesn't reconnect.
+    async fn shutdown(&mut self) {
+        self.sweep = None;
+        if self.sensor.transport.is_connected().await {
+            let res = self.stop_measurement().await;
+            debug!("Stopped measurement on shutdown?: {res:?}");
+            self.sensor.unsubscribe_listened().await;
+        }
+        if let Some(bt_task) = self.bt_task.take() {
+            bt_task.abort();
+            let _ = bt_task.await;
+        }
+        if self.sensor.transport.is_connected().await {
+            if let Err(error) = self.sensor.transport.disconnect().await {
+                tracing::warn!("Could not disconnect: {error}");
+            }
+        }
+        if let Err(error) = self.handler.flush().await {
+            tracing::error!("Could not flush handler: {error}");
+        }
+    }
+
+    /// Apply [`HandlerErrorPolicy`] to result of handler callback
+    fn on_handler_result(&mut self, res: HandlerResult) {
+        let Err(error) = res else {
+            return;
+        };
+        tracing::error!("Handler error: {error}");
+        match self.handler_error_policy {
+            HandlerErrorPolicy::LogAndContinue => {}
+            HandlerErrorPolicy::Pause => {
+                tracing::warn!("Bluetooth events handling is paused after handler error");
+                let _ = self.pause.send(true);
+            }
+            HandlerErrorPolicy::Stop => {
+                self.outcome
+                    .get_or_insert(LoopOutcome::HandlerError(Arc::new(Error::from(error))));
+            }
+        }
+    }
+
+    /// Pass bluetooth data to handler, decoded resistance readings are queued for the sweep
+    async fn on_bluetooth(&mut self, data: BluetoothEvent) {
+        use BluetoothEvent::*;
+        if *self.pause.borrow() {
+            // device status is still tracked to confirm commands, link changes are handled
+            match data {
+                DeviceStatus(status_data) => {
+                    debug!("loop paused: device status is tracked, but not passed to handler");
+                    self.last_status = Some(status_data);
+                    return;
+                }
+                EggOrResistanceData(_) => return,
+                Disconnected | Reconnected | ReconnectFailed => {}
+            }
+        }
+        match data {
+            DeviceStatus(status_data) => {
+                self.last_status = Some(status_data);
+                let res = self.handler.device_status_update(status_data).await;
+                self.on_handler_result(res);
+                self.on_battery(status_data).await;
+            }
+            EggOrResistanceData(eeg_data) => {
+                let res = self.measurement.dispatch(&mut self.handler, eeg_data).await;
+                match res {
+                    Ok(reading) => self.readings.extend(reading),
+                    Err(error) => self.on_handler_result(Err(error)),
+                }
+            }
+            Disconnected => {
+                let res = self
+                    .handler
+                    .connection_update(ConnectionEvent::Disconnected)
+                    .await;
+                self.on_handler_result(res);
+            }
+            Reconnected => {
+                self.restore_measurement = true;
+                let res = self
+                    .handler
+                    .connection_update(ConnectionEvent::Reconnected)
+                    .await;
+                self.on_handler_result(res);
+            }
+            ReconnectFailed => {
+                let res = self
+                    .handler
+                    .connection_update(ConnectionEvent::ReconnectFailed)
+                    .await;
+                self.on_handler_result(res);
+            }
+        }
+    }
+
+    /// Track battery level of device status, pass its changes to handler
+    async fn on_battery(&mut self, status_data: DeviceStatusData) {
+        let Some(battery) = self.battery.update(&status_data, Instant::now()) else {
+            return;
+        };
+        if let Some(alert) = battery.alert {
+            tracing::warn!(
+                "Battery level alert {alert:?}: {:.1}%",
+                battery.smoothed_level
+            );
+        }
+        if battery.alert == Some(BatteryAlert::Critical) && self.battery.config().stop_on_critical {
+            self.battery_stop = true;
+        }
+        let res = self.handler.battery_update(battery).await;
+        self.on_handler_result(res);
+    }
+
+    /// Start again the measurement which was running when BLE link was lost
+    async fn restore_measurement(&mut self) {
+        let Some(measure_type) = self.measurement.measure_type else {
+            return;
+        };
+        let res = self.start_measurement(measure_type).await;
+        debug!("Restored '{measure_type:?}' measurement?: {res:?}");
+        if let Err(error) = res {
+            tracing::warn!("Could not restore '{measure_type:?}' measurement: {error}");
+            self.measurement.measure_type = Some(measure_type);
+        }
+    }
+
+    /// Execute request from [`BleHandle`], returns false when the loop should finish
+    async fn on_event(&mut self, event: BleDeviceEvent) -> bool {
+        match event {
+            BleDeviceEvent::Stop => {
+                debug!("Stop Signal");
+                return false;
+            }
+            BleDeviceEvent::StopMeasurement { ret } => {
+                self.sweep = None;
+                let res = self.stop_measurement().await;
+                debug!("Stopped Measurement?: {res:?}");
+                let _ = ret.send(res);
+            }
+            BleDeviceEvent::StartSignal { config, ret } => {
+                self.sweep = None;
+                let res = self.start_measurement(MeasurementType::Eeg(config)).await;
+                debug!("Started Signal Measurement?: {res:?}");
+                let _ = ret.send(res);
+            }
+            BleDeviceEvent::StartResistance { channel_type, ret } => {
+                self.sweep = None;
+                let res = self
+                    .start_measurement(MeasurementType::Resistance(channel_type))
+                    .await;
+                debug!("Started Resists Measurement?: {res:?}");
+                let _ = ret.send(res);
+            }
+            BleDeviceEvent::MeasureResistance { config, ret } => {
+                let sweep = ResistanceSweep::new(config);
+                let channel_type = sweep.channel();
+                let res = self
+                    .start_measurement(MeasurementType::Resistance(channel_type))
+                    .await;
+                debug!("Started Resistance Sweep?: {res:?}");
+                match res {
+                    Ok(_) => {
+                        self.sweep = Some(RunningSweep {
+                            sweep,
+                            deadline: Instant::now() + config.channel_timeout,
+                            ret,
+                        })
+                    }
+                    Err(error) => {
+                        let _ = ret.send(Err(error));
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Send command to device and wait until device status confirms it, then pass it to
+    /// handler.
+    ///
+    /// Bluetooth data received meanwhile is passed to handler as usual. Device keeps reporting
+    /// the error of a previous command in periodic status, so the same error is not treated as
+    /// a response to the new command.
+    async fn execute(&mut self, command: ControlPointCommand) -> BBitResult<()> {
+        let expected = command.cmd_type.expected_status();
+        let previous_state = self
+            .last_status
+            .map(|status| (status.status_nss2, status.cmd_error));
+        self.sensor.send_command(command.clone()).await?;
+        let res = match expected.filter(|_| self.acknowledge) {
+            Some(expected) => self.wait_confirmation(expected, previous_state).await,
+            None => Ok(()),
+        };
+        let command_data = CommandData {
+            command,
+            confirmed: res.is_ok() && self.acknowledge,
+        };
+        let handler_res = self.handler.send_command(command_data).await;
+        self.on_handler_result(handler_res);
+        res
+    }
+
+    /// Pass bluetooth data to handler until device status reports `expected` state
+    async fn wait_confirmation(
+        &mut self,
+        expected: Nss2Status,
+        previous_state: Option<(Nss2Status, CommandExecutionState)>,
+    ) -> BBitResult<()> {
+        let deadline = Instant::now() + self.command_timeout;
+        loop {
+            let data = match tokio::time::timeout_at(deadline, self.bt_rx.recv()).await {
+                Ok(Some(data)) => data,
+                Ok(None) => return Err(Error::NotConnected),
+                Err(_) => {
+                    tracing::warn!("Device has not confirmed '{expected:?}' status in time");
+                    return Err(Error::NoControlPointResponse);
+                }
+            };
+            if let BluetoothEvent::DeviceStatus(status_data) = data {
+                self.on_bluetooth(data).await;
+                let state = (status_data.status_nss2, status_data.cmd_error);
+                if status_data.cmd_error != CommandExecutionState::Ok
+                    && previous_state != Some(state)
+                {
+                    return Err(Error::CommandRejected(status_data.cmd_error));
+                }
+                if status_data.status_nss2 == expected {
+                    debug!("Device confirmed '{expected:?}' status");
+                    return Ok(());
+                }
+            } else {
+                self.on_bluetooth(data).await;
+            }
+        }
+    }
+
+    /// Start measurement and prepare decoding of its packets
+    async fn start_measurement(&mut self, measure_type: MeasurementType) -> BBitResult<()> {
         debug!("Starting an '{measure_type:?}' measurement...");
-        let controller = self.control_point.as_ref().unwrap();
-        let device = self.ble_device.as_ref().unwrap();
-        let command: ControlPointCommand = match measure_type {
-            MeasurementType::Resistance(ChannelType::O1) => {
-                let cmd_data = [
-                    ADS1294ChannelInput::PowerDownGain3.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    0b00000001,
-                    0x01,
-                    0x0,
-                ];
-                ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
-            }
-            MeasurementType::Resistance(ChannelType::T3) => {
-                let cmd_data = [
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerDownGain3.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    0b00000010,
-                    0x03,
-                    0x0,
-                ];
-                ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
-            }
-            MeasurementType::Resistance(ChannelType::T4) => {
-                let cmd_data = [
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerDownGain3.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    0b00000100,
-                    0x05,
-                    0x0,
-                ];
-                ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
-            }
-            MeasurementType::Resistance(ChannelType::O2) => {
-                let cmd_data = [
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerUpGain1.into(),
-                    ADS1294ChannelInput::PowerDownGain3.into(),
-                    0b0001000,
-                    0b0001000,
-                    0x0,
-                ];
-                ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
-            }
-            MeasurementType::Eeg => {
-                let cmd_data = [ADS1294ChannelInput::PowerDownGain6.into(), 0x00, 0x00, 0x0];
-                ControlPointCommand::new(
-                    ControlCommandType::StartEegSignal,
-                    Some(Vec::from(cmd_data)),
-                )
+        if let Some(device_info) = self.sensor.device_info.get() {
+            device_info.capabilities().check(&measure_type)?;
+        }
+        let res = self.execute(measurement_command(measure_type)).await;
+        self.measurement
+            .restart(res.as_ref().ok().map(|_| measure_type));
+        res
+    }
+
+    /// Stop any type of possible measurement
+    async fn stop_measurement(&mut self) -> BBitResult<()> {
+        debug!("Stopping any measurement...");
+        let res = self
+            .execute(ControlPointCommand::new(ControlCommandType::StopAll, None))
+            .await;
+        self.measurement.restart(None);
+        res
+    }
+
+    /// Account queued readings by running sweep and switch measured channel if needed.
+    ///
+    /// Device keeps reporting resistance transmission of the previous channel until it executes
+    /// the switch, so measurement is stopped first: once device reports stopped transmission,
+    /// the next resistance status and packets belong to the new channel. Readings received
+    /// before the switch is confirmed are dropped.
+    async fn advance_sweep(&mut self) {
+        while let Some(reading) = self.readings.pop_front() {
+            let Some(running) = self.sweep.as_mut() else {
+                self.readings.clear();
+                return;
+            };
+            match running.sweep.push(reading) {
+                SweepStep::Continue => {}
+                SweepStep::NextChannel(channel_type) => {
+                    debug!("Resistance sweep switches to {channel_type:?}");
+                    running.deadline = Instant::now() + running.sweep.config().channel_timeout;
+                    let mut res = self.stop_measurement().await;
+                    if res.is_ok() {
+                        res = self
+                            .start_measurement(MeasurementType::Resistance(channel_type))
+                            .await;
+                    }
+                    self.readings.clear();
+                    if let Err(error) = res {
+                        self.finish_sweep(Err(error)).await;
+                    }
+                }
+                SweepStep::Done(state) => self.finish_sweep(Ok(state)).await,
             }
+        }
+    }
+
+    /// Stop resistance measurement and return the sweep result
+    async fn finish_sweep(&mut self, result: BBitResult<ResistState>) {
+        let Some(running) = self.sweep.take() else {
+            return;
         };
-        controller
-            .send_control_command_enum(&device, command)
-            .await?;
-        debug!("DONE. Started an '{measure_type:?}' measurement");
-        Ok(())
+        let res = self.stop_measurement().await;
+        debug!("Stopped Resistance Sweep?: {res:?}, result: {result:?}");
+        let _ = running.ret.send(result);
+    }
+}
+
+/// Build device command starting measurement of specified type
+pub(crate) fn measurement_command(measure_type: MeasurementType) -> ControlPointCommand {
+    match measure_type {
+        MeasurementType::Resistance(ChannelType::O1) => {
+            let cmd_data = [
+                ADS1294ChannelInput::PowerDownGain1Shorted.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                0b00000001,
+                0x01,
+                0x0,
+            ];
+            ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
+        }
+        MeasurementType::Resistance(ChannelType::T3) => {
+            let cmd_data = [
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerDownGain1Shorted.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                0b00000010,
+                0x03,
+                0x0,
+            ];
+            ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
+        }
+        MeasurementType::Resistance(ChannelType::T4) => {
+            let cmd_data = [
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerDownGain1Shorted.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                0b00000100,
+                0x05,
+                0x0,
+            ];
+            ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
+        }
+        MeasurementType::Resistance(ChannelType::O2) => {
+            let cmd_data = [
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerUpGain4.into(),
+                ADS1294ChannelInput::PowerDownGain1Shorted.into(),
+                0b0001000,
+                0b0001000,
+                0x0,
+            ];
+            ControlPointCommand::new(ControlCommandType::StartResist, Some(Vec::from(cmd_data)))
+        }
+        MeasurementType::Eeg(config) => {
+            let cmd_data = <[u8; 4]>::from(config);
+            ControlPointCommand::new(
+                ControlCommandType::StartEegSignal,
+                Some(Vec::from(cmd_data)),
+            )
+        }
     }
 }
 
@@ -583,21 +1304,58 @@ impl<L: Level + Connected> BBitSensor<L> {
 pub struct BleHandle {
     sender: mpsc::Sender<BleDeviceEvent>,
     pause: Arc<watch::Sender<bool>>,
+    /// Events published to subscribers
+    hub: broadcast::Sender<SensorEvent>,
+    /// How the event loop is finished, it's set once
+    outcome: watch::Receiver<Option<LoopOutcome>>,
 }
 
 impl BleHandle {
-    fn new(sender: mpsc::Sender<BleDeviceEvent>, pause: watch::Sender<bool>) -> Self {
+    fn new(
+        sender: mpsc::Sender<BleDeviceEvent>,
+        pause: Arc<watch::Sender<bool>>,
+        hub: broadcast::Sender<SensorEvent>,
+        outcome: watch::Receiver<Option<LoopOutcome>>,
+    ) -> Self {
         Self {
             sender,
-            pause: Arc::new(pause),
+            pause,
+            hub,
+            outcome,
+        }
+    }
+
+    /// Wait until the event loop is finished and return how it's finished, like
+    /// [`tokio::task::JoinHandle`] but every clone of the handle can wait
+    pub async fn join(&self) -> LoopOutcome {
+        let mut outcome = self.outcome.clone();
+        let res = outcome.wait_for(Option::is_some).await;
+        match res {
+            Ok(outcome) => outcome.clone().unwrap(),
+            Err(_) => LoopOutcome::Aborted,
         }
     }
 
-    /// Stop Signal or Resistance measurement
+    /// Check if the event loop is finished
+    pub fn is_finished(&self) -> bool {
+        self.outcome.borrow().is_some() || self.outcome.has_changed().is_err()
+    }
+
+    /// Receive device events in addition to the event handler, every subscriber gets
+    /// all events published after subscription
+    pub fn subscribe(&self) -> EventSubscriber {
+        EventSubscriber::new(self.hub.subscribe())
+    }
+
+    /// Stop Signal or Resistance measurement, unsubscribe, disconnect and finish the event
+    /// loop. Resolves when the event loop is finished.
+    ///
+    /// The same shutdown is done when all handles are dropped, but it's not awaited then.
     #[instrument(skip(self))]
-    pub async fn stop(self) {
+    pub async fn stop(self) -> LoopOutcome {
         tracing::info!("stopping bbit sensor");
         let _ = self.sender.send(BleDeviceEvent::Stop).await;
+        self.join().await
     }
 
     /// Start Signal or Resistance measurement
@@ -614,8 +1372,52 @@ impl BleHandle {
         rx.await.ok()
     }
 
-    /// Pause handling of bluetooth events. This will stop all Bluetooth
-    /// events from being sent to your handler.
+    /// Start EEG Signal measurement with specified amplifier config
+    #[instrument(skip(self))]
+    pub async fn start_eeg(&self, config: EegConfig) -> Option<BBitResult<()>> {
+        tracing::info!("starting EEG Signal measurement on bbit sensor...");
+        let (ret, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(BleDeviceEvent::StartSignal { config, ret })
+            .await;
+
+        rx.await.ok()
+    }
+
+    /// Stop Signal or Resistance measurement, the event loop keeps running so
+    /// a new measurement can be started on the same handle
+    #[instrument(skip(self))]
+    pub async fn stop_measurement(&self) -> Option<BBitResult<()>> {
+        tracing::info!("stopping measurement on bbit sensor...");
+        let (ret, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(BleDeviceEvent::StopMeasurement { ret })
+            .await;
+
+        rx.await.ok()
+    }
+
+    /// Check electrodes contact: measure resistance on O1, T3, T4 and O2 one by one.
+    /// Measurement is stopped when the sweep is finished.
+    #[instrument(skip(self))]
+    pub async fn measure_resistance(
+        &self,
+        config: ResistanceSweepConfig,
+    ) -> Option<BBitResult<ResistState>> {
+        tracing::info!("starting Resistance sweep on bbit sensor...");
+        let (ret, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(BleDeviceEvent::MeasureResistance { config, ret })
+            .await;
+
+        rx.await.ok()
+    }
+
+    /// Pause handling of bluetooth events. This will stop device data and status
+    /// events from being sent to your handler, BLE link changes are still sent.
     #[instrument(skip_all)]
     pub fn pause(&self) {
         tracing::info!("pausing bluetooth event handling");
@@ -636,8 +1438,15 @@ impl BleHandle {
 enum BleDeviceEvent {
     /// Stop the Signal or Resistance measurement
     Stop,
+    /// Stop the Signal or Resistance measurement, but keep the event loop running
+    StopMeasurement {
+        /// channel to receive return value
+        ret: oneshot::Sender<BBitResult<()>>,
+    },
     /// Send config command for Signal and start the event loop
     StartSignal {
+        /// Amplifier config of channels
+        config: EegConfig,
         /// channel to receive return value
         ret: oneshot::Sender<BBitResult<()>>,
     },
@@ -648,11 +1457,77 @@ enum BleDeviceEvent {
         /// channel to receive return value
         ret: oneshot::Sender<BBitResult<()>>,
     },
+    /// Measure resistance on all channels one by one
+    MeasureResistance {
+        /// Sweep settings
+        config: ResistanceSweepConfig,
+        /// channel to receive sweep result
+        ret: oneshot::Sender<BBitResult<ResistState>>,
+    },
 }
 
 /// Bluetooth data received from the sensor
 #[derive(Debug)]
-enum BluetoothEvent {
+pub(crate) enum BluetoothEvent {
     DeviceStatus(DeviceStatusData),
     EggOrResistanceData(Vec<u8>),
+    /// BLE link is lost, the bluetooth task tries to reconnect
+    Disconnected,
+    /// BLE link is restored and listened streams are subscribed again
+    Reconnected,
+    /// Reconnection is given up, the bluetooth task is finished
+    ReconnectFailed,
+}
+
+impl BluetoothEvent {
+    /// Parse notification of subscribed characteristic
+    pub(crate) fn from_notification(
+        data: ValueNotification,
+        profile: &dyn DeviceProfile,
+    ) -> Option<Self> {
+        if Some(data.uuid) == profile.notify_uuid(NotifyStream::DeviceState) {
+            let result = profile.decode_status(&data.value);
+            tracing::trace!("loop - received DeviceStatusData: {result:?}");
+            match result {
+                Ok(status_data) => Some(BluetoothEvent::DeviceStatus(status_data)),
+                Err(error) => {
+                    debug!("Error receiving Device Status data: {error:?}");
+                    None
+                }
+            }
+        } else if Some(data.uuid) == profile.notify_uuid(NotifyStream::EegOrResistanceMeasurement) {
+            tracing::trace!("loop - received eeg-resist_data: {:02X?}", data.value);
+            Some(BluetoothEvent::EggOrResistanceData(data.value))
+        } else {
+            None
+        }
+    }
+}
+
+/// How the event loop is finished, returned by [`BleHandle::join`].
+/// Measurement is stopped and device is disconnected in any case.
+#[derive(Debug, Clone)]
+pub enum LoopOutcome {
+    /// Stopped by [`BleHandle::stop`] or all handles are dropped
+    Stopped,
+    /// Ctrl-C is received with [`BBitSensor::stop_on_ctrl_c`] enabled
+    Interrupted,
+    /// [`EventHandler::should_continue`] returned false
+    Finished,
+    /// Handler returned error with [`HandlerErrorPolicy::Stop`]
+    HandlerError(Arc<Error>),
+    /// Event task panicked
+    Aborted,
+}
+
+/// Change of BLE link state reported to [`EventHandler::connection_update`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// BLE link is lost, reconnection is started when it's enabled
+    Disconnected,
+    /// Device is connected again, listened streams and running measurement are restored
+    Reconnected,
+    /// Reconnection attempts are exhausted, timed out or cancelled by [`ConnectPolicy`],
+    /// the device stays disconnected
+    ReconnectFailed,
 }
@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use btleplug::api::{BDAddr, PeripheralProperties};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::bbit::errors::Error;
+use crate::bbit::profile::DeviceProfile;
+use crate::bbit::results::BBitResult;
+use crate::bbit::transport::Transport;
+use crate::bbit::uuids::StringUuid;
+use crate::find_characteristic;
+
+/// Headset found by scan, it is not connected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredDevice {
+    /// BLE address, it can be used by [`ConnectTarget::Address`]
+    pub address: BDAddr,
+    /// Advertised local name
+    pub local_name: Option<String>,
+    /// Signal strength on scan, dBm
+    pub rssi: Option<i16>,
+    /// Advertised services
+    pub services: Vec<Uuid>,
+}
+
+impl From<PeripheralProperties> for DiscoveredDevice {
+    fn from(value: PeripheralProperties) -> Self {
+        Self {
+            address: value.address,
+            local_name: value.local_name,
+            rssi: value.rssi,
+            services: value.services,
+        }
+    }
+}
+
+/// Which headset to connect to when several ones are nearby
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectTarget {
+    /// The first device with local name starting with the filter
+    Name(String),
+    /// Device with BLE address
+    Address(BDAddr),
+    /// Device with serial number, every found device is connected to read its serial number
+    Serial(String),
+}
+
+impl ConnectTarget {
+    fn matches_advertisement(&self, device: &DiscoveredDevice, name_filter: &str) -> bool {
+        match self {
+            ConnectTarget::Name(name) => device
+                .local_name
+                .as_ref()
+                .is_some_and(|local_name| local_name.starts_with(name.as_str())),
+            ConnectTarget::Address(address) => device.address == *address,
+            ConnectTarget::Serial(_) => device
+                .local_name
+                .as_ref()
+                .is_some_and(|local_name| local_name.starts_with(name_filter)),
+        }
+    }
+}
+
+/// Scan for devices advertising the service of `profile` with local name starting with
+/// its name filter
+pub(crate) async fn scan<T: Transport>(
+    transport: &T,
+    profile: &dyn DeviceProfile,
+    duration: Duration,
+) -> BBitResult<Vec<DiscoveredDevice>> {
+    let name_filter = profile.name_filter();
+    let found = transport.scan(profile.service_uuid(), duration).await?;
+    Ok(found
+        .into_iter()
+        .map(DiscoveredDevice::from)
+        .filter(|device| {
+            device
+                .local_name
+                .as_ref()
+                .is_some_and(|local_name| local_name.starts_with(name_filter))
+        })
+        .collect())
+}
+
+/// Scan during `scan_duration`, connect to the device selected by `target` and discover its
+/// services. Name filter of `profile` limits candidates checked by serial number.
+pub(crate) async fn connect_target<T: Transport>(
+    transport: &T,
+    target: &ConnectTarget,
+    profile: &dyn DeviceProfile,
+    scan_duration: Duration,
+) -> BBitResult<()> {
+    let found = transport
+        .scan(profile.service_uuid(), scan_duration)
+        .await?;
+    let candidates = found
+        .into_iter()
+        .map(DiscoveredDevice::from)
+        .filter(|device| target.matches_advertisement(device, profile.name_filter()));
+
+    for device in candidates {
+        debug!("BLE '{:?}' is found, try to connect...", device.local_name);
+        transport.connect(device.address).await?;
+        debug!("Try to discover...");
+        transport.discover_services().await?;
+
+        let ConnectTarget::Serial(serial_number) = target else {
+            return Ok(());
+        };
+        let serial_uuid = profile
+            .string_uuid(StringUuid::SerialNumber)
+            .ok_or_else(|| Error::NotSupported(format!("{} serial number", profile.name())))?;
+        let characteristic = find_characteristic(transport, serial_uuid).await?;
+        let data = transport.read(&characteristic).await?;
+        let device_serial = String::from_utf8_lossy(&data);
+        if device_serial.trim_matches(char::from(0)) == serial_number {
+            return Ok(());
+        }
+        debug!("Device serial '{device_serial}' does not match, disconnecting...");
+        transport.disconnect().await?;
+    }
+    tracing::warn!("Device '{target:?}' is not found !");
+    Err(Error::NoDevice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbit::profile::{BrainBitProfile, ColibriRProfile, NeuroBleProfile};
+    use crate::bbit::uuids::NSS2_SERVICE_UUID;
+    use crate::sim::{SimConfig, SimulatedBrainBit};
+
+    #[tokio::test]
+    async fn test_scan_and_connect_target() {
+        let config = SimConfig::default();
+        let device = SimulatedBrainBit::new(config.clone());
+
+        let found = scan(&device, &BrainBitProfile, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(1, found.len());
+        assert_eq!(config.address, found[0].address);
+        assert_eq!(Some(config.local_name.clone()), found[0].local_name);
+        assert_eq!(Some(config.rssi), found[0].rssi);
+        assert_eq!(vec![NSS2_SERVICE_UUID], found[0].services);
+        assert!(scan(&device, &ColibriRProfile, Duration::ZERO)
+            .await
+            .unwrap()
+            .is_empty());
+        // NeuroBLE is scanned without service filter and matched by name
+        assert!(scan(&device, &NeuroBleProfile, Duration::ZERO)
+            .await
+            .unwrap()
+            .is_empty());
+        let neuroble = SimulatedBrainBit::new(SimConfig {
+            local_name: "NeuroBLE".to_string(),
+            ..config.clone()
+        });
+        let found = scan(&neuroble, &NeuroBleProfile, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(1, found.len());
+
+        let profile = &BrainBitProfile;
+        let wrong_serial = ConnectTarget::Serial("000000".to_string());
+        let res = connect_target(&device, &wrong_serial, profile, Duration::ZERO).await;
+        assert!(matches!(res, Err(Error::NoDevice)));
+        assert!(!device.is_connected().await);
+
+        let wrong_address = ConnectTarget::Address(BDAddr::from([1, 2, 3, 4, 5, 6]));
+        let res = connect_target(&device, &wrong_address, profile, Duration::ZERO).await;
+        assert!(matches!(res, Err(Error::NoDevice)));
+
+        let serial = ConnectTarget::Serial(config.serial_number.clone());
+        connect_target(&device, &serial, profile, Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(device.is_connected().await);
+        device.disconnect().await.unwrap();
+
+        let address = ConnectTarget::Address(config.address);
+        connect_target(&device, &address, profile, Duration::ZERO)
+            .await
+            .unwrap();
+        assert!(device.is_connected().await);
+    }
+}
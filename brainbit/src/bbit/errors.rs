@@ -1,11 +1,17 @@
 use thiserror::Error;
 
+use crate::bbit::internals::ChannelType;
+use crate::bbit::responses::CommandExecutionState;
+
 /// Error type for general brainbit errors and internal btleplug Ble errors
 #[derive(Debug, Error)]
 pub enum Error {
     /// Bluetooth adapter is not found on attempt to scan it
     #[error("No BLE adaptor")]
     NoBleAdaptor,
+    /// Selected Bluetooth adapter is not found among available ones
+    #[error("BLE adaptor '{0}' is not found")]
+    AdapterNotFound(String),
     /// Could not connect to a device by filter
     #[error("No BLE device")]
     NoDevice,
@@ -18,12 +24,33 @@ pub enum Error {
     /// EEG Data packets received from device is not parsed
     #[error("Invalid '{0}'")]
     InvalidData(String),
+    /// Resistance readings were not received on channel in time
+    #[error("No resistance data on channel '{0:?}'")]
+    NoResistanceData(ChannelType),
+    /// Connection attempts allowed by connection policy are made, but device is not connected
+    #[error("Not connected after {0} attempts")]
+    ConnectAttemptsExhausted(u32),
+    /// Connection deadline of connection policy is reached
+    #[error("Connection deadline is reached")]
+    ConnectTimeout,
+    /// Connection is cancelled by the token of connection policy
+    #[error("Connection is cancelled")]
+    ConnectCancelled,
+    /// Device family doesn't provide the characteristic or data format
+    #[error("'{0}' is not supported by the device")]
+    NotSupported(String),
     /// The command did not return a response
     #[error("No command response")]
     NoControlPointResponse,
+    /// Device reported error in status after the command
+    #[error("Command is rejected by device: '{0:?}'")]
+    CommandRejected(CommandExecutionState),
+    /// Session file could not be read or written
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
     /// An error occurred in the underlying BLE library.
     #[error("BLE error: {0}")]
     BleError(#[from] btleplug::Error),
-    #[error("Error generated by event handler")]
+    #[error("Error generated by event handler: {0}")]
     HandlerError(#[from] Box<dyn std::error::Error + Sync + Send>),
 }
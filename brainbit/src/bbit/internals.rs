@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use crate::bbit::errors::Error;
 use crate::bbit::results::BBitResult;
 
@@ -6,8 +8,8 @@ use crate::bbit::results::BBitResult;
 pub enum MeasurementType {
     /// Resistance
     Resistance(ChannelType),
-    /// EEG
-    Eeg,
+    /// EEG with per-channel amplifier config
+    Eeg(EegConfig),
 }
 
 /// List of channels in BBit.
@@ -24,6 +26,14 @@ pub enum ChannelType {
 }
 
 impl ChannelType {
+    /// All channels in the order they are transmitted by device
+    pub const ALL: [ChannelType; 4] = [
+        ChannelType::O1,
+        ChannelType::T3,
+        ChannelType::T4,
+        ChannelType::O2,
+    ];
+
     pub fn new(channel_number: u8) -> BBitResult<Self> {
         match channel_number {
             0 => Ok(ChannelType::O1),
@@ -37,22 +47,266 @@ impl ChannelType {
     }
 }
 
-impl Into<u8> for ChannelType {
-    fn into(self) -> u8 {
-        self as u8
+impl From<ChannelType> for u8 {
+    fn from(value: ChannelType) -> Self {
+        value as u8
     }
 }
 
-/// Internal constants to assign for Resistance commands
+/// Preset ADS1294 channel config bytes, named by their register bits as [`ChannelConfig`]
+/// decodes them: power bit 7, gain bits 6:4, input bits 2:0
 #[derive(Debug, Copy, Clone)]
 pub enum ADS1294ChannelInput {
-    PowerDownGain6 = 0x00,
-    PowerDownGain3 = 0x91,
-    PowerUpGain1 = 0x48,
+    /// Powered up, gain 6, electrode input, the reset value
+    PowerUpGain6 = 0x00,
+    /// Powered down, gain 1, shorted input
+    PowerDownGain1Shorted = 0x91,
+    /// Powered up, gain 4, electrode input, reserved bit 3 is set
+    PowerUpGain4 = 0x48,
+}
+
+#[allow(non_upper_case_globals)]
+impl ADS1294ChannelInput {
+    #[deprecated(note = "misnamed, the channel is powered up, use `PowerUpGain6`")]
+    pub const PowerDownGain6: Self = Self::PowerUpGain6;
+    #[deprecated(note = "misnamed, the gain is 1, use `PowerDownGain1Shorted`")]
+    pub const PowerDownGain3: Self = Self::PowerDownGain1Shorted;
+    #[deprecated(note = "misnamed, the gain is 4, use `PowerUpGain4`")]
+    pub const PowerUpGain1: Self = Self::PowerUpGain4;
+}
+
+impl From<ADS1294ChannelInput> for u8 {
+    fn from(value: ADS1294ChannelInput) -> Self {
+        value as u8
+    }
 }
 
-impl Into<u8> for ADS1294ChannelInput {
-    fn into(self) -> u8 {
-        self as u8
+/// Power state of ADS1294 channel, bit 7 of channel config byte
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ChannelPower {
+    /// Normal operation
+    #[default]
+    Up,
+    /// Channel is powered down, it transmits zeros
+    Down,
+}
+
+/// Input multiplexer of ADS1294 channel, bits 2:0 of channel config byte
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum ChannelInput {
+    /// Electrode input
+    #[default]
+    Normal = 0b000,
+    /// Input is shorted, used to measure offset and noise of amplifier
+    Shorted = 0b001,
+    /// Internal test signal, square wave about 1 Hz
+    Test = 0b101,
+}
+
+/// Typed ADS1294 channel config byte, the typed form of [`ADS1294ChannelInput`] presets.
+///
+/// Reserved bit 3 is ignored on parsing and written as zero.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ChannelConfig {
+    pub gain: Gain,
+    pub power: ChannelPower,
+    pub input: ChannelInput,
+}
+
+impl ChannelConfig {
+    /// Powered up channel measuring electrode input with specified gain
+    pub fn new(gain: Gain) -> Self {
+        Self {
+            gain,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_power(mut self, power: ChannelPower) -> Self {
+        self.power = power;
+        self
+    }
+
+    pub fn with_input(mut self, input: ChannelInput) -> Self {
+        self.input = input;
+        self
+    }
+}
+
+impl From<ChannelConfig> for u8 {
+    fn from(value: ChannelConfig) -> Self {
+        let power = match value.power {
+            ChannelPower::Up => 0,
+            ChannelPower::Down => 0x80,
+        };
+        power | (value.gain as u8) << 4 | value.input as u8
+    }
+}
+
+impl TryFrom<u8> for ChannelConfig {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let power = if value & 0x80 == 0 {
+            ChannelPower::Up
+        } else {
+            ChannelPower::Down
+        };
+        let input = match value & 0x07 {
+            0b000 => ChannelInput::Normal,
+            0b001 => ChannelInput::Shorted,
+            0b101 => ChannelInput::Test,
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "Unsupported input in channel config {value:#04X}"
+                )))
+            }
+        };
+        Ok(Self {
+            gain: Gain::from_channel_config(value)?,
+            power,
+            input,
+        })
+    }
+}
+
+/// Amplifier config of all channels used for EEG measurement
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct EegConfig {
+    /// Channel configs in O1, T3, T4, O2 order
+    pub channels: [ChannelConfig; 4],
+}
+
+impl EegConfig {
+    /// All channels powered up with the same gain
+    pub fn new(gain: Gain) -> Self {
+        Self {
+            channels: [ChannelConfig::new(gain); 4],
+        }
+    }
+
+    /// Replace config of one channel
+    pub fn with_channel(mut self, channel: ChannelType, config: ChannelConfig) -> Self {
+        self.channels[channel as usize] = config;
+        self
+    }
+
+    /// Config of one channel
+    pub fn channel(&self, channel: ChannelType) -> ChannelConfig {
+        self.channels[channel as usize]
+    }
+}
+
+impl From<EegConfig> for [u8; 4] {
+    fn from(value: EegConfig) -> Self {
+        value.channels.map(u8::from)
+    }
+}
+
+/// Programmable gain of ADS1294 channel amplifier, stored in bits 6:4 of channel config byte
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Gain {
+    X1 = 0b001,
+    X2 = 0b010,
+    X3 = 0b011,
+    X4 = 0b100,
+    /// Default gain after device reset
+    #[default]
+    X6 = 0b000,
+    X8 = 0b101,
+    X12 = 0b110,
+}
+
+impl Gain {
+    /// All gains in increasing order
+    pub const ALL: [Gain; 7] = [
+        Gain::X1,
+        Gain::X2,
+        Gain::X3,
+        Gain::X4,
+        Gain::X6,
+        Gain::X8,
+        Gain::X12,
+    ];
+
+    /// Extract gain from ADS1294 channel config byte
+    pub fn from_channel_config(config: u8) -> BBitResult<Self> {
+        match (config >> 4) & 0x07 {
+            0b000 => Ok(Gain::X6),
+            0b001 => Ok(Gain::X1),
+            0b010 => Ok(Gain::X2),
+            0b011 => Ok(Gain::X3),
+            0b100 => Ok(Gain::X4),
+            0b101 => Ok(Gain::X8),
+            0b110 => Ok(Gain::X12),
+            _ => Err(Error::InvalidData(format!(
+                "Incorrect gain in channel config {config:#04X}"
+            ))),
+        }
+    }
+
+    /// Amplification factor
+    pub fn factor(&self) -> f64 {
+        match self {
+            Gain::X1 => 1.0,
+            Gain::X2 => 2.0,
+            Gain::X3 => 3.0,
+            Gain::X4 => 4.0,
+            Gain::X6 => 6.0,
+            Gain::X8 => 8.0,
+            Gain::X12 => 12.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_config_bytes() {
+        let parse = |input: ADS1294ChannelInput| ChannelConfig::try_from(u8::from(input)).unwrap();
+        assert_eq!(
+            EegConfig::default().channels[0],
+            parse(ADS1294ChannelInput::PowerUpGain6)
+        );
+        assert_eq!(
+            ChannelConfig::new(Gain::X6),
+            parse(ADS1294ChannelInput::PowerUpGain6)
+        );
+        assert_eq!(
+            ChannelConfig::new(Gain::X1)
+                .with_power(ChannelPower::Down)
+                .with_input(ChannelInput::Shorted),
+            parse(ADS1294ChannelInput::PowerDownGain1Shorted)
+        );
+        assert_eq!(
+            0x91,
+            u8::from(parse(ADS1294ChannelInput::PowerDownGain1Shorted))
+        );
+        assert_eq!(
+            ChannelConfig::new(Gain::X4),
+            parse(ADS1294ChannelInput::PowerUpGain4)
+        );
+        // reserved bit is dropped
+        assert_eq!(0x40, u8::from(parse(ADS1294ChannelInput::PowerUpGain4)));
+        #[allow(deprecated)]
+        let aliases = [
+            ADS1294ChannelInput::PowerDownGain6,
+            ADS1294ChannelInput::PowerDownGain3,
+            ADS1294ChannelInput::PowerUpGain1,
+        ];
+        assert_eq!([0x00, 0x91, 0x48], aliases.map(u8::from));
+        let test_signal = ChannelConfig::new(Gain::X12)
+            .with_power(ChannelPower::Down)
+            .with_input(ChannelInput::Test);
+        assert_eq!(0xE5, u8::from(test_signal));
+        assert!(ChannelConfig::try_from(0x03).is_err());
+        assert!(ChannelConfig::try_from(0x70).is_err());
+
+        let config =
+            EegConfig::new(Gain::X8).with_channel(ChannelType::T4, ChannelConfig::new(Gain::X1));
+        assert_eq!([0x50, 0x50, 0x10, 0x50], <[u8; 4]>::from(config));
+        assert_eq!([0x00; 4], <[u8; 4]>::from(EegConfig::default()));
     }
 }
@@ -0,0 +1,315 @@
+use std::collections::VecDeque;
+
+use crate::bbit::responses::{EegPacket, EegSample, PACKET_COUNTER_MODULO, SAMPLES_PER_PACKET};
+
+/// Number of packets behind the last received one within which a late packet is recognized
+/// as duplicated, or as reordered one subtracted from lost packets
+const REORDER_WINDOW: u16 = 32;
+
+/// Type of discontinuity in packet counter sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketGapKind {
+    /// One or more packets were not received
+    Dropped,
+    /// Packet with the same counter was received again
+    Duplicated,
+    /// Packet arrived later than packets sent after it
+    Reordered,
+}
+
+/// Cumulative packet statistics since measurement start
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PacketLossStats {
+    /// Received packets including duplicated and reordered ones
+    pub received: u64,
+    /// Packets which were not received (packets arrived late within the reorder window are
+    /// subtracted)
+    pub lost: u64,
+    /// Packets received more than once
+    pub duplicated: u64,
+    /// Packets received out of order
+    pub reordered: u64,
+}
+
+impl PacketLossStats {
+    /// Part of lost packets among all expected packets, 0.0..1.0
+    pub fn loss_ratio(&self) -> f64 {
+        let expected = self.received - self.duplicated + self.lost;
+        if expected == 0 {
+            return 0.0;
+        }
+        self.lost as f64 / expected as f64
+    }
+}
+
+/// Discontinuity found in EEG packets sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketGap {
+    /// Type of discontinuity
+    pub kind: PacketGapKind,
+    /// Counter value expected next
+    pub expected_counter: u16,
+    /// Counter value actually received
+    pub received_counter: u16,
+    /// Number of packets missing between expected and received ones (only for dropped)
+    pub lost_packets: u16,
+    /// Statistics including this gap
+    pub stats: PacketLossStats,
+}
+
+/// Tracks 11-bit packet counter and reports dropped, duplicated or reordered packets
+#[derive(Debug, Default, Clone)]
+pub struct PacketTracker {
+    last_counter: Option<u16>,
+    recent: VecDeque<u16>,
+    /// Counters reported as lost which are still within the reorder window
+    missing: VecDeque<u16>,
+    stats: PacketLossStats,
+}
+
+impl PacketTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget sequence and statistics, called when a new measurement starts
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Cumulative statistics
+    pub fn stats(&self) -> PacketLossStats {
+        self.stats
+    }
+
+    /// Register received packet counter, returns found discontinuity
+    pub fn track(&mut self, counter: u16) -> Option<PacketGap> {
+        self.stats.received += 1;
+        let Some(last) = self.last_counter else {
+            self.remember(counter);
+            self.advance(counter);
+            return None;
+        };
+        let expected_counter = (last + 1) % PACKET_COUNTER_MODULO;
+        let gap = |kind, lost_packets, stats| PacketGap {
+            kind,
+            expected_counter,
+            received_counter: counter,
+            lost_packets,
+            stats,
+        };
+
+        if self.recent.contains(&counter) {
+            self.stats.duplicated += 1;
+            return Some(gap(PacketGapKind::Duplicated, 0, self.stats));
+        }
+        self.remember(counter);
+
+        if let Some(index) = self.missing.iter().position(|&missing| missing == counter) {
+            // packet was counted as lost before
+            self.missing.remove(index);
+            self.stats.reordered += 1;
+            self.stats.lost -= 1;
+            return Some(gap(PacketGapKind::Reordered, 0, self.stats));
+        }
+
+        let distance = (counter + PACKET_COUNTER_MODULO - last) % PACKET_COUNTER_MODULO;
+        if distance > PACKET_COUNTER_MODULO / 2 {
+            // counter is behind the last one, the packet is late but it's not known if it was
+            // counted as lost
+            self.stats.reordered += 1;
+            return Some(gap(PacketGapKind::Reordered, 0, self.stats));
+        }
+        self.advance(counter);
+        if distance == 1 {
+            return None;
+        }
+        let lost_packets = distance - 1;
+        self.stats.lost += lost_packets as u64;
+        let first_missing = counter + PACKET_COUNTER_MODULO - lost_packets.min(REORDER_WINDOW);
+        self.missing.extend(
+            (first_missing..counter + PACKET_COUNTER_MODULO).map(|c| c % PACKET_COUNTER_MODULO),
+        );
+        Some(gap(PacketGapKind::Dropped, lost_packets, self.stats))
+    }
+
+    fn remember(&mut self, counter: u16) {
+        if self.recent.len() == REORDER_WINDOW as usize {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(counter);
+    }
+
+    /// Move the sequence forward and forget missing counters which left the reorder window
+    fn advance(&mut self, counter: u16) {
+        self.last_counter = Some(counter);
+        let behind =
+            |missing: u16| (counter + PACKET_COUNTER_MODULO - missing) % PACKET_COUNTER_MODULO;
+        self.missing
+            .retain(|&missing| behind(missing) <= REORDER_WINDOW);
+    }
+}
+
+/// How dropped EEG packets are replaced for downstream consumers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GapFill {
+    /// Dropped packets are only reported
+    #[default]
+    None,
+    /// Dropped packets are replaced with packets of NaN samples
+    Nan,
+    /// Dropped packets are replaced with samples linearly interpolated between neighbours
+    Interpolate,
+}
+
+impl GapFill {
+    /// Synthesize `lost_packets` packets between `previous` and `next` ones
+    pub fn fill(
+        &self,
+        previous: Option<&EegPacket>,
+        next: &EegPacket,
+        lost_packets: u16,
+    ) -> Vec<EegPacket> {
+        let first_counter = next.counter + PACKET_COUNTER_MODULO - lost_packets;
+        let counter = |i: u16| (first_counter + i) % PACKET_COUNTER_MODULO;
+        match (self, previous) {
+            (GapFill::None, _) => vec![],
+            (GapFill::Nan, _) | (GapFill::Interpolate, None) => {
+                let nan = EegSample {
+                    o1: f64::NAN,
+                    t3: f64::NAN,
+                    t4: f64::NAN,
+                    o2: f64::NAN,
+                };
+                (0..lost_packets)
+                    .map(|i| EegPacket {
+                        counter: counter(i),
+                        samples: [nan; SAMPLES_PER_PACKET],
+                    })
+                    .collect()
+            }
+            (GapFill::Interpolate, Some(previous)) => {
+                let from = previous.samples[SAMPLES_PER_PACKET - 1];
+                let to = next.samples[0];
+                let steps = (lost_packets as usize * SAMPLES_PER_PACKET + 1) as f64;
+                let lerp = |a: f64, b: f64, k: f64| a + (b - a) * k;
+                (0..lost_packets)
+                    .map(|i| EegPacket {
+                        counter: counter(i),
+                        samples: std::array::from_fn(|j| {
+                            let k = (i as usize * SAMPLES_PER_PACKET + j + 1) as f64 / steps;
+                            EegSample {
+                                o1: lerp(from.o1, to.o1, k),
+                                t3: lerp(from.t3, to.t3, k),
+                                t4: lerp(from.t4, to.t4, k),
+                                o2: lerp(from.o2, to.o2, k),
+                            }
+                        }),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(counter: u16, value: f64) -> EegPacket {
+        let sample = EegSample {
+            o1: value,
+            t3: value,
+            t4: value,
+            o2: value,
+        };
+        EegPacket {
+            counter,
+            samples: [sample; SAMPLES_PER_PACKET],
+        }
+    }
+
+    #[test]
+    fn test_continuous_sequence_with_wrap() {
+        let mut tracker = PacketTracker::new();
+        for counter in (2040..2048).chain(0..10) {
+            assert_eq!(None, tracker.track(counter));
+        }
+        assert_eq!(18, tracker.stats().received);
+        assert_eq!(0.0, tracker.stats().loss_ratio());
+    }
+
+    #[test]
+    fn test_dropped_packets() {
+        let mut tracker = PacketTracker::new();
+        tracker.track(2046);
+        let gap = tracker.track(2).unwrap();
+        assert_eq!(PacketGapKind::Dropped, gap.kind);
+        assert_eq!(2047, gap.expected_counter);
+        assert_eq!(3, gap.lost_packets);
+        assert_eq!(3, gap.stats.lost);
+        assert_eq!(None, tracker.track(3));
+        assert_eq!(0.5, tracker.stats().loss_ratio());
+    }
+
+    #[test]
+    fn test_duplicated_and_reordered_packets() {
+        let mut tracker = PacketTracker::new();
+        tracker.track(10);
+        tracker.track(11);
+        assert_eq!(PacketGapKind::Duplicated, tracker.track(11).unwrap().kind);
+        assert_eq!(PacketGapKind::Dropped, tracker.track(13).unwrap().kind);
+        let gap = tracker.track(12).unwrap();
+        assert_eq!(PacketGapKind::Reordered, gap.kind);
+        assert_eq!(0, gap.stats.lost);
+        assert_eq!(1, gap.stats.duplicated);
+        assert_eq!(1, gap.stats.reordered);
+        assert_eq!(None, tracker.track(14));
+    }
+
+    #[test]
+    fn test_late_packets_outside_reorder_window() {
+        let mut tracker = PacketTracker::new();
+        tracker.track(100);
+        let gap = tracker.track(1000).unwrap();
+        assert_eq!(PacketGapKind::Dropped, gap.kind);
+        assert_eq!(899, gap.lost_packets);
+        // recent missing counter arrives late
+        assert_eq!(PacketGapKind::Reordered, tracker.track(990).unwrap().kind);
+        assert_eq!(898, tracker.stats().lost);
+        // counters less than a half of the range behind are late, not a jump forward
+        for counter in [200, 150, 10] {
+            let gap = tracker.track(counter).unwrap();
+            assert_eq!(PacketGapKind::Reordered, gap.kind);
+            assert_eq!(0, gap.lost_packets);
+        }
+        // late counters behind the window are never subtracted from lost ones
+        assert_eq!(4, tracker.stats().reordered);
+        assert_eq!(898, tracker.stats().lost);
+        assert_eq!(PacketGapKind::Duplicated, tracker.track(150).unwrap().kind);
+        assert_eq!(None, tracker.track(1001));
+    }
+
+    #[test]
+    fn test_gap_fill() {
+        let previous = packet(2047, 0.0);
+        let next = packet(2, 6.0);
+        assert!(GapFill::None.fill(Some(&previous), &next, 2).is_empty());
+
+        let nan = GapFill::Nan.fill(Some(&previous), &next, 2);
+        assert_eq!(
+            vec![0, 1],
+            nan.iter().map(|p| p.counter).collect::<Vec<_>>()
+        );
+        assert!(nan[0].samples[0].o1.is_nan());
+
+        let interpolated = GapFill::Interpolate.fill(Some(&previous), &next, 2);
+        let values: Vec<f64> = interpolated
+            .iter()
+            .flat_map(|p| p.samples.iter().map(|s| s.t3))
+            .collect();
+        for (value, expected) in values.iter().zip([1.2, 2.4, 3.6, 4.8]) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+/// Duration of scan done on every connection attempt by default
+pub const DEFAULT_SCAN_DURATION: Duration = Duration::from_secs(2);
+
+/// Number of connection attempts made by default
+pub const DEFAULT_CONNECT_ATTEMPTS: u32 = 20;
+
+/// Delay between connection attempts by default
+pub const DEFAULT_CONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Delay between connection attempts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backoff {
+    /// The same delay after every failed attempt
+    Fixed(Duration),
+    /// Delay starts from `initial` and doubles after every failed attempt up to `max`.
+    /// With `jitter` the actual delay is random between a half and the whole computed one,
+    /// so several sensors don't retry in lockstep.
+    Exponential {
+        initial: Duration,
+        max: Duration,
+        jitter: bool,
+    },
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::Fixed(DEFAULT_CONNECT_DELAY)
+    }
+}
+
+impl Backoff {
+    /// Delay after failed attempt number `attempt`, attempts are counted from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential {
+                initial,
+                max,
+                jitter,
+            } => {
+                let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+                let delay = initial.saturating_mul(factor).min(max);
+                if !jitter {
+                    return delay;
+                }
+                let half = delay / 2;
+                let spread = u64::try_from((delay - half).as_millis()).unwrap_or(u64::MAX);
+                half + Duration::from_millis(fastrand::u64(0..=spread))
+            }
+        }
+    }
+}
+
+/// How [`crate::bbit::device::BBitSensor`] connects to a device: scan window, attempts,
+/// delays between them, overall deadline and cancellation.
+///
+/// The same policy applies to every automatic reconnection by the event loop, its attempts
+/// and deadline are counted from the moment BLE link is lost.
+#[derive(Debug, Clone)]
+pub struct ConnectPolicy {
+    /// Duration of scan done on every attempt
+    pub scan_duration: Duration,
+    /// Maximum number of attempts, `None` means retry until connected, cancelled or the deadline
+    pub max_attempts: Option<u32>,
+    /// Delay between attempts
+    pub backoff: Backoff,
+    /// Maximum time of the whole connection, `None` means no limit
+    pub deadline: Option<Duration>,
+    /// Cancel the connection (or reconnection) from another task, clones of the policy
+    /// share the token
+    pub cancel: CancellationToken,
+}
+
+impl Default for ConnectPolicy {
+    fn default() -> Self {
+        Self {
+            scan_duration: DEFAULT_SCAN_DURATION,
+            max_attempts: Some(DEFAULT_CONNECT_ATTEMPTS),
+            backoff: Backoff::default(),
+            deadline: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+}
+
+impl ConnectPolicy {
+    /// Set duration of scan done on every attempt
+    pub fn scan_duration(mut self, duration: Duration) -> Self {
+        self.scan_duration = duration;
+        self
+    }
+
+    /// Set maximum number of attempts, `None` is unlimited
+    pub fn max_attempts(mut self, attempts: Option<u32>) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Set delay between attempts
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set maximum time of the whole connection
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Use the token to cancel connection, keep its clone to call [`CancellationToken::cancel`]
+    pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Check if more attempts are allowed after `attempt` ones were made
+    pub(crate) fn has_attempts_after(&self, attempt: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempt < max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let fixed = Backoff::Fixed(Duration::from_millis(500));
+        assert_eq!(Duration::from_millis(500), fixed.delay(1));
+        assert_eq!(Duration::from_millis(500), fixed.delay(10));
+
+        let initial = Duration::from_millis(100);
+        let max = Duration::from_secs(1);
+        let exponential = Backoff::Exponential {
+            initial,
+            max,
+            jitter: false,
+        };
+        let delays: Vec<_> = (1..=6).map(|attempt| exponential.delay(attempt)).collect();
+        assert_eq!(
+            vec![100, 200, 400, 800, 1000, 1000],
+            delays.iter().map(Duration::as_millis).collect::<Vec<_>>()
+        );
+        assert_eq!(max, exponential.delay(u32::MAX));
+
+        let jitter = Backoff::Exponential {
+            initial,
+            max,
+            jitter: true,
+        };
+        for attempt in 1..=6 {
+            let delay = jitter.delay(attempt);
+            let full = exponential.delay(attempt);
+            assert!(delay >= full / 2 && delay <= full, "{delay:?} of {full:?}");
+        }
+
+        let policy = ConnectPolicy::default().max_attempts(Some(2));
+        assert!(policy.has_attempts_after(1));
+        assert!(!policy.has_attempts_after(2));
+        assert!(ConnectPolicy::default()
+            .max_attempts(None)
+            .has_attempts_after(u32::MAX));
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt::Debug;
+
+use uuid::Uuid;
+
+use crate::bbit::control::ControlPointCommand;
+use crate::bbit::errors::Error;
+use crate::bbit::responses::{
+    packet_counter, DeviceStatusData, EegDecoder, EegPacket, Nss2Status, ResistanceDecoder,
+    ResistanceReading, MAX_BATTERY_LEVEL,
+};
+use crate::bbit::results::BBitResult;
+use crate::bbit::uuids::{
+    NotifyStream, NotifyUuid, StringUuid, BATTERY_LEVEL_UUID, COLIBRI_DATA_NOTIFY_UUID,
+    COLIBRI_NAME_MATCH_FILTER, COLIBRI_SERVICE_UUID, COLIBRI_WRITE_COMMAND_UUID,
+    DEVICE_NAME_STRING_UUID, NEUROBLE_DATA_NOTIFY_UUID, NEUROBLE_NAME_MATCH_FILTER,
+    NSS2_SERVICE_UUID, PERIPHERAL_NAME_MATCH_FILTER, WRITE_COMMAN_UUID,
+};
+
+/// GATT layout, command encoding and packet decoding of one Neurotech device family.
+///
+/// [`crate::bbit::device::BBitSensor`] uses [`BrainBitProfile`] unless another one is set by
+/// [`crate::bbit::device::BBitSensor::with_profile`].
+pub trait DeviceProfile: Debug + Send + Sync {
+    /// Family name used in logs and errors
+    fn name(&self) -> &'static str;
+
+    /// Prefix of advertised local name
+    fn name_filter(&self) -> &'static str;
+
+    /// Service advertised by devices of the family, scan is filtered by it. `None` scans
+    /// all peripherals, devices are matched by advertised name only.
+    fn service_uuid(&self) -> Option<Uuid>;
+
+    /// Characteristic notifying the stream, `None` if the family doesn't provide it
+    fn notify_uuid(&self, stream: NotifyStream) -> Option<Uuid>;
+
+    /// Characteristic receiving commands, `None` if the device is not controlled
+    fn command_uuid(&self) -> Option<Uuid>;
+
+    /// Characteristic keeping device information string, missing strings are read as empty
+    fn string_uuid(&self, string: StringUuid) -> Option<Uuid>;
+
+    /// Bytes written to the command characteristic
+    fn encode_command(&self, command: &ControlPointCommand) -> BBitResult<Vec<u8>> {
+        Err(Error::NotSupported(format!(
+            "{command:?} command of {}",
+            self.name()
+        )))
+    }
+
+    /// Decode notification of [`NotifyStream::DeviceState`] characteristic
+    fn decode_status(&self, data: &[u8]) -> BBitResult<DeviceStatusData>;
+
+    /// Read packet sequence counter of [`NotifyStream::EegOrResistanceMeasurement`] notification
+    fn packet_counter(&self, _data: &[u8]) -> BBitResult<u16> {
+        Err(Error::NotSupported(format!(
+            "{} packet counter",
+            self.name()
+        )))
+    }
+
+    /// Decode EEG notification with gains of running measurement
+    fn decode_eeg(&self, _decoder: &EegDecoder, _data: &[u8]) -> BBitResult<EegPacket> {
+        Err(Error::NotSupported(format!("{} EEG packets", self.name())))
+    }
+
+    /// Decode notification received while resistance measurement is running
+    fn decode_resistance(
+        &self,
+        _decoder: &ResistanceDecoder,
+        _data: &[u8],
+    ) -> BBitResult<ResistanceReading> {
+        Err(Error::NotSupported(format!(
+            "{} resistance packets",
+            self.name()
+        )))
+    }
+}
+
+/// BrainBit headset with NSS2 service, the only family supporting EEG and resistance
+/// measurements
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BrainBitProfile;
+
+impl DeviceProfile for BrainBitProfile {
+    fn name(&self) -> &'static str {
+        "BrainBit"
+    }
+
+    fn name_filter(&self) -> &'static str {
+        PERIPHERAL_NAME_MATCH_FILTER
+    }
+
+    fn service_uuid(&self) -> Option<Uuid> {
+        Some(NSS2_SERVICE_UUID)
+    }
+
+    fn notify_uuid(&self, stream: NotifyStream) -> Option<Uuid> {
+        Some(NotifyUuid::from(stream).into())
+    }
+
+    fn command_uuid(&self) -> Option<Uuid> {
+        Some(WRITE_COMMAN_UUID)
+    }
+
+    fn string_uuid(&self, string: StringUuid) -> Option<Uuid> {
+        Some(string.into())
+    }
+
+    fn encode_command(&self, command: &ControlPointCommand) -> BBitResult<Vec<u8>> {
+        command
+            .clone()
+            .try_into()
+            .map_err(|e: &str| Error::InvalidData(e.to_string()))
+    }
+
+    fn decode_status(&self, data: &[u8]) -> BBitResult<DeviceStatusData> {
+        DeviceStatusData::try_from(data.to_vec()).map_err(|e| Error::InvalidData(e.to_string()))
+    }
+
+    fn packet_counter(&self, data: &[u8]) -> BBitResult<u16> {
+        packet_counter(data)
+    }
+
+    fn decode_eeg(&self, decoder: &EegDecoder, data: &[u8]) -> BBitResult<EegPacket> {
+        decoder.decode(data)
+    }
+
+    fn decode_resistance(
+        &self,
+        decoder: &ResistanceDecoder,
+        data: &[u8],
+    ) -> BBitResult<ResistanceReading> {
+        decoder.decode(data)
+    }
+}
+
+/// Colibri R device. Its GATT dump has neither Device Information nor Battery service and
+/// format of its data and commands is not documented, so the profile covers connection and
+/// device name only. It has no device status stream, [`crate::bbit::uuids::EventType::State`]
+/// listened for it is skipped when the sensor is built.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ColibriRProfile;
+
+impl DeviceProfile for ColibriRProfile {
+    fn name(&self) -> &'static str {
+        "Colibri R"
+    }
+
+    fn name_filter(&self) -> &'static str {
+        COLIBRI_NAME_MATCH_FILTER
+    }
+
+    fn service_uuid(&self) -> Option<Uuid> {
+        Some(COLIBRI_SERVICE_UUID)
+    }
+
+    fn notify_uuid(&self, stream: NotifyStream) -> Option<Uuid> {
+        match stream {
+            NotifyStream::DeviceState => None,
+            NotifyStream::EegOrResistanceMeasurement => Some(COLIBRI_DATA_NOTIFY_UUID),
+        }
+    }
+
+    fn command_uuid(&self) -> Option<Uuid> {
+        Some(COLIBRI_WRITE_COMMAND_UUID)
+    }
+
+    fn string_uuid(&self, string: StringUuid) -> Option<Uuid> {
+        match string {
+            StringUuid::ModelNumber => Some(DEVICE_NAME_STRING_UUID),
+            _ => None,
+        }
+    }
+
+    fn decode_status(&self, _data: &[u8]) -> BBitResult<DeviceStatusData> {
+        Err(Error::NotSupported(format!("{} status", self.name())))
+    }
+}
+
+/// NeuroBLE device reporting battery level by the standard Battery Level characteristic,
+/// it's passed as device status.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NeuroBleProfile;
+
+impl DeviceProfile for NeuroBleProfile {
+    fn name(&self) -> &'static str {
+        "NeuroBLE"
+    }
+
+    fn name_filter(&self) -> &'static str {
+        NEUROBLE_NAME_MATCH_FILTER
+    }
+
+    /// Only characteristics of NeuroBLE are known, not its service
+    fn service_uuid(&self) -> Option<Uuid> {
+        None
+    }
+
+    fn notify_uuid(&self, stream: NotifyStream) -> Option<Uuid> {
+        match stream {
+            NotifyStream::DeviceState => Some(BATTERY_LEVEL_UUID),
+            NotifyStream::EegOrResistanceMeasurement => Some(NEUROBLE_DATA_NOTIFY_UUID),
+        }
+    }
+
+    fn command_uuid(&self) -> Option<Uuid> {
+        None
+    }
+
+    fn string_uuid(&self, string: StringUuid) -> Option<Uuid> {
+        match string {
+            StringUuid::ModelNumber => Some(DEVICE_NAME_STRING_UUID),
+            _ => None,
+        }
+    }
+
+    /// Battery level in percents is converted to BrainBit scale of
+    /// [`DeviceStatusData::battery_level`]
+    fn decode_status(&self, data: &[u8]) -> BBitResult<DeviceStatusData> {
+        let [percents] = data else {
+            return Err(Error::InvalidData(format!("battery level {data:02X?}")));
+        };
+        let level = u16::from((*percents).min(100)) * u16::from(MAX_BATTERY_LEVEL) / 100;
+        Ok(DeviceStatusData {
+            status_nss2: Nss2Status::Stopped,
+            battery_level: level as u8,
+            ..DeviceStatusData::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbit::control::ControlCommandType;
+
+    #[test]
+    fn test_device_profiles() {
+        let brainbit = BrainBitProfile;
+        let command = ControlPointCommand::new(ControlCommandType::StopAll, None);
+        let encoded: Vec<u8> = command.clone().try_into().unwrap();
+        assert_eq!(encoded, brainbit.encode_command(&command).unwrap());
+        assert_eq!(
+            Some(Uuid::from(NotifyUuid::DeviceStateChange)),
+            brainbit.notify_uuid(NotifyStream::DeviceState)
+        );
+        let status = brainbit.decode_status(&[0x01, 0x00, 0x51, 0x00]).unwrap();
+        assert_eq!(Nss2Status::Stopped, status.status_nss2);
+        assert!(brainbit.decode_status(&[0x01]).is_err());
+
+        let colibri = ColibriRProfile;
+        assert_eq!(None, colibri.notify_uuid(NotifyStream::DeviceState));
+        assert_eq!(None, colibri.string_uuid(StringUuid::SerialNumber));
+        assert!(matches!(
+            colibri.encode_command(&command),
+            Err(Error::NotSupported(_))
+        ));
+
+        let neuroble = NeuroBleProfile;
+        assert_eq!(None, neuroble.service_uuid());
+        assert_eq!(None, neuroble.command_uuid());
+        assert_eq!(
+            Some(BATTERY_LEVEL_UUID),
+            neuroble.notify_uuid(NotifyStream::DeviceState)
+        );
+        let status = neuroble.decode_status(&[100]).unwrap();
+        assert_eq!(100.0, status.get_battery_charge_level());
+        let status = neuroble.decode_status(&[50]).unwrap();
+        assert!((status.get_battery_charge_level() - 50.0).abs() < 1.0);
+        assert!(neuroble.decode_status(&[50, 0]).is_err());
+        assert!(neuroble
+            .decode_eeg(&EegDecoder::default(), &[0; 20])
+            .is_err());
+    }
+}
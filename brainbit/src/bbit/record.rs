@@ -0,0 +1,583 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use btleplug::api::{BDAddr, Characteristic, PeripheralProperties, ValueNotification};
+use futures::StreamExt;
+use tokio::time::Instant;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::bbit::battery::{BatteryConfig, BatteryMonitor};
+use crate::bbit::control::ControlPointCommand;
+use crate::bbit::device::{BluetoothEvent, MeasurementState};
+use crate::bbit::errors::Error;
+use crate::bbit::packet_loss::GapFill;
+use crate::bbit::profile::{BrainBitProfile, DeviceProfile};
+use crate::bbit::responses::DEFAULT_LEAD_OFF_CURRENT_A;
+use crate::bbit::results::BBitResult;
+use crate::bbit::traits::EventHandler;
+use crate::bbit::transport::{DisconnectionStream, NotificationStream, Transport};
+
+/// The first bytes of session file
+const SESSION_FILE_MAGIC: &[u8; 4] = b"BBRS";
+
+/// Version of session file format written by [`SessionRecorder`]
+const SESSION_FILE_VERSION: u8 = 1;
+
+/// Record header: kind, offset in microseconds, characteristic UUID and data length
+const RECORD_HEADER_LEN: usize = 1 + 8 + 16 + 2;
+
+/// What is captured by a session record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// Notification received from subscribed characteristic
+    Notification = 0,
+    /// Command written into characteristic
+    Command = 1,
+}
+
+impl TryFrom<u8> for RecordKind {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(RecordKind::Notification),
+            1 => Ok(RecordKind::Command),
+            _ => Err(Error::InvalidData(format!("session record kind {value}"))),
+        }
+    }
+}
+
+/// One notification or command of recorded session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRecord {
+    pub kind: RecordKind,
+    /// Time since the recording start
+    pub at: Duration,
+    /// Characteristic notifying or receiving the data
+    pub uuid: Uuid,
+    pub data: Vec<u8>,
+}
+
+impl SessionRecord {
+    /// Append the record in session file format
+    fn encode(&self, out: &mut Vec<u8>) -> BBitResult<()> {
+        let len = u16::try_from(self.data.len()).map_err(|_| {
+            Error::InvalidData(format!("{} bytes of session record", self.data.len()))
+        })?;
+        let micros = u64::try_from(self.at.as_micros()).unwrap_or(u64::MAX);
+        out.push(self.kind as u8);
+        out.extend_from_slice(&micros.to_le_bytes());
+        out.extend_from_slice(self.uuid.as_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&self.data);
+        Ok(())
+    }
+
+    /// Parse records of session file content
+    pub fn decode_all(content: &[u8]) -> BBitResult<Vec<SessionRecord>> {
+        let header = content.strip_prefix(SESSION_FILE_MAGIC);
+        let Some((version, mut rest)) = header.and_then(|content| content.split_first()) else {
+            return Err(Error::InvalidData("session file header".to_string()));
+        };
+        if *version != SESSION_FILE_VERSION {
+            return Err(Error::InvalidData(format!(
+                "session file version {version}"
+            )));
+        }
+        let mut records = vec![];
+        while !rest.is_empty() {
+            let Some((header, data)) = rest.split_first_chunk::<RECORD_HEADER_LEN>() else {
+                // recording was interrupted in the middle of the record
+                tracing::warn!("Session file is truncated after {} records", records.len());
+                break;
+            };
+            let len = usize::from(u16::from_le_bytes([header[25], header[26]]));
+            if data.len() < len {
+                tracing::warn!("Session file is truncated after {} records", records.len());
+                break;
+            }
+            let micros = u64::from_le_bytes(header[1..9].try_into().unwrap());
+            records.push(SessionRecord {
+                kind: RecordKind::try_from(header[0])?,
+                at: Duration::from_micros(micros),
+                uuid: Uuid::from_slice(&header[9..25]).unwrap(),
+                data: data[..len].to_vec(),
+            });
+            rest = &data[len..];
+        }
+        Ok(records)
+    }
+}
+
+/// Writes session records with time offsets into a file
+#[derive(Debug)]
+pub struct SessionRecorder {
+    output: Mutex<BufWriter<File>>,
+    started: Instant,
+}
+
+impl SessionRecorder {
+    /// Create session file, time offsets of records are counted from now
+    pub fn create(path: impl AsRef<Path>) -> BBitResult<Self> {
+        let mut output = BufWriter::new(File::create(path)?);
+        output.write_all(SESSION_FILE_MAGIC)?;
+        output.write_all(&[SESSION_FILE_VERSION])?;
+        Ok(Self {
+            output: Mutex::new(output),
+            started: Instant::now(),
+        })
+    }
+
+    /// Append record captured now
+    pub fn record(&self, kind: RecordKind, uuid: Uuid, data: &[u8]) -> BBitResult<()> {
+        let record = SessionRecord {
+            kind,
+            at: self.started.elapsed(),
+            uuid,
+            data: data.to_vec(),
+        };
+        let mut encoded = Vec::with_capacity(RECORD_HEADER_LEN + data.len());
+        record.encode(&mut encoded)?;
+        self.output.lock().unwrap().write_all(&encoded)?;
+        Ok(())
+    }
+
+    /// Write buffered records into the file
+    pub fn flush(&self) -> BBitResult<()> {
+        self.output.lock().unwrap().flush()?;
+        Ok(())
+    }
+}
+
+/// [`Transport`] capturing notifications and written commands of another transport into
+/// session file, the file is replayed by [`SessionReplay`].
+///
+/// Recording errors are logged and don't interrupt the session.
+pub struct RecordingTransport<T: Transport> {
+    inner: T,
+    recorder: Arc<SessionRecorder>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Record traffic of `inner` transport into file created at `path`
+    pub fn new(inner: T, path: impl AsRef<Path>) -> BBitResult<Self> {
+        Ok(Self {
+            inner,
+            recorder: Arc::new(SessionRecorder::create(path)?),
+        })
+    }
+
+    /// Recorded transport
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Write buffered records, it's done on disconnection and when notification stream is
+    /// dropped as well
+    pub fn flush(&self) -> BBitResult<()> {
+        self.recorder.flush()
+    }
+}
+
+/// Record data ignoring and logging recording errors
+fn record_logged(recorder: &SessionRecorder, kind: RecordKind, uuid: Uuid, data: &[u8]) {
+    if let Err(error) = recorder.record(kind, uuid, data) {
+        tracing::warn!("Could not record {kind:?} of {uuid}: {error}");
+    }
+}
+
+/// Recorder of notification stream, buffered records are flushed when the stream is dropped
+/// as nothing is recorded by it after that
+struct StreamRecorder(Arc<SessionRecorder>);
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        if let Err(error) = self.0.flush() {
+            tracing::warn!("Could not flush session file: {error}");
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn scan(
+        &self,
+        service: Option<Uuid>,
+        duration: Duration,
+    ) -> BBitResult<Vec<PeripheralProperties>> {
+        self.inner.scan(service, duration).await
+    }
+
+    async fn connect(&self, address: BDAddr) -> BBitResult<()> {
+        self.inner.connect(address).await
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.inner.is_connected().await
+    }
+
+    async fn discover_services(&self) -> BBitResult<()> {
+        self.inner.discover_services().await
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        self.inner.characteristics()
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BBitResult<Vec<u8>> {
+        self.inner.read(characteristic).await
+    }
+
+    async fn write(&self, characteristic: &Characteristic, data: &[u8]) -> BBitResult<()> {
+        self.inner.write(characteristic, data).await?;
+        record_logged(
+            &self.recorder,
+            RecordKind::Command,
+            characteristic.uuid,
+            data,
+        );
+        Ok(())
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BBitResult<()> {
+        self.inner.subscribe(characteristic).await
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BBitResult<()> {
+        self.inner.unsubscribe(characteristic).await
+    }
+
+    async fn notifications(&self) -> BBitResult<NotificationStream> {
+        let recorder = StreamRecorder(self.recorder.clone());
+        let stream = self
+            .inner
+            .notifications()
+            .await?
+            .inspect(move |notification| {
+                record_logged(
+                    &recorder.0,
+                    RecordKind::Notification,
+                    notification.uuid,
+                    &notification.value,
+                );
+            });
+        Ok(Box::pin(stream))
+    }
+
+    async fn disconnections(&self) -> BBitResult<DisconnectionStream> {
+        self.inner.disconnections().await
+    }
+
+    async fn disconnect(&self) -> BBitResult<()> {
+        let res = self.inner.disconnect().await;
+        if let Err(error) = self.recorder.flush() {
+            tracing::warn!("Could not flush session file: {error}");
+        }
+        res
+    }
+}
+
+/// Pace of [`SessionReplay`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Records are passed with recorded intervals
+    #[default]
+    RealTime,
+    /// Recorded intervals are divided by the factor, 2.0 replays twice as fast
+    Scaled(f64),
+    /// Records are passed without waiting
+    Unlimited,
+}
+
+impl ReplaySpeed {
+    /// Time since replay start when record captured at `at` is passed, `None` if it's
+    /// passed immediately
+    fn delay(&self, at: Duration) -> Option<Duration> {
+        match self {
+            ReplaySpeed::RealTime => Some(at),
+            ReplaySpeed::Scaled(factor) if *factor > 0.0 => Some(at.div_f64(*factor)),
+            ReplaySpeed::Scaled(_) | ReplaySpeed::Unlimited => None,
+        }
+    }
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = Error;
+
+    /// `max` is parsed as unlimited speed, a number as speed factor
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "max" {
+            return Ok(ReplaySpeed::Unlimited);
+        }
+        match s.parse::<f64>() {
+            Ok(factor) if factor > 0.0 => Ok(ReplaySpeed::Scaled(factor)),
+            _ => Err(Error::InvalidData(format!("replay speed '{s}'"))),
+        }
+    }
+}
+
+/// Feeds session recorded by [`RecordingTransport`] to [`EventHandler`] with the same
+/// decoding as the event loop does
+#[derive(Debug)]
+pub struct SessionReplay {
+    records: Vec<SessionRecord>,
+    profile: Arc<dyn DeviceProfile>,
+    gap_fill: GapFill,
+    battery: BatteryConfig,
+    lead_off_current_a: f64,
+    speed: ReplaySpeed,
+}
+
+impl SessionReplay {
+    /// Read session file, the session is decoded by [`BrainBitProfile`] in real time
+    pub fn open(path: impl AsRef<Path>) -> BBitResult<Self> {
+        let content = std::fs::read(path)?;
+        Ok(Self::new(SessionRecord::decode_all(&content)?))
+    }
+
+    pub fn new(records: Vec<SessionRecord>) -> Self {
+        Self {
+            records,
+            profile: Arc::new(BrainBitProfile),
+            gap_fill: GapFill::default(),
+            battery: BatteryConfig::default(),
+            lead_off_current_a: DEFAULT_LEAD_OFF_CURRENT_A,
+            speed: ReplaySpeed::default(),
+        }
+    }
+
+    /// Decode session of another device family
+    pub fn with_profile<P: DeviceProfile + 'static>(mut self, profile: P) -> Self {
+        self.profile = Arc::new(profile);
+        self
+    }
+
+    /// Replace dropped EEG packets like [`crate::bbit::device::BBitSensor::fill_gaps`] does
+    pub fn fill_gaps(mut self, gap_fill: GapFill) -> Self {
+        self.gap_fill = gap_fill;
+        self
+    }
+
+    /// Monitor battery like [`crate::bbit::device::BBitSensor::battery`] does
+    pub fn battery(mut self, config: BatteryConfig) -> Self {
+        self.battery = config;
+        self
+    }
+
+    /// Compute electrode impedance like [`crate::bbit::device::BBitSensor::lead_off_current`]
+    /// does
+    pub fn lead_off_current(mut self, lead_off_current_a: f64) -> Self {
+        self.lead_off_current_a = lead_off_current_a;
+        self
+    }
+
+    pub fn speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn records(&self) -> &[SessionRecord] {
+        &self.records
+    }
+
+    /// Pass recorded notifications to the handler until the session ends or
+    /// [`EventHandler::should_continue`] returns false. Recorded commands switch decoding
+    /// of measurement packets. Handler error interrupts the replay.
+    pub async fn run<H: EventHandler + Send + Sync>(&self, handler: &mut H) -> BBitResult<()> {
+        let started = Instant::now();
+        let mut measurement = MeasurementState::new(self.gap_fill, self.profile.clone())
+            .with_lead_off_current(self.lead_off_current_a);
+        let mut battery = BatteryMonitor::new(self.battery);
+        for record in &self.records {
+            if let Some(delay) = self.speed.delay(record.at) {
+                tokio::time::sleep_until(started + delay).await;
+            }
+            match record.kind {
+                RecordKind::Command => {
+                    if Some(record.uuid) != self.profile.command_uuid() {
+                        continue;
+                    }
+                    let command = ControlPointCommand::try_from(record.data.as_slice());
+                    debug!("replay - command: {command:?}");
+                    measurement.reset_decoders(command.as_ref().ok());
+                }
+                RecordKind::Notification => {
+                    let notification = ValueNotification {
+                        uuid: record.uuid,
+                        value: record.data.clone(),
+                    };
+                    match BluetoothEvent::from_notification(notification, self.profile.as_ref()) {
+                        Some(BluetoothEvent::DeviceStatus(status_data)) => {
+                            handler.device_status_update(status_data).await?;
+                            // battery is estimated by recorded time at any replay speed
+                            if let Some(status) = battery.update(&status_data, started + record.at)
+                            {
+                                handler.battery_update(status).await?;
+                            }
+                        }
+                        Some(BluetoothEvent::EggOrResistanceData(data)) => {
+                            measurement.dispatch(handler, data).await?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            if !handler.should_continue().await {
+                debug!("replay is finished by handler");
+                break;
+            }
+        }
+        handler.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbit::device::{measurement_command, BBitSensor};
+    use crate::bbit::internals::{EegConfig, MeasurementType};
+    use crate::bbit::responses::{encode_counts, EegPacket};
+    use crate::bbit::traits::HandlerResult;
+    use crate::bbit::uuids::{
+        EventType, EEG_DATA_NOTIFY_CHARACTERISTIC_UUID, PERIPHERAL_NAME_MATCH_FILTER,
+        WRITE_COMMAN_UUID,
+    };
+    use crate::sim::SimulatedBrainBit;
+
+    #[derive(Debug, Default)]
+    struct PacketCounter {
+        packets: Vec<u16>,
+    }
+
+    #[async_trait]
+    impl EventHandler for PacketCounter {
+        async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+            self.packets.push(packet.counter);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_session_records() {
+        let records = vec![
+            SessionRecord {
+                kind: RecordKind::Command,
+                at: Duration::from_micros(1),
+                uuid: Uuid::from_u128(1),
+                data: vec![0x01],
+            },
+            SessionRecord {
+                kind: RecordKind::Notification,
+                at: Duration::from_secs(3600),
+                uuid: Uuid::from_u128(2),
+                data: vec![0xAB; 20],
+            },
+        ];
+        let mut content = SESSION_FILE_MAGIC.to_vec();
+        content.push(SESSION_FILE_VERSION);
+        for record in &records {
+            record.encode(&mut content).unwrap();
+        }
+        assert_eq!(records, SessionRecord::decode_all(&content).unwrap());
+        // the last record is dropped if the recording is interrupted
+        let truncated = &content[..content.len() - 1];
+        assert_eq!(records[..1], SessionRecord::decode_all(truncated).unwrap());
+        assert!(SessionRecord::decode_all(b"BBRS\x02").is_err());
+        assert!(SessionRecord::decode_all(b"EEG").is_err());
+
+        let at = Duration::from_secs(2);
+        assert_eq!(Some(at), ReplaySpeed::RealTime.delay(at));
+        assert_eq!(
+            Some(Duration::from_secs(1)),
+            ReplaySpeed::Scaled(2.0).delay(at)
+        );
+        assert_eq!(None, ReplaySpeed::Unlimited.delay(at));
+        assert_eq!(ReplaySpeed::Unlimited, "max".parse().unwrap());
+        assert_eq!(ReplaySpeed::Scaled(0.5), "0.5".parse().unwrap());
+        assert!("0".parse::<ReplaySpeed>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_skips_repeated_packets() {
+        let command = measurement_command(MeasurementType::Eeg(EegConfig::default()));
+        let mut records = vec![SessionRecord {
+            kind: RecordKind::Command,
+            at: Duration::ZERO,
+            uuid: WRITE_COMMAN_UUID,
+            data: command.try_into().unwrap(),
+        }];
+        records.extend([10, 11, 11, 13, 12, 14].map(|counter| SessionRecord {
+            kind: RecordKind::Notification,
+            at: Duration::ZERO,
+            uuid: EEG_DATA_NOTIFY_CHARACTERISTIC_UUID,
+            data: encode_counts(counter, &Default::default()),
+        }));
+
+        let mut replayed = PacketCounter::default();
+        let replay = SessionReplay::new(records).speed(ReplaySpeed::Unlimited);
+        replay.run(&mut replayed).await.unwrap();
+        // late packet is passed as is when nothing was filled in its place
+        assert_eq!(vec![10, 11, 13, 12, 14], replayed.packets);
+
+        let mut replayed = PacketCounter::default();
+        replay
+            .fill_gaps(GapFill::Nan)
+            .run(&mut replayed)
+            .await
+            .unwrap();
+        assert_eq!(vec![10, 11, 12, 13, 14], replayed.packets);
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let session_file =
+            std::env::temp_dir().join(format!("bbit_record_replay_{}.bbrs", std::process::id()));
+        let transport =
+            RecordingTransport::new(SimulatedBrainBit::default(), &session_file).unwrap();
+        let handle = BBitSensor::with_transport(transport)
+            .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+            .await
+            .unwrap()
+            .listen(EventType::State)
+            .listen(EventType::EegOrResistance)
+            .build()
+            .await
+            .unwrap()
+            .event_loop(PacketCounter::default())
+            .await;
+        handle
+            .start_eeg(EegConfig::default())
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        handle.stop().await;
+
+        let replay = SessionReplay::open(&session_file)
+            .unwrap()
+            .speed(ReplaySpeed::Unlimited);
+        let commands = replay
+            .records()
+            .iter()
+            .filter(|record| record.kind == RecordKind::Command)
+            .count();
+        // stop on build, start of EEG and stop on shutdown
+        assert_eq!(3, commands);
+        let mut replayed = PacketCounter::default();
+        replay.run(&mut replayed).await.unwrap();
+        assert!(replayed.packets.len() > 5);
+        assert!(replayed
+            .packets
+            .windows(2)
+            .all(|pair| pair[1] == pair[0].wrapping_add(1)));
+        std::fs::remove_file(&session_file).unwrap();
+    }
+}
@@ -1,3 +1,8 @@
+use std::time::Duration;
+
+use crate::bbit::internals::ChannelType;
+use crate::bbit::responses::ResistanceReading;
+
 /// Structure for storing result of resistance measurement on every electrode
 /// Data is computed and quality of electrode's contact
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,3 +37,204 @@ pub enum ResistsMeasureResult {
     /// Bad electrode's to head contact
     BAD,
 }
+
+impl ResistState {
+    /// Result for specified electrode
+    pub fn get(&self, channel: ChannelType) -> ResistsMeasureResult {
+        match channel {
+            ChannelType::O1 => self.ch_o1,
+            ChannelType::T3 => self.ch_t3,
+            ChannelType::T4 => self.ch_t4,
+            ChannelType::O2 => self.ch_o2,
+        }
+    }
+
+    /// Store result for specified electrode
+    pub fn set(&mut self, channel: ChannelType, result: ResistsMeasureResult) {
+        match channel {
+            ChannelType::O1 => self.ch_o1 = result,
+            ChannelType::T3 => self.ch_t3 = result,
+            ChannelType::T4 => self.ch_t4 = result,
+            ChannelType::O2 => self.ch_o2 = result,
+        }
+    }
+
+    /// All electrodes have good contact
+    pub fn is_good(&self) -> bool {
+        ChannelType::ALL
+            .iter()
+            .all(|channel| self.get(*channel) == ResistsMeasureResult::GOOD)
+    }
+}
+
+/// Settings of resistance sweep over all electrodes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResistanceSweepConfig {
+    /// Readings skipped on every channel while lead-off current settles
+    pub skip_readings: usize,
+    /// Readings averaged on every channel after skipped ones
+    pub average_readings: usize,
+    /// Lowest impedance treated as good contact, lower values mean shorted electrodes
+    pub min_good_ohms: f64,
+    /// Highest impedance treated as good contact
+    pub max_good_ohms: f64,
+    /// Maximum time to wait for readings on one channel
+    pub channel_timeout: Duration,
+}
+
+impl Default for ResistanceSweepConfig {
+    fn default() -> Self {
+        Self {
+            skip_readings: 20,
+            average_readings: 20,
+            min_good_ohms: 1_000.0,
+            max_good_ohms: 500_000.0,
+            channel_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ResistanceSweepConfig {
+    /// Contact quality for averaged impedance
+    pub fn classify(&self, ohms: f64) -> ResistsMeasureResult {
+        if (self.min_good_ohms..=self.max_good_ohms).contains(&ohms) {
+            ResistsMeasureResult::GOOD
+        } else {
+            ResistsMeasureResult::BAD
+        }
+    }
+}
+
+/// Next action of the resistance sweep after a reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepStep {
+    /// More readings are needed on current channel
+    Continue,
+    /// Current channel is done, measurement should be started on the next channel
+    NextChannel(ChannelType),
+    /// All channels are measured
+    Done(ResistState),
+}
+
+/// Sweep of resistance measurement over O1 -> T3 -> T4 -> O2 electrodes
+#[derive(Debug, Clone)]
+pub struct ResistanceSweep {
+    config: ResistanceSweepConfig,
+    /// Index of measured channel in [`ChannelType::ALL`]
+    channel_index: usize,
+    skipped: usize,
+    sum_ohms: f64,
+    averaged: usize,
+    /// Averaged impedance of already measured electrodes
+    ohms: [Option<f64>; 4],
+    state: ResistState,
+}
+
+impl ResistanceSweep {
+    pub fn new(config: ResistanceSweepConfig) -> Self {
+        Self {
+            config,
+            channel_index: 0,
+            skipped: 0,
+            sum_ohms: 0.0,
+            averaged: 0,
+            ohms: [None; 4],
+            state: ResistState::default(),
+        }
+    }
+
+    /// Sweep settings
+    pub fn config(&self) -> &ResistanceSweepConfig {
+        &self.config
+    }
+
+    /// Electrode measured now
+    pub fn channel(&self) -> ChannelType {
+        ChannelType::ALL[self.channel_index]
+    }
+
+    /// Averaged impedance of electrode, present when the electrode is measured
+    pub fn ohms(&self, channel: ChannelType) -> Option<f64> {
+        self.ohms[channel as usize]
+    }
+
+    /// Account a reading, readings of other electrodes are ignored
+    pub fn push(&mut self, reading: ResistanceReading) -> SweepStep {
+        if reading.channel != self.channel() {
+            return SweepStep::Continue;
+        }
+        if self.skipped < self.config.skip_readings {
+            self.skipped += 1;
+            return SweepStep::Continue;
+        }
+        self.sum_ohms += reading.ohms;
+        self.averaged += 1;
+        if self.averaged < self.config.average_readings.max(1) {
+            return SweepStep::Continue;
+        }
+
+        let channel = self.channel();
+        let ohms = self.sum_ohms / self.averaged as f64;
+        self.ohms[channel as usize] = Some(ohms);
+        self.state.set(channel, self.config.classify(ohms));
+        self.skipped = 0;
+        self.sum_ohms = 0.0;
+        self.averaged = 0;
+        match ChannelType::ALL.get(self.channel_index + 1) {
+            Some(next) => {
+                self.channel_index += 1;
+                SweepStep::NextChannel(*next)
+            }
+            None => SweepStep::Done(self.state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(channel: ChannelType, ohms: f64) -> ResistanceReading {
+        ResistanceReading {
+            counter: 0,
+            channel,
+            ohms,
+        }
+    }
+
+    #[test]
+    fn test_resistance_sweep() {
+        let config = ResistanceSweepConfig {
+            skip_readings: 2,
+            average_readings: 3,
+            ..Default::default()
+        };
+        let mut sweep = ResistanceSweep::new(config);
+        let channel_ohms = [100_000.0, 2_000_000.0, 300_000.0, 10.0];
+        let mut result = None;
+        for (channel, ohms) in ChannelType::ALL.into_iter().zip(channel_ohms) {
+            assert_eq!(channel, sweep.channel());
+            // readings of other channels arriving after switch are ignored
+            let other = ChannelType::ALL[(channel as usize + 3) % 4];
+            assert_eq!(SweepStep::Continue, sweep.push(reading(other, 1.0)));
+            // settling readings are skipped
+            sweep.push(reading(channel, 1e9));
+            sweep.push(reading(channel, 1e9));
+            sweep.push(reading(channel, ohms - 1_000.0));
+            sweep.push(reading(channel, ohms + 1_000.0));
+            let step = sweep.push(reading(channel, ohms));
+            assert!((sweep.ohms(channel).unwrap() - ohms).abs() < 1e-6);
+            match step {
+                SweepStep::NextChannel(next) => assert_eq!(next, sweep.channel()),
+                SweepStep::Done(state) => result = Some(state),
+                SweepStep::Continue => panic!("channel {channel:?} is not finished"),
+            }
+        }
+        let state = result.unwrap();
+        assert_eq!(ResistsMeasureResult::GOOD, state.ch_o1);
+        assert_eq!(ResistsMeasureResult::BAD, state.ch_t3);
+        assert_eq!(ResistsMeasureResult::GOOD, state.ch_t4);
+        assert_eq!(ResistsMeasureResult::BAD, state.ch_o2);
+        assert!(!state.is_good());
+    }
+}
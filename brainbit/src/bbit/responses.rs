@@ -1,9 +1,21 @@
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bbit::control::{ControlCommandType, ControlPointCommand};
+use crate::bbit::errors::Error;
+use crate::bbit::internals::{ChannelType, Gain, MeasurementType};
+use crate::bbit::results::BBitResult;
+use crate::bbit::uuids::{COLIBRI_NAME_MATCH_FILTER, NEUROBLE_NAME_MATCH_FILTER};
 
 // Maximum battery level encoded in byte without sign (highest bit)
 pub(crate) const MAX_BATTERY_LEVEL: u8 = 0x57; // 87 in decimal
 
+/// Highest bit of battery level byte is set while the device is charging
+pub(crate) const BATTERY_CHARGING_FLAG: u8 = 0x80;
+
 /// A common device's state type
 // ??? Probably it's returned from UUID = 6E400002-B534-F393-68A9-E50E24DCCA9E (READ / NOTIFY)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,15 +32,149 @@ pub enum CommonDeviceState {
     Dfu,
 }
 
-/// Structure to contain HR data and RR interval.
+// Structure to contain HR data and RR interval.
 // #[derive(Debug, Clone)]
 // pub struct EggData {
 //     data: Vec<u16>,
 // }
 
+/// Model number reported by BrainBit
+const BRAINBIT_MODEL_NUMBER: &str = "65";
+
+/// Device model decoded from model number, other families report their device name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeviceModel {
+    /// Model number "65"
+    BrainBit,
+    /// Device name "Neurotech_Colibri_R"
+    ColibriR,
+    /// Device name "NeuroBLE"
+    NeuroBle,
+    /// Not known model number
+    Unknown(String),
+}
+
+impl From<&str> for DeviceModel {
+    fn from(value: &str) -> Self {
+        match value.trim() {
+            BRAINBIT_MODEL_NUMBER => DeviceModel::BrainBit,
+            name if name.starts_with(COLIBRI_NAME_MATCH_FILTER) => DeviceModel::ColibriR,
+            name if name.starts_with(NEUROBLE_NAME_MATCH_FILTER) => DeviceModel::NeuroBle,
+            other => DeviceModel::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl Display for DeviceModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceModel::BrainBit => write!(f, "BrainBit"),
+            DeviceModel::ColibriR => write!(f, "Colibri R"),
+            DeviceModel::NeuroBle => write!(f, "NeuroBLE"),
+            DeviceModel::Unknown(model) => write!(f, "Unknown '{model}'"),
+        }
+    }
+}
+
+/// Hardware or firmware revision like "6" or "1.2.3", missing parts are zeros
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Revision {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Revision {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for Revision {
+    type Err = Error;
+
+    /// Parse dot separated numbers, leading 'v' is skipped
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidData(format!("revision '{s}'"));
+        let trimmed = s.trim().trim_start_matches(['v', 'V']);
+        let mut parts = [0u32; 3];
+        for (index, part) in trimmed.split('.').enumerate() {
+            let slot = parts.get_mut(index).ok_or_else(invalid)?;
+            *slot = part.parse().map_err(|_| invalid())?;
+        }
+        Ok(Self::new(parts[0], parts[1], parts[2]))
+    }
+}
+
+impl Display for Revision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Layout of EEG and resistance notifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PacketFormat {
+    /// 11-bit counter and two samples of four 18-bit channels, see [`EegPacket`]
+    Nss2,
+    /// Not documented format
+    Unknown,
+}
+
+/// Features supported by a device, derived from [`DeviceInfo`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// EEG measurement can be started
+    pub eeg: bool,
+    /// Resistance measurement can be started
+    pub resistance: bool,
+    /// Channel gains accepted by EEG command
+    pub gains: Vec<Gain>,
+    /// Layout of data notifications
+    pub packet_format: PacketFormat,
+}
+
+impl Capabilities {
+    /// Check if the measurement can be started on device
+    pub fn check(&self, measure_type: &MeasurementType) -> BBitResult<()> {
+        match measure_type {
+            MeasurementType::Eeg(_) | MeasurementType::Resistance(_)
+                if self.packet_format == PacketFormat::Unknown =>
+            {
+                Err(Error::NotSupported(format!(
+                    "{measure_type:?} with unknown packet format"
+                )))
+            }
+            MeasurementType::Eeg(config) => {
+                if !self.eeg {
+                    return Err(Error::NotSupported("EEG measurement".to_string()));
+                }
+                match config
+                    .channels
+                    .iter()
+                    .find(|channel| !self.gains.contains(&channel.gain))
+                {
+                    Some(channel) => Err(Error::NotSupported(format!("{:?} gain", channel.gain))),
+                    None => Ok(()),
+                }
+            }
+            MeasurementType::Resistance(_) if !self.resistance => {
+                Err(Error::NotSupported("resistance measurement".to_string()))
+            }
+            MeasurementType::Resistance(_) => Ok(()),
+        }
+    }
+}
+
 /// Contains common information about device like:
 /// model, serial number, HW, SW revision
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DeviceInfo {
     model_number: String,
     serial_number: String,
@@ -50,6 +196,65 @@ impl DeviceInfo {
             firmware_revision,
         }
     }
+
+    /// Model number string as reported by device
+    pub fn model_number(&self) -> &str {
+        &self.model_number
+    }
+
+    /// Serial number string as reported by device
+    pub fn serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    /// Hardware revision string as reported by device, see [`Self::hardware_version`]
+    pub fn hardware_revision(&self) -> &str {
+        &self.hardware_revision
+    }
+
+    /// Firmware revision string as reported by device, see [`Self::firmware_version`]
+    pub fn firmware_revision(&self) -> &str {
+        &self.firmware_revision
+    }
+
+    /// Decoded model, "65" is BrainBit
+    pub fn model(&self) -> DeviceModel {
+        DeviceModel::from(self.model_number.as_str())
+    }
+
+    /// Parsed hardware revision, `None` if it's not a number
+    pub fn hardware_version(&self) -> Option<Revision> {
+        self.hardware_revision.parse().ok()
+    }
+
+    /// Parsed firmware revision, `None` if it's not a number
+    pub fn firmware_version(&self) -> Option<Revision> {
+        self.firmware_revision.parse().ok()
+    }
+
+    /// Features of the device, they don't depend on firmware revision. Unknown model number
+    /// is assumed to be a BrainBit, so measurements are not blocked by an unexpected string.
+    pub fn capabilities(&self) -> Capabilities {
+        let brainbit = || Capabilities {
+            eeg: true,
+            resistance: true,
+            gains: Gain::ALL.to_vec(),
+            packet_format: PacketFormat::Nss2,
+        };
+        match self.model() {
+            DeviceModel::BrainBit => brainbit(),
+            DeviceModel::Unknown(model_number) => {
+                tracing::warn!("Unknown model number '{model_number}', assuming BrainBit");
+                brainbit()
+            }
+            DeviceModel::ColibriR | DeviceModel::NeuroBle => Capabilities {
+                eeg: false,
+                resistance: false,
+                gains: vec![],
+                packet_format: PacketFormat::Unknown,
+            },
+        }
+    }
 }
 
 /// Common Device status data including NSS2 service state, Commands execution state, battery level, Firmware version
@@ -76,10 +281,17 @@ impl Default for DeviceStatusData {
 }
 
 impl DeviceStatusData {
-    /// Return battery charge level in % percents
+    /// Return battery charge level in % percents, charging flag is not counted
     pub fn get_battery_charge_level(&self) -> f32 {
-        (self.battery_level as f32) * 100.0 / MAX_BATTERY_LEVEL as f32
+        let level = (self.battery_level & !BATTERY_CHARGING_FLAG).min(MAX_BATTERY_LEVEL);
+        (level as f32) * 100.0 / MAX_BATTERY_LEVEL as f32
     }
+
+    /// Check if the device is charging
+    pub fn is_charging(&self) -> bool {
+        self.battery_level & BATTERY_CHARGING_FLAG != 0
+    }
+
     pub fn get_battery_charge_level_string(&self) -> Cow<'_, str> {
         let value = self.get_battery_charge_level();
         format!("{:03.1?}", value).into()
@@ -92,7 +304,7 @@ impl TryFrom<Vec<u8>> for DeviceStatusData {
     /// Create new instance of [`DeviceStatusData`] from Vec<u8>.
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
         if value.is_empty() || value.len() != 4 {
-            eprintln!("Invalid DeviceStatus result vec length {:?}", value);
+            tracing::warn!("Invalid DeviceStatus result vec length {:02X?}", value);
             return Err("device status Vec length");
         }
         let status_nss2 = Nss2Status::try_from(value[0])?;
@@ -113,10 +325,11 @@ impl Display for DeviceStatusData {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Status='{:?}', Err={:?}, Bat='{:03.1?}%'",
+            "Status='{:?}', Err={:?}, Bat='{:03.1?}%'{}",
             self.status_nss2,
             self.cmd_error,
-            self.get_battery_charge_level() // formatted as 89.7%
+            self.get_battery_charge_level(), // formatted as 89.7%
+            if self.is_charging() { " charging" } else { "" }
         )
     }
 }
@@ -149,9 +362,9 @@ impl TryFrom<u8> for Nss2Status {
         }
     }
 }
-impl Into<u8> for Nss2Status {
-    fn into(self) -> u8 {
-        self as u8
+impl From<Nss2Status> for u8 {
+    fn from(value: Nss2Status) -> Self {
+        value as u8
     }
 }
 
@@ -178,9 +391,277 @@ impl TryFrom<u8> for CommandExecutionState {
     }
 }
 
+/// Device sample rate per channel, Hz
+pub const SAMPLE_RATE_HZ: u32 = 250;
+/// Number of 4-channel samples in one EEG notification
+pub const SAMPLES_PER_PACKET: usize = 2;
+/// Length of EEG (or resistance) notification in bytes
+pub const EEG_PACKET_LENGTH: usize = 20;
+/// Packet counter is 11 bits long and wraps around
+pub const PACKET_COUNTER_MODULO: u16 = 2048;
+/// ADC reference voltage in microvolts
+pub(crate) const ADC_VREF_UV: f64 = 2_400_000.0;
+/// Max positive value of signed 18-bit ADC sample
+pub(crate) const ADC_MAX_COUNT: i32 = 0x1_FFFF;
+/// Bits per one ADC sample in packet
+const ADC_SAMPLE_BITS: u32 = 18;
+/// Lead-off current assumed for electrode under resistance test, amperes. It's 6 nA, the reset
+/// value of ILEAD_OFF bits 3:2 of ADS1294 LOFF register. Resistance start command sets only
+/// the lead-off channel masks, so the magnitude used by firmware is not known.
+pub const DEFAULT_LEAD_OFF_CURRENT_A: f64 = 6e-9;
+
+/// One sample of all four electrodes in microvolts
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EegSample {
+    /// Left occipital electrode
+    pub o1: f64,
+    /// Left temporal electrode
+    pub t3: f64,
+    /// Right temporal electrode
+    pub t4: f64,
+    /// Right occipital electrode
+    pub o2: f64,
+}
+
+impl EegSample {
+    /// Value of one channel in microvolts
+    pub fn channel(&self, channel: ChannelType) -> f64 {
+        match channel {
+            ChannelType::O1 => self.o1,
+            ChannelType::T3 => self.t3,
+            ChannelType::T4 => self.t4,
+            ChannelType::O2 => self.o2,
+        }
+    }
+}
+
+/// Decoded EEG notification received from `EEG_DATA_NOTIFY_CHARACTERISTIC_UUID`.
+///
+/// Packet layout is 20 bytes:
+/// - bytes 0..2 - big endian u16, upper 11 bits keep packet counter, lower 5 bits are marker
+/// - bytes 2..20 - two samples by four channels (O1, T3, T4, O2), every value is signed
+///   18-bit ADC count packed MSB first without padding
+#[derive(Debug, Clone, PartialEq)]
+pub struct EegPacket {
+    /// Packet sequence counter, 0..2047
+    pub counter: u16,
+    /// Samples in the order they were measured
+    pub samples: [EegSample; SAMPLES_PER_PACKET],
+}
+
+impl EegPacket {
+    /// Decode packet converting ADC counts to microvolts with per channel gains (O1, T3, T4, O2)
+    pub fn decode(data: &[u8], gains: &[Gain; 4]) -> BBitResult<Self> {
+        let (counter, counts) = decode_counts(data)?;
+        let mut samples = [EegSample::default(); SAMPLES_PER_PACKET];
+        for (sample, counts) in samples.iter_mut().zip(counts.iter()) {
+            let microvolts: [f64; 4] =
+                std::array::from_fn(|i| counts_to_microvolts(counts[i], gains[i]));
+            *sample = EegSample {
+                o1: microvolts[0],
+                t3: microvolts[1],
+                t4: microvolts[2],
+                o2: microvolts[3],
+            };
+        }
+        Ok(Self { counter, samples })
+    }
+}
+
+/// Decoder of EEG packets remembering gains sent to device
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EegDecoder {
+    gains: [Gain; 4],
+}
+
+impl EegDecoder {
+    /// Create decoder with gains of O1, T3, T4, O2 channels
+    pub fn new(gains: [Gain; 4]) -> Self {
+        Self { gains }
+    }
+
+    /// Create decoder with gains from `StartEegSignal` command config
+    pub(crate) fn from_command(command: &ControlPointCommand) -> BBitResult<Self> {
+        let config = match (&command.cmd_type, &command.data) {
+            (ControlCommandType::StartEegSignal, Some(config)) if config.len() == 4 => config,
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "Not an EEG start command {command:?}"
+                )))
+            }
+        };
+        let mut gains = [Gain::default(); 4];
+        for (gain, channel_config) in gains.iter_mut().zip(config) {
+            *gain = Gain::from_channel_config(*channel_config)?;
+        }
+        Ok(Self::new(gains))
+    }
+
+    /// Channel gains in order O1, T3, T4, O2
+    pub fn gains(&self) -> &[Gain; 4] {
+        &self.gains
+    }
+
+    /// Decode one EEG notification
+    pub fn decode(&self, data: &[u8]) -> BBitResult<EegPacket> {
+        EegPacket::decode(data, &self.gains)
+    }
+}
+
+/// Electrode impedance computed from one resistance mode packet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResistanceReading {
+    /// Packet sequence counter, 0..2047
+    pub counter: u16,
+    /// Electrode under test
+    pub channel: ChannelType,
+    /// Electrode to skin impedance in ohms
+    pub ohms: f64,
+}
+
+/// Decoder of packets received while resistance measurement is running.
+///
+/// Resistance packets have the same layout as [`EegPacket`]. Device injects lead-off current
+/// into electrode selected by `resist_sensp` bit mask, so the channel under test carries the
+/// voltage `I * R`. When the channel bit is set in `resist_flipp` the current polarity flips,
+/// so magnitudes of samples are averaged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResistanceDecoder {
+    channel: ChannelType,
+    gain: Gain,
+    lead_off_current_a: f64,
+}
+
+impl ResistanceDecoder {
+    /// Create decoder for electrode under test measured with specified gain, lead-off current
+    /// is [`DEFAULT_LEAD_OFF_CURRENT_A`]
+    pub fn new(channel: ChannelType, gain: Gain) -> Self {
+        Self {
+            channel,
+            gain,
+            lead_off_current_a: DEFAULT_LEAD_OFF_CURRENT_A,
+        }
+    }
+
+    /// Compute impedance for another lead-off current, amperes
+    pub fn with_lead_off_current(mut self, lead_off_current_a: f64) -> Self {
+        self.lead_off_current_a = lead_off_current_a;
+        self
+    }
+
+    /// Create decoder from `StartResist` command config layout:
+    /// four channel configs, `resist_sensp`, `resist_sensn`, `resist_flipp`
+    pub(crate) fn from_command(command: &ControlPointCommand) -> BBitResult<Self> {
+        let config = match (&command.cmd_type, &command.data) {
+            (ControlCommandType::StartResist, Some(config)) if config.len() == 7 => config,
+            _ => {
+                return Err(Error::InvalidData(format!(
+                    "Not a resistance start command {command:?}"
+                )))
+            }
+        };
+        let resist_sensp = config[4];
+        if resist_sensp.count_ones() != 1 || resist_sensp > 0x0F {
+            return Err(Error::InvalidData(format!(
+                "Resistance measured on several channels, resist_sensp = {resist_sensp:#04X}"
+            )));
+        }
+        let channel = ChannelType::new(resist_sensp.trailing_zeros() as u8)?;
+        let gain = Gain::from_channel_config(config[channel as usize])?;
+        Ok(Self::new(channel, gain))
+    }
+
+    /// Electrode under test
+    pub fn channel(&self) -> ChannelType {
+        self.channel
+    }
+
+    /// Decode one resistance packet
+    pub fn decode(&self, data: &[u8]) -> BBitResult<ResistanceReading> {
+        let (counter, counts) = decode_counts(data)?;
+        let mean_microvolts = counts
+            .iter()
+            .map(|sample| counts_to_microvolts(sample[self.channel as usize], self.gain).abs())
+            .sum::<f64>()
+            / SAMPLES_PER_PACKET as f64;
+        Ok(ResistanceReading {
+            counter,
+            channel: self.channel,
+            ohms: mean_microvolts * 1e-6 / self.lead_off_current_a,
+        })
+    }
+}
+
+/// Convert signed ADC count into microvolts
+pub(crate) fn counts_to_microvolts(count: i32, gain: Gain) -> f64 {
+    count as f64 * ADC_VREF_UV / (gain.factor() * ADC_MAX_COUNT as f64)
+}
+
+/// Input range of ADC with the gain in microvolts, samples reaching it are saturated
+pub fn full_scale_microvolts(gain: Gain) -> f64 {
+    counts_to_microvolts(ADC_MAX_COUNT, gain)
+}
+
+/// Convert microvolts into ADC count saturating at ADC range
+pub(crate) fn microvolts_to_counts(microvolts: f64, gain: Gain) -> i32 {
+    let count = (microvolts * gain.factor() * ADC_MAX_COUNT as f64 / ADC_VREF_UV).round();
+    count.clamp(-(ADC_MAX_COUNT as f64) - 1.0, ADC_MAX_COUNT as f64) as i32
+}
+
+/// Read packet sequence counter from EEG or resistance notification
+pub fn packet_counter(data: &[u8]) -> BBitResult<u16> {
+    if data.len() != EEG_PACKET_LENGTH {
+        return Err(Error::InvalidData(format!(
+            "EEG packet length {} (expected {EEG_PACKET_LENGTH})",
+            data.len()
+        )));
+    }
+    Ok(u16::from_be_bytes([data[0], data[1]]) >> 5)
+}
+
+/// Split packet into counter and raw signed ADC counts
+pub(crate) fn decode_counts(data: &[u8]) -> BBitResult<(u16, [[i32; 4]; SAMPLES_PER_PACKET])> {
+    let counter = packet_counter(data)?;
+    let mut counts = [[0i32; 4]; SAMPLES_PER_PACKET];
+    let mut accumulator: u64 = 0;
+    let mut bits = 0;
+    let mut bytes = data[2..].iter();
+    for value in counts.iter_mut().flatten() {
+        while bits < ADC_SAMPLE_BITS {
+            accumulator = (accumulator << 8) | *bytes.next().unwrap() as u64;
+            bits += 8;
+        }
+        bits -= ADC_SAMPLE_BITS;
+        let raw = ((accumulator >> bits) & 0x3_FFFF) as i32;
+        // sign extension of 18-bit value
+        *value = (raw << (32 - ADC_SAMPLE_BITS)) >> (32 - ADC_SAMPLE_BITS);
+    }
+    Ok((counter, counts))
+}
+
+/// Pack counter and signed ADC counts into packet, it's reverse of [`decode_counts`]
+pub(crate) fn encode_counts(counter: u16, counts: &[[i32; 4]; SAMPLES_PER_PACKET]) -> Vec<u8> {
+    let mut packet = ((counter % PACKET_COUNTER_MODULO) << 5)
+        .to_be_bytes()
+        .to_vec();
+    let mut accumulator: u64 = 0;
+    let mut bits = 0;
+    for value in counts.iter().flatten() {
+        let clamped = (*value).clamp(-ADC_MAX_COUNT - 1, ADC_MAX_COUNT);
+        accumulator = (accumulator << ADC_SAMPLE_BITS) | (clamped as u64 & 0x3_FFFF);
+        bits += ADC_SAMPLE_BITS;
+        while bits >= 8 {
+            bits -= 8;
+            packet.push((accumulator >> bits) as u8);
+        }
+    }
+    packet
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bbit::internals::EegConfig;
 
     #[test]
     fn test_max_battery_level() {
@@ -223,4 +704,172 @@ mod tests {
             status.get_battery_charge_level_string()
         );
     }
+
+    #[test]
+    fn test_charging_battery_level() {
+        let status: DeviceStatusData = vec![0x00, 0x00, BATTERY_CHARGING_FLAG | 0x51, 0x00]
+            .try_into()
+            .unwrap();
+        assert!(status.is_charging());
+        assert_eq!(93.10345, status.get_battery_charge_level());
+        let status: DeviceStatusData = vec![0x00, 0x00, 0xFF, 0x00].try_into().unwrap();
+        assert_eq!(100f32, status.get_battery_charge_level());
+        let status: DeviceStatusData = vec![0x00, 0x00, 0x51, 0x00].try_into().unwrap();
+        assert!(!status.is_charging());
+    }
+
+    #[test]
+    fn test_device_info() {
+        let info = DeviceInfo::new(
+            "65".to_string(),
+            "130101".to_string(),
+            "6".to_string(),
+            "1".to_string(),
+        );
+        assert_eq!(DeviceModel::BrainBit, info.model());
+        assert_eq!("130101", info.serial_number());
+        assert_eq!(Some(Revision::new(6, 0, 0)), info.hardware_version());
+        assert_eq!("1", info.firmware_revision());
+        assert_eq!(Some(Revision::new(1, 0, 0)), info.firmware_version());
+        assert!(Revision::new(1, 10, 0) > "1.9.3".parse().unwrap());
+        assert_eq!(Revision::new(2, 1, 0), "v2.1".parse().unwrap());
+        assert!("1.2.3.4".parse::<Revision>().is_err());
+        assert!("beta".parse::<Revision>().is_err());
+
+        let capabilities = info.capabilities();
+        assert_eq!(PacketFormat::Nss2, capabilities.packet_format);
+        let eeg = MeasurementType::Eeg(EegConfig::new(Gain::X12));
+        assert!(capabilities.check(&eeg).is_ok());
+        let old = DeviceInfo::new(
+            "65".to_string(),
+            "1".to_string(),
+            "5".to_string(),
+            "0.9".to_string(),
+        );
+        assert_eq!(capabilities, old.capabilities());
+
+        let neuroble = DeviceInfo::new(
+            "NeuroBLE".to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+        );
+        assert_eq!(DeviceModel::NeuroBle, neuroble.model());
+        assert!(neuroble
+            .capabilities()
+            .check(&MeasurementType::Resistance(ChannelType::O1))
+            .is_err());
+
+        let unknown = DeviceInfo::new(
+            "66".to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+        );
+        assert_eq!(capabilities, unknown.capabilities());
+
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(info, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn test_eeg_packet_decode() {
+        // counter = 5, first sample O1 = 1 (bits 000000000000000001), all others 0
+        let mut data = vec![0u8; EEG_PACKET_LENGTH];
+        data[1] = 5 << 5;
+        data[4] = 0b0100_0000;
+        let packet = EegPacket::decode(&data, &[Gain::X1; 4]).unwrap();
+        assert_eq!(5, packet.counter);
+        assert_eq!(ADC_VREF_UV / ADC_MAX_COUNT as f64, packet.samples[0].o1);
+        assert_eq!(0.0, packet.samples[0].t3);
+        assert_eq!(0.0, packet.samples[1].o2);
+    }
+
+    #[test]
+    fn test_eeg_packet_round_trip_with_gains() {
+        let counts = [
+            [1000, -1000, 0, ADC_MAX_COUNT],
+            [-ADC_MAX_COUNT - 1, 1, -1, 77],
+        ];
+        let data = encode_counts(2047, &counts);
+        assert_eq!(EEG_PACKET_LENGTH, data.len());
+        assert_eq!((2047, counts), decode_counts(&data).unwrap());
+
+        let gains = [Gain::X6, Gain::X1, Gain::X12, Gain::X6];
+        let packet = EegPacket::decode(&data, &gains).unwrap();
+        assert_eq!(2047, packet.counter);
+        let expected_o1 = 1000.0 * ADC_VREF_UV / (6.0 * ADC_MAX_COUNT as f64);
+        assert!((packet.samples[0].o1 - expected_o1).abs() < 1e-9);
+        assert!((packet.samples[0].channel(ChannelType::T3) + 18310.69).abs() < 0.01);
+        assert_eq!(0.0, packet.samples[0].t4);
+    }
+
+    #[test]
+    fn test_eeg_decoder_from_command() {
+        let command = ControlPointCommand::new(
+            ControlCommandType::StartEegSignal,
+            Some(vec![0x00, 0x10, 0x60, 0x48]),
+        );
+        let decoder = EegDecoder::from_command(&command).unwrap();
+        assert_eq!(&[Gain::X6, Gain::X1, Gain::X12, Gain::X4], decoder.gains());
+
+        let stop = ControlPointCommand::new(ControlCommandType::StopAll, None);
+        assert!(matches!(
+            EegDecoder::from_command(&stop),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_resistance_decoder() {
+        let command = ControlPointCommand::new(
+            ControlCommandType::StartResist,
+            Some(vec![0x48, 0x48, 0x91, 0x48, 0b0100, 0x05, 0x00]),
+        );
+        let decoder = ResistanceDecoder::from_command(&command).unwrap();
+        assert_eq!(ChannelType::T4, decoder.channel());
+
+        // 100 kOhm * 6 nA = 600 uV on T4 measured with gain 1, flipped polarity
+        let count = microvolts_to_counts(-600.0, Gain::X1);
+        let data = encode_counts(12, &[[0, 0, count, 0], [5000, 0, count, 0]]);
+        let reading = decoder.decode(&data).unwrap();
+        assert_eq!(12, reading.counter);
+        assert_eq!(ChannelType::T4, reading.channel);
+        assert!((reading.ohms - 100_000.0).abs() < 1_000.0);
+
+        // samples of opposite polarity don't cancel the voltage
+        let data = encode_counts(13, &[[0, 0, count, 0], [0, 0, -count, 0]]);
+        let reading = decoder.decode(&data).unwrap();
+        assert!((reading.ohms - 100_000.0).abs() < 1_000.0);
+
+        // twice the current develops the same voltage on a half of impedance
+        let reading = decoder.with_lead_off_current(12e-9).decode(&data).unwrap();
+        assert!((reading.ohms - 50_000.0).abs() < 500.0);
+    }
+
+    #[test]
+    fn test_resistance_decoder_rejects_invalid_command() {
+        let several_channels = ControlPointCommand::new(
+            ControlCommandType::StartResist,
+            Some(vec![0x91, 0x91, 0x48, 0x48, 0b0011, 0x01, 0x00]),
+        );
+        assert!(ResistanceDecoder::from_command(&several_channels).is_err());
+        let eeg = ControlPointCommand::new(ControlCommandType::StartEegSignal, Some(vec![0; 4]));
+        assert!(ResistanceDecoder::from_command(&eeg).is_err());
+        let decoder = ResistanceDecoder::new(ChannelType::O1, Gain::X1);
+        assert!(matches!(
+            decoder.decode(&[0; 3]),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_malformed_eeg_packet() {
+        let short = vec![0u8; EEG_PACKET_LENGTH - 1];
+        assert!(matches!(
+            EegPacket::decode(&short, &[Gain::X6; 4]),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(EegDecoder::default().decode(&[]).is_err());
+    }
 }
@@ -1,3 +1,13 @@
+use std::time::Duration;
+
+use crate::bbit::battery::BatteryConfig;
+use crate::bbit::control::DEFAULT_COMMAND_TIMEOUT;
+use crate::bbit::discovery::ConnectTarget;
+use crate::bbit::packet_loss::GapFill;
+use crate::bbit::policy::ConnectPolicy;
+use crate::bbit::responses::DEFAULT_LEAD_OFF_CURRENT_A;
+use crate::bbit::traits::HandlerErrorPolicy;
+
 // Sealed Traits
 // So crate users will not implement [`Level`] on any type to make weird [`BLESensor`]s
 
@@ -14,24 +24,83 @@ mod internal {
 }
 
 /// [`BleSensor`] level for connecting to your device
-pub struct Bluetooth;
+#[derive(Default)]
+pub struct Bluetooth {
+    /// How to connect and reconnect to the device
+    pub connect_policy: ConnectPolicy,
+}
 
 impl internal::Level for Bluetooth {}
 
 /// [`BleSensor`] level for registering data types to listen for
-#[derive(Default)]
 pub struct Configure {
     /// Is subscribed to device status changes, cmd errors, battery
     pub device_status: bool,
     /// Is subscribed to EEG or Resistance stream
     pub eeg_rate: bool,
+    /// How dropped EEG packets are replaced
+    pub gap_fill: GapFill,
+    /// Maximum time to wait for device status confirming a command
+    pub command_timeout: Duration,
+    /// Device selection used to connect, it's used again on reconnection
+    pub target: ConnectTarget,
+    /// Reconnect automatically when BLE link is lost
+    pub reconnect: bool,
+    /// Scan duration, delays and cancellation used on reconnection
+    pub connect_policy: ConnectPolicy,
+    /// What the event loop does when handler returns error
+    pub handler_error_policy: HandlerErrorPolicy,
+    /// Shut down the event loop on Ctrl-C
+    pub stop_on_ctrl_c: bool,
+    /// Battery thresholds and smoothing
+    pub battery: BatteryConfig,
+    /// Lead-off current used to compute electrode impedance, amperes
+    pub lead_off_current_a: f64,
+}
+
+impl Configure {
+    /// Default configuration of the device connected by `target` with `connect_policy`
+    pub fn new(target: ConnectTarget, connect_policy: ConnectPolicy) -> Self {
+        Self {
+            device_status: false,
+            eeg_rate: false,
+            gap_fill: GapFill::default(),
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            target,
+            reconnect: true,
+            connect_policy,
+            handler_error_policy: HandlerErrorPolicy::default(),
+            stop_on_ctrl_c: false,
+            battery: BatteryConfig::default(),
+            lead_off_current_a: DEFAULT_LEAD_OFF_CURRENT_A,
+        }
+    }
 }
 
 impl internal::Level for Configure {}
 impl Connected for Configure {}
 
 /// [`BleSensor`] level for starting the event loop
-pub struct EventLoop;
+pub struct EventLoop {
+    /// How dropped EEG packets are replaced
+    pub gap_fill: GapFill,
+    /// Maximum time to wait for device status confirming a command
+    pub command_timeout: Duration,
+    /// Device selection used to connect, it's used again on reconnection
+    pub target: ConnectTarget,
+    /// Reconnect automatically when BLE link is lost
+    pub reconnect: bool,
+    /// Scan duration, delays and cancellation used on reconnection
+    pub connect_policy: ConnectPolicy,
+    /// What the event loop does when handler returns error
+    pub handler_error_policy: HandlerErrorPolicy,
+    /// Shut down the event loop on Ctrl-C
+    pub stop_on_ctrl_c: bool,
+    /// Battery thresholds and smoothing
+    pub battery: BatteryConfig,
+    /// Lead-off current used to compute electrode impedance, amperes
+    pub lead_off_current_a: f64,
+}
 
 impl internal::Level for EventLoop {}
 impl Connected for EventLoop {}
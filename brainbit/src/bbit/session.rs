@@ -0,0 +1,291 @@
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::instrument;
+
+use crate::bbit::battery::BatteryStatus;
+use crate::bbit::device::{BBitSensor, BleHandle, ConnectionEvent};
+use crate::bbit::discovery::ConnectTarget;
+use crate::bbit::errors::Error;
+use crate::bbit::internals::EegConfig;
+use crate::bbit::packet_loss::PacketGap;
+use crate::bbit::resist::{ResistState, ResistanceSweepConfig};
+use crate::bbit::responses::{DeviceStatusData, EegPacket, ResistanceReading};
+use crate::bbit::results::BBitResult;
+use crate::bbit::sealed::Bluetooth;
+use crate::bbit::stream::{SensorEvent, SENSOR_EVENT_BUFFER};
+use crate::bbit::traits::{EventHandler, HandlerResult};
+use crate::bbit::transport::Transport;
+use crate::bbit::uuids::EventType;
+
+/// One headset of a [`SessionGroup`]
+pub struct SessionMember<T: Transport> {
+    /// Identifier reported in every [`GroupEvent`] of this headset
+    pub id: String,
+    /// Not connected sensor
+    pub sensor: BBitSensor<Bluetooth, T>,
+    /// Which headset to connect the sensor to
+    pub target: ConnectTarget,
+}
+
+/// Event of one headset stamped by the shared host clock
+#[derive(Debug, Clone)]
+pub struct GroupEvent {
+    /// [`SessionMember::id`] of the headset
+    pub device_id: String,
+    /// Time of receiving since [`SessionGroup::started_at`]
+    pub host_time: Duration,
+    pub event: SensorEvent,
+}
+
+/// Several headsets connected at once, measurements are started and stopped on all of them
+/// together and their events are merged into one stream.
+pub struct SessionGroup {
+    handles: Vec<(String, BleHandle)>,
+    events: mpsc::Receiver<GroupEvent>,
+    started_at: SystemTime,
+}
+
+impl SessionGroup {
+    /// Connect all members concurrently and start their event loops.
+    /// Fails if any of headsets is not connected, the connected ones are stopped then.
+    #[instrument(skip_all)]
+    pub async fn connect<T: Transport>(members: Vec<SessionMember<T>>) -> BBitResult<Self> {
+        let (tx, events) = mpsc::channel(SENSOR_EVENT_BUFFER);
+        let started_at = SystemTime::now();
+        let origin = Instant::now();
+
+        let connected = join_all(members.into_iter().map(|member| {
+            let handler = GroupHandler {
+                device_id: member.id.clone(),
+                origin,
+                tx: tx.clone(),
+            };
+            async move {
+                let res = Self::connect_member(member.sensor, member.target, handler).await;
+                (member.id, res)
+            }
+        }))
+        .await;
+
+        let mut handles = vec![];
+        let mut error = None;
+        for (device_id, res) in connected {
+            match res {
+                Ok(handle) => handles.push((device_id, handle)),
+                Err(e) => {
+                    tracing::error!("Could not connect '{device_id}': {e}");
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(error) = error {
+            join_all(handles.into_iter().map(|(_, handle)| handle.stop())).await;
+            return Err(error);
+        }
+        Ok(Self {
+            handles,
+            events,
+            started_at,
+        })
+    }
+
+    async fn connect_member<T: Transport>(
+        sensor: BBitSensor<Bluetooth, T>,
+        target: ConnectTarget,
+        handler: GroupHandler,
+    ) -> BBitResult<BleHandle> {
+        let sensor = sensor
+            .connect_to(target)
+            .await?
+            .listen(EventType::State)
+            .listen(EventType::EegOrResistance)
+            .build()
+            .await?;
+        Ok(sensor.event_loop(handler).await)
+    }
+
+    /// Wall clock time of [`GroupEvent::host_time`] origin
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    /// Handles of connected headsets with their identifiers
+    pub fn handles(&self) -> &[(String, BleHandle)] {
+        &self.handles
+    }
+
+    /// Next event of any headset
+    pub async fn next_event(&mut self) -> Option<GroupEvent> {
+        self.events.recv().await
+    }
+
+    /// Start EEG measurement on all headsets
+    #[instrument(skip(self))]
+    pub async fn start_eeg(&self, config: EegConfig) -> BBitResult<()> {
+        let results = join_all(
+            self.handles
+                .iter()
+                .map(|(_, handle)| handle.start_eeg(config)),
+        )
+        .await;
+        self.first_error(results)
+    }
+
+    /// Stop measurement on all headsets, event loops keep running
+    #[instrument(skip(self))]
+    pub async fn stop_measurement(&self) -> BBitResult<()> {
+        let results = join_all(
+            self.handles
+                .iter()
+                .map(|(_, handle)| handle.stop_measurement()),
+        )
+        .await;
+        self.first_error(results)
+    }
+
+    /// Check electrodes contact on all headsets at once
+    #[instrument(skip(self))]
+    pub async fn measure_resistance(
+        &self,
+        config: ResistanceSweepConfig,
+    ) -> BBitResult<Vec<(String, ResistState)>> {
+        let results = join_all(
+            self.handles
+                .iter()
+                .map(|(_, handle)| handle.measure_resistance(config)),
+        )
+        .await;
+        let mut states = vec![];
+        for ((device_id, _), res) in self.handles.iter().zip(results) {
+            let state = res.unwrap_or(Err(Error::NotConnected)).inspect_err(|e| {
+                tracing::error!("Resistance measurement failed on '{device_id}': {e}")
+            })?;
+            states.push((device_id.clone(), state));
+        }
+        Ok(states)
+    }
+
+    /// Stop measurement and event loops of all headsets
+    #[instrument(skip(self))]
+    pub async fn stop(self) {
+        join_all(self.handles.into_iter().map(|(_, handle)| handle.stop())).await;
+    }
+
+    fn first_error(&self, results: Vec<Option<BBitResult<()>>>) -> BBitResult<()> {
+        let mut error = None;
+        for ((device_id, _), res) in self.handles.iter().zip(results) {
+            if let Err(e) = res.unwrap_or(Err(Error::NotConnected)) {
+                tracing::error!("Command failed on '{device_id}': {e}");
+                error.get_or_insert(e);
+            }
+        }
+        error.map_or(Ok(()), Err)
+    }
+}
+
+/// Handler of one headset forwarding its events into the group stream
+struct GroupHandler {
+    device_id: String,
+    origin: Instant,
+    tx: mpsc::Sender<GroupEvent>,
+}
+
+impl GroupHandler {
+    fn send(&self, event: SensorEvent) {
+        let event = GroupEvent {
+            device_id: self.device_id.clone(),
+            host_time: self.origin.elapsed(),
+            event,
+        };
+        if let Err(mpsc::error::TrySendError::Full(event)) = self.tx.try_send(event) {
+            tracing::warn!("Group events are not consumed, dropping {event:?}");
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for GroupHandler {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
+        self.send(SensorEvent::DeviceStatus(status_data));
+        Ok(())
+    }
+
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        self.send(SensorEvent::EegPacket(packet));
+        Ok(())
+    }
+
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        self.send(SensorEvent::Resistance(reading));
+        Ok(())
+    }
+
+    async fn packet_loss_update(&mut self, gap: PacketGap) -> HandlerResult {
+        self.send(SensorEvent::PacketLoss(gap));
+        Ok(())
+    }
+
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        self.send(SensorEvent::Connection(event));
+        Ok(())
+    }
+
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        self.send(SensorEvent::Battery(battery));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbit::responses::Nss2Status;
+    use crate::sim::{SimConfig, SimulatedBrainBit};
+    use btleplug::api::BDAddr;
+
+    #[tokio::test]
+    async fn test_group_session() {
+        let first = SimulatedBrainBit::default();
+        let second = SimulatedBrainBit::new(SimConfig {
+            address: BDAddr::from([0xD1, 0x97, 0x4B, 0x0D, 0xC7, 0xF2]),
+            serial_number: "130102".to_string(),
+            ..Default::default()
+        });
+        let members = [("first", &first), ("second", &second)]
+            .into_iter()
+            .map(|(id, device)| SessionMember {
+                id: id.to_string(),
+                sensor: BBitSensor::with_transport(device.clone()),
+                target: ConnectTarget::Name("BrainBit".to_string()),
+            })
+            .collect();
+        let mut group = SessionGroup::connect(members).await.unwrap();
+        assert_eq!(2, group.handles().len());
+
+        group.start_eeg(EegConfig::default()).await.unwrap();
+        assert_eq!(Nss2Status::EegTransmission, first.status().status_nss2);
+        assert_eq!(Nss2Status::EegTransmission, second.status().status_nss2);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        group.stop_measurement().await.unwrap();
+        assert_eq!(Nss2Status::Stopped, first.status().status_nss2);
+        assert_eq!(Nss2Status::Stopped, second.status().status_nss2);
+
+        let mut packets = [0, 0];
+        let mut last_time = Duration::ZERO;
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(50), group.next_event()).await
+        {
+            assert!(event.host_time >= last_time);
+            last_time = event.host_time;
+            if let SensorEvent::EegPacket(_) = event.event {
+                packets[usize::from(event.device_id == "second")] += 1;
+            }
+        }
+        assert!(packets.iter().all(|count| *count > 10));
+        group.stop().await;
+    }
+}
@@ -0,0 +1,373 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures::Stream;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::bbit::battery::BatteryStatus;
+use crate::bbit::device::{BleHandle, CommandData, ConnectionEvent, LoopOutcome};
+use crate::bbit::packet_loss::PacketGap;
+use crate::bbit::responses::{DeviceStatusData, EegPacket, ResistanceReading};
+use crate::bbit::traits::{EventHandler, HandlerResult};
+
+/// Number of events buffered for the stream consumer, newer events are dropped when it's full
+pub(crate) const SENSOR_EVENT_BUFFER: usize = 4096;
+
+/// Number of events kept for every [`EventSubscriber`], a subscriber lagging by more events
+/// loses the oldest ones
+pub(crate) const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Typed event received from a device, the same data as passed to [`EventHandler`]
+#[derive(Debug, Clone)]
+pub enum SensorEvent {
+    /// Device status, cmd error, battery level
+    DeviceStatus(DeviceStatusData),
+    /// Decoded EEG samples, gap-filled packets included
+    EegPacket(EegPacket),
+    /// Impedance of the electrode under test
+    Resistance(ResistanceReading),
+    /// Dropped, duplicated or reordered EEG packets
+    PacketLoss(PacketGap),
+    /// BLE link is lost or restored
+    Connection(ConnectionEvent),
+    /// Battery level trend, charging state or crossed threshold
+    Battery(BatteryStatus),
+}
+
+/// Stream of [`SensorEvent`] produced by [`crate::bbit::device::BBitSensor::into_stream`].
+///
+/// The stream ends when the event loop is finished.
+pub struct SensorStream {
+    handle: BleHandle,
+    events: mpsc::Receiver<SensorEvent>,
+}
+
+impl SensorStream {
+    pub(crate) fn new(handle: BleHandle, events: mpsc::Receiver<SensorEvent>) -> Self {
+        Self { handle, events }
+    }
+
+    /// Handle to start and stop measurements of the streamed device
+    pub fn handle(&self) -> &BleHandle {
+        &self.handle
+    }
+
+    /// Split into handle and plain receiver of events
+    pub fn into_parts(self) -> (BleHandle, mpsc::Receiver<SensorEvent>) {
+        (self.handle, self.events)
+    }
+
+    /// Stop measurement, disconnect and finish the event loop, see [`BleHandle::stop`]
+    pub async fn stop(self) -> LoopOutcome {
+        self.handle.stop().await
+    }
+}
+
+impl Stream for SensorStream {
+    type Item = SensorEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+/// Handler forwarding events into [`SensorStream`]
+pub(crate) struct StreamHandler {
+    tx: mpsc::Sender<SensorEvent>,
+}
+
+impl StreamHandler {
+    pub(crate) fn new(tx: mpsc::Sender<SensorEvent>) -> Self {
+        Self { tx }
+    }
+
+    fn send(&self, event: SensorEvent) {
+        if let Err(mpsc::error::TrySendError::Full(event)) = self.tx.try_send(event) {
+            tracing::warn!("Sensor events are not consumed, dropping {event:?}");
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for StreamHandler {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
+        self.send(SensorEvent::DeviceStatus(status_data));
+        Ok(())
+    }
+
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        self.send(SensorEvent::EegPacket(packet));
+        Ok(())
+    }
+
+    async fn packet_loss_update(&mut self, gap: PacketGap) -> HandlerResult {
+        self.send(SensorEvent::PacketLoss(gap));
+        Ok(())
+    }
+
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        self.send(SensorEvent::Resistance(reading));
+        Ok(())
+    }
+
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        self.send(SensorEvent::Connection(event));
+        Ok(())
+    }
+
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        self.send(SensorEvent::Battery(battery));
+        Ok(())
+    }
+}
+
+/// Result of [`EventSubscriber::recv`]
+#[derive(Debug, Clone)]
+pub enum Received {
+    /// The next event
+    Event(SensorEvent),
+    /// Subscriber was too slow, this number of the oldest events was skipped
+    Lagged(u64),
+}
+
+/// Independent receiver of device events created by [`BleHandle::subscribe`].
+///
+/// Every subscriber gets every event, a slow one loses its oldest events and never
+/// blocks the event loop or other subscribers.
+pub struct EventSubscriber {
+    rx: broadcast::Receiver<SensorEvent>,
+    lagged: u64,
+}
+
+impl EventSubscriber {
+    pub(crate) fn new(rx: broadcast::Receiver<SensorEvent>) -> Self {
+        Self { rx, lagged: 0 }
+    }
+
+    /// Receive the next event or number of skipped ones, [`None`] when the event loop is finished
+    pub async fn recv(&mut self) -> Option<Received> {
+        match self.rx.recv().await {
+            Ok(event) => Some(Received::Event(event)),
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                self.lagged += skipped;
+                Some(Received::Lagged(skipped))
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+
+    /// Total number of events skipped by this subscriber
+    pub fn lagged(&self) -> u64 {
+        self.lagged
+    }
+}
+
+/// Handler passing events to the user handler and publishing them to [`EventSubscriber`]s
+pub(crate) struct FanoutHandler<H> {
+    inner: H,
+    hub: broadcast::Sender<SensorEvent>,
+}
+
+impl<H> FanoutHandler<H> {
+    pub(crate) fn new(inner: H, hub: broadcast::Sender<SensorEvent>) -> Self {
+        Self { inner, hub }
+    }
+
+    fn publish(&self, event: SensorEvent) {
+        // there may be no subscribers
+        let _ = self.hub.send(event);
+    }
+}
+
+#[async_trait]
+impl<H: EventHandler + Send + Sync> EventHandler for FanoutHandler<H> {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
+        self.publish(SensorEvent::DeviceStatus(status_data));
+        self.inner.device_status_update(status_data).await
+    }
+
+    async fn eeg_update(&mut self, eeg_data: Vec<u8>) -> HandlerResult {
+        self.inner.eeg_update(eeg_data).await
+    }
+
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        self.publish(SensorEvent::EegPacket(packet.clone()));
+        self.inner.eeg_packet_update(packet).await
+    }
+
+    async fn packet_loss_update(&mut self, gap: PacketGap) -> HandlerResult {
+        self.publish(SensorEvent::PacketLoss(gap));
+        self.inner.packet_loss_update(gap).await
+    }
+
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        self.publish(SensorEvent::Resistance(reading));
+        self.inner.resistance_update(reading).await
+    }
+
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        self.publish(SensorEvent::Connection(event));
+        self.inner.connection_update(event).await
+    }
+
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        self.publish(SensorEvent::Battery(battery));
+        self.inner.battery_update(battery).await
+    }
+
+    async fn send_command(&self, command_data: CommandData) -> HandlerResult {
+        self.inner.send_command(command_data).await
+    }
+
+    async fn flush(&mut self) -> HandlerResult {
+        self.inner.flush().await
+    }
+
+    async fn should_continue(&self) -> bool {
+        self.inner.should_continue().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::bbit::device::BBitSensor;
+    use crate::bbit::internals::{ChannelType, EegConfig};
+    use crate::bbit::responses::Nss2Status;
+    use crate::bbit::uuids::{EventType, PERIPHERAL_NAME_MATCH_FILTER};
+    use crate::sim::SimulatedBrainBit;
+
+    #[tokio::test]
+    async fn test_sensor_stream() {
+        let device = SimulatedBrainBit::default();
+        let mut stream = BBitSensor::with_transport(device.clone())
+            .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+            .await
+            .unwrap()
+            .listen(EventType::State)
+            .listen(EventType::EegOrResistance)
+            .build()
+            .await
+            .unwrap()
+            .into_stream()
+            .await;
+
+        stream
+            .handle()
+            .start_eeg(EegConfig::default())
+            .await
+            .unwrap()
+            .unwrap();
+        let (mut statuses, mut packets) = (0, 0);
+        while packets < 10 {
+            let event = tokio::time::timeout(Duration::from_secs(1), stream.next())
+                .await
+                .unwrap()
+                .unwrap();
+            match event {
+                SensorEvent::DeviceStatus(_) => statuses += 1,
+                SensorEvent::EegPacket(_) => packets += 1,
+                _ => {}
+            }
+        }
+        assert!(statuses > 0);
+
+        device.set_in_range(false);
+        let disconnected = stream.by_ref().any(|event| async move {
+            matches!(
+                event,
+                SensorEvent::Connection(ConnectionEvent::Disconnected)
+            )
+        });
+        assert!(tokio::time::timeout(Duration::from_secs(1), disconnected)
+            .await
+            .unwrap());
+        assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+        stream.stop().await;
+    }
+
+    struct Recorder(Vec<ResistanceReading>);
+
+    #[async_trait]
+    impl EventHandler for Recorder {
+        async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+            self.0.push(reading);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fanout_lag() {
+        let (hub, _) = broadcast::channel(4);
+        let mut fast = EventSubscriber::new(hub.subscribe());
+        let mut slow = EventSubscriber::new(hub.subscribe());
+        let mut handler = FanoutHandler::new(Recorder(vec![]), hub);
+
+        for counter in 0..10 {
+            let reading = ResistanceReading {
+                counter,
+                channel: ChannelType::O1,
+                ohms: 1000.0,
+            };
+            handler.resistance_update(reading).await.unwrap();
+            let Some(Received::Event(SensorEvent::Resistance(received))) = fast.recv().await else {
+                panic!("resistance reading is expected");
+            };
+            assert_eq!(reading, received);
+        }
+        // slow subscriber doesn't block handler and others
+        assert_eq!(10, handler.inner.0.len());
+        assert!(matches!(slow.recv().await, Some(Received::Lagged(6))));
+        assert_eq!(6, slow.lagged());
+        for counter in 6..10 {
+            let Some(Received::Event(SensorEvent::Resistance(received))) = slow.recv().await else {
+                panic!("resistance reading is expected");
+            };
+            assert_eq!(counter, received.counter);
+        }
+        assert_eq!(0, fast.lagged());
+
+        drop(handler);
+        assert!(fast.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_event_loop() {
+        let device = SimulatedBrainBit::default();
+        let handle = BBitSensor::with_transport(device.clone())
+            .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+            .await
+            .unwrap()
+            .listen(EventType::State)
+            .listen(EventType::EegOrResistance)
+            .build()
+            .await
+            .unwrap()
+            .event_loop(Recorder(vec![]))
+            .await;
+        let mut subscribers = [handle.subscribe(), handle.subscribe()];
+
+        handle
+            .start_eeg(EegConfig::default())
+            .await
+            .unwrap()
+            .unwrap();
+        for subscriber in subscribers.iter_mut() {
+            let mut packets = 0;
+            while packets < 10 {
+                let received = tokio::time::timeout(Duration::from_secs(1), subscriber.recv())
+                    .await
+                    .unwrap();
+                if let Some(Received::Event(SensorEvent::EegPacket(_))) = received {
+                    packets += 1;
+                }
+            }
+        }
+        handle.stop().await;
+    }
+}
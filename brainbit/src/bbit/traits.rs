@@ -1,29 +1,93 @@
-use crate::bbit::device::CommandData;
-use crate::bbit::responses::DeviceStatusData;
+use crate::bbit::battery::BatteryStatus;
+use crate::bbit::device::{CommandData, ConnectionEvent};
+use crate::bbit::packet_loss::PacketGap;
+use crate::bbit::responses::{DeviceStatusData, EegPacket, ResistanceReading};
 use async_trait::async_trait;
 
+/// Error returned by [`EventHandler`] callbacks, any error type can be converted by `?`
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Result of [`EventHandler`] callbacks, errors are processed by [`HandlerErrorPolicy`]
+pub type HandlerResult = Result<(), HandlerError>;
+
+/// What the event loop does when an [`EventHandler`] callback returns error
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HandlerErrorPolicy {
+    /// Log the error and keep handling events
+    #[default]
+    LogAndContinue,
+    /// Log the error and pause handling of bluetooth events until
+    /// [`crate::bbit::device::BleHandle::resume`] is called
+    Pause,
+    /// Stop measurement, disconnect from the device and finish the event loop
+    Stop,
+}
+
 /// Base trait for handling events coming from a BrainBit device.
 #[async_trait]
 pub trait EventHandler {
     /// Dispatched when an internal device status update is received.
     ///
     /// Contains the status, cmd error, battery level.
-    async fn device_status_update(&self, _status_data: DeviceStatusData) {}
-    // async fn device_status_update(&self, _status: DeviceStatusData) -> ();
+    async fn device_status_update(&self, _status_data: DeviceStatusData) -> HandlerResult {
+        Ok(())
+    }
 
     /// Dispatched when an eeg data is received.
     ///
     /// Contains information about the O1, O2, T3, T4 + interval.
-    async fn eeg_update(&mut self, _eeg_data: Vec<u8>) {}
-    // async fn eeg_update(&mut self, _eeg_data: Vec<u8>) -> ();
+    async fn eeg_update(&mut self, _eeg_data: Vec<u8>) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched after [`EventHandler::eeg_update`] when EEG measurement is running.
+    ///
+    /// Contains packet counter and O1, T3, T4, O2 samples in microvolts.
+    async fn eeg_packet_update(&mut self, _packet: EegPacket) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched when packet counter shows dropped, duplicated or reordered packets.
+    ///
+    /// Contains the gap and cumulative loss statistics since measurement start.
+    async fn packet_loss_update(&mut self, _gap: PacketGap) -> HandlerResult {
+        Ok(())
+    }
 
-    /// Dispatched when measurement data is received over the PMD data UUID.
+    /// Dispatched after [`EventHandler::eeg_update`] when resistance measurement is running.
     ///
-    /// Contains data in a [`CommandData`].
-    async fn send_command(&self, _command_data: CommandData) {}
-    // async fn send_command(&self, _command_data: CommandData) -> () {        ()     }
+    /// Contains impedance in ohms of the electrode under test.
+    async fn resistance_update(&mut self, _reading: ResistanceReading) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched after [`EventHandler::device_status_update`] when the smoothed battery level
+    /// or charging state is changed, or a low or critical threshold is crossed.
+    ///
+    /// Contains the level trend and estimated remaining time of recording.
+    async fn battery_update(&mut self, _battery: BatteryStatus) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched when BLE link is lost and when it's restored by reconnection.
+    async fn connection_update(&mut self, _event: ConnectionEvent) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched when a control command is written to device and its confirmation is
+    /// awaited.
+    async fn send_command(&self, _command_data: CommandData) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched once when the event loop is finished and the device is disconnected.
+    ///
+    /// Write buffered data here, the handler is dropped after it.
+    async fn flush(&mut self) -> HandlerResult {
+        Ok(())
+    }
 
-    /// Checked at start of each event loop.
+    /// Checked at start of each event loop iteration.
     ///
     /// Returns [`false`] if the event loop should be terminated and close connection.
     async fn should_continue(&self) -> bool {
@@ -0,0 +1,305 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use btleplug::api::{
+    BDAddr, Central, CentralEvent, Characteristic, Manager as _, Peripheral as _,
+    PeripheralProperties, ScanFilter, ValueNotification, WriteType,
+};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures::{Stream, StreamExt};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::bbit::errors::Error;
+use crate::bbit::results::BBitResult;
+
+/// Stream of notifications received from subscribed characteristics
+pub type NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+/// Stream yielding an item every time the connected peripheral is disconnected
+pub type DisconnectionStream = Pin<Box<dyn Stream<Item = ()> + Send>>;
+
+/// Low level BLE link to one peripheral used by [`crate::bbit::device::BBitSensor`].
+///
+/// Default implementation is [`BtleplugTransport`], other implementations can replace a real
+/// device (simulators, recorded sessions, test doubles).
+#[async_trait]
+pub trait Transport: Send + Sync + 'static {
+    /// Scan for peripherals advertising `service` (all peripherals for `None`) during
+    /// `duration`, return their properties
+    async fn scan(
+        &self,
+        service: Option<Uuid>,
+        duration: Duration,
+    ) -> BBitResult<Vec<PeripheralProperties>>;
+
+    /// Connect to the peripheral with `address` found by a previous [`Transport::scan`]
+    async fn connect(&self, address: BDAddr) -> BBitResult<()>;
+
+    /// Check if peripheral is connected
+    async fn is_connected(&self) -> bool;
+
+    /// Discover services and characteristics of connected peripheral
+    async fn discover_services(&self) -> BBitResult<()>;
+
+    /// Characteristics discovered on connected peripheral
+    fn characteristics(&self) -> BTreeSet<Characteristic>;
+
+    /// Read characteristic value
+    async fn read(&self, characteristic: &Characteristic) -> BBitResult<Vec<u8>>;
+
+    /// Write data into characteristic (with response)
+    async fn write(&self, characteristic: &Characteristic, data: &[u8]) -> BBitResult<()>;
+
+    /// Enable notifications for characteristic
+    async fn subscribe(&self, characteristic: &Characteristic) -> BBitResult<()>;
+
+    /// Disable notifications for characteristic
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BBitResult<()>;
+
+    /// Stream of notifications from all subscribed characteristics
+    async fn notifications(&self) -> BBitResult<NotificationStream>;
+
+    /// Stream of disconnection events of connected peripheral (link loss or explicit disconnect)
+    async fn disconnections(&self) -> BBitResult<DisconnectionStream>;
+
+    /// Disconnect from peripheral
+    async fn disconnect(&self) -> BBitResult<()>;
+}
+
+/// Bluetooth adapter found on host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterInfo {
+    /// Position in the adapters list, it can be used by [`AdapterSelector::Index`]
+    pub index: usize,
+    /// Platform specific description, e.g. `hci0 (usb:v1D6Bp0246d0540)` on Linux
+    pub info: String,
+}
+
+/// Which Bluetooth adapter is used when host has several ones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AdapterSelector {
+    /// The first adapter reported by platform
+    #[default]
+    First,
+    /// Adapter with index from [`list_adapters`]
+    Index(usize),
+    /// The first adapter with info containing the name, e.g. `hci1`
+    Name(String),
+}
+
+impl fmt::Display for AdapterSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdapterSelector::First => write!(f, "first"),
+            AdapterSelector::Index(index) => write!(f, "#{index}"),
+            AdapterSelector::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl FromStr for AdapterSelector {
+    type Err = std::convert::Infallible;
+
+    /// Number is parsed as index, any other string as name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(index) => AdapterSelector::Index(index),
+            Err(_) => AdapterSelector::Name(s.to_string()),
+        })
+    }
+}
+
+/// List Bluetooth adapters available on host
+pub async fn list_adapters() -> BBitResult<Vec<AdapterInfo>> {
+    let manager = Manager::new().await?;
+    let mut list = vec![];
+    for (index, adapter) in manager.adapters().await?.iter().enumerate() {
+        list.push(AdapterInfo {
+            index,
+            info: adapter.adapter_info().await?,
+        });
+    }
+    Ok(list)
+}
+
+/// [`Transport`] implemented on top of platform BLE stack by btleplug
+pub struct BtleplugTransport {
+    /// BLE connection manager
+    ble_manager: Manager,
+    /// Adapter used for scan and connection
+    adapter: AdapterSelector,
+    /// Adapter used for the last scan
+    central: Mutex<Option<Adapter>>,
+    /// Connected and controlled device
+    ble_device: Mutex<Option<Peripheral>>,
+}
+
+impl BtleplugTransport {
+    /// Construct transport using platform BLE manager and the first adapter
+    pub async fn new() -> BBitResult<Self> {
+        Self::with_adapter(AdapterSelector::First).await
+    }
+
+    /// Construct transport using platform BLE manager and selected adapter
+    pub async fn with_adapter(adapter: AdapterSelector) -> BBitResult<Self> {
+        Ok(Self {
+            ble_manager: Manager::new().await?,
+            adapter,
+            central: Mutex::new(None),
+            ble_device: Mutex::new(None),
+        })
+    }
+
+    /// Find adapter selected on construction
+    async fn select_adapter(&self) -> BBitResult<Adapter> {
+        let adapters = self.ble_manager.adapters().await?;
+        if adapters.is_empty() {
+            tracing::error!("No ble adaptor found");
+            return Err(Error::NoBleAdaptor);
+        }
+        let selected = match &self.adapter {
+            AdapterSelector::First => adapters.into_iter().next(),
+            AdapterSelector::Index(index) => adapters.into_iter().nth(*index),
+            AdapterSelector::Name(name) => {
+                let mut selected = None;
+                for adapter in adapters {
+                    if adapter.adapter_info().await?.contains(name.as_str()) {
+                        selected = Some(adapter);
+                        break;
+                    }
+                }
+                selected
+            }
+        };
+        selected.ok_or_else(|| {
+            tracing::error!("Ble adaptor '{}' is not found", self.adapter);
+            Error::AdapterNotFound(self.adapter.to_string())
+        })
+    }
+
+    /// Connected (or selected) peripheral
+    fn device(&self) -> BBitResult<Peripheral> {
+        self.ble_device
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(Error::NotConnected)
+    }
+}
+
+#[async_trait]
+impl Transport for BtleplugTransport {
+    async fn scan(
+        &self,
+        service: Option<Uuid>,
+        duration: Duration,
+    ) -> BBitResult<Vec<PeripheralProperties>> {
+        let central = self.select_adapter().await?;
+
+        debug!("Start scanning for {duration:?}...");
+        let mut scan_filter = ScanFilter::default();
+        scan_filter.services.extend(service);
+        central.start_scan(scan_filter).await?;
+        tokio::time::sleep(duration).await;
+
+        let mut found = vec![];
+        for p in central.peripherals().await? {
+            if let Some(properties) = p.properties().await? {
+                found.push(properties);
+            }
+        }
+        *self.central.lock().unwrap() = Some(central);
+        Ok(found)
+    }
+
+    async fn connect(&self, address: BDAddr) -> BBitResult<()> {
+        let central = self.central.lock().unwrap().clone();
+        let Some(central) = central else {
+            return Err(Error::NoDevice);
+        };
+        let mut device = None;
+        for p in central.peripherals().await? {
+            if p.address() == address {
+                device = Some(p);
+                break;
+            }
+        }
+        let Some(device) = device else {
+            return Err(Error::NoDevice);
+        };
+        device.connect().await?;
+        *self.ble_device.lock().unwrap() = Some(device);
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> bool {
+        let Ok(device) = self.device() else {
+            return false;
+        };
+        device.is_connected().await.unwrap_or(false)
+    }
+
+    async fn discover_services(&self) -> BBitResult<()> {
+        self.device()?.discover_services().await?;
+        Ok(())
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        self.device()
+            .map(|device| device.characteristics())
+            .unwrap_or_default()
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BBitResult<Vec<u8>> {
+        self.device()?
+            .read(characteristic)
+            .await
+            .map_err(Error::BleError)
+    }
+
+    async fn write(&self, characteristic: &Characteristic, data: &[u8]) -> BBitResult<()> {
+        self.device()?
+            .write(characteristic, data, WriteType::WithResponse)
+            .await
+            .map_err(Error::BleError)
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BBitResult<()> {
+        self.device()?.subscribe(characteristic).await?;
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BBitResult<()> {
+        self.device()?.unsubscribe(characteristic).await?;
+        Ok(())
+    }
+
+    async fn notifications(&self) -> BBitResult<NotificationStream> {
+        Ok(self.device()?.notifications().await?)
+    }
+
+    async fn disconnections(&self) -> BBitResult<DisconnectionStream> {
+        let central = self.central.lock().unwrap().clone();
+        let Some(central) = central else {
+            return Err(Error::NotConnected);
+        };
+        let id = self.device()?.id();
+        let events = central.events().await?;
+        let stream = events.filter_map(move |event| {
+            let disconnected = matches!(event, CentralEvent::DeviceDisconnected(ref peripheral) if *peripheral == id);
+            async move { disconnected.then_some(()) }
+        });
+        Ok(Box::pin(stream))
+    }
+
+    async fn disconnect(&self) -> BBitResult<()> {
+        self.device()?.disconnect().await?;
+        Ok(())
+    }
+}
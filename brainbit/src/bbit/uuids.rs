@@ -1,7 +1,16 @@
 use uuid::{uuid, Uuid};
 
 /// Device name to search for
-pub const PERIPHERAL_NAME_MATCH_FILTER: &'static str = "BrainBit";
+pub const PERIPHERAL_NAME_MATCH_FILTER: &str = "BrainBit";
+/// Colibri R device name to search for
+pub const COLIBRI_NAME_MATCH_FILTER: &str = "Neurotech_Colibri_R";
+/// NeuroBLE device name to search for
+pub const NEUROBLE_NAME_MATCH_FILTER: &str = "NeuroBLE";
+
+/// Device name, UTF-8 string (in Generic Access service)
+pub(crate) const DEVICE_NAME_STRING_UUID: Uuid = uuid!("00002A00-0000-1000-8000-00805F9B34FB");
+/// Standard battery level in percents, 1 byte (in Battery service)
+pub const BATTERY_LEVEL_UUID: Uuid = uuid!("00002A19-0000-1000-8000-00805F9B34FB");
 
 /// GAT attribute service for several device's characteristics
 pub const GENERIC_ATTRIBUTE_SERVICE_UUID: Uuid = uuid!("0000180A-0000-1000-8000-00805F9B34FB");
@@ -28,6 +37,16 @@ pub const EEG_DATA_NOTIFY_CHARACTERISTIC_UUID: Uuid = uuid!("6E400004-B534-F393-
 /// Commands data for transmitting (in NSS2_SERVICE_UUID)
 pub const WRITE_COMMAN_UUID: Uuid = uuid!("6E400003-B534-F393-68A9-E50E24DCCA9E");
 
+/// Main GAT service of Colibri R device
+pub const COLIBRI_SERVICE_UUID: Uuid = uuid!("3D2F0001-D6B9-11E4-88CF-0002A5D5C51B");
+/// Commands data for transmitting (in COLIBRI_SERVICE_UUID)
+pub const COLIBRI_WRITE_COMMAND_UUID: Uuid = uuid!("3D2F0002-D6B9-11E4-88CF-0002A5D5C51B");
+/// Data for receiving (in COLIBRI_SERVICE_UUID)
+pub const COLIBRI_DATA_NOTIFY_UUID: Uuid = uuid!("3D2F0003-D6B9-11E4-88CF-0002A5D5C51B");
+
+/// Data for receiving, the only custom characteristic in NeuroBLE GATT dump
+pub const NEUROBLE_DATA_NOTIFY_UUID: Uuid = uuid!("6E400002-B534-F393-67A9-E50E24DCCA9E");
+
 /// Which UUID to send BLE messages to.
 pub enum NotifyUuid {
     /// Notify about device status change, including command errors, battery level change
@@ -103,6 +122,8 @@ impl From<NotifyUuid> for Uuid {
     }
 }
 
+/// Device information strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StringUuid {
     ModelNumber,
     HardwareRevision,
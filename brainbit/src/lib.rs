@@ -1,14 +1,14 @@
 pub mod bbit;
+pub mod sim;
 
 use bbit::errors::Error;
 use bbit::results::BBitResult;
-use btleplug::api::{Characteristic, Peripheral as _};
-use btleplug::platform::Peripheral;
-use thiserror::Error;
+use bbit::transport::Transport;
+use btleplug::api::Characteristic;
 use uuid::Uuid;
 
 /// Private helper to find characteristics from a [`Uuid`].
-async fn find_characteristic(device: &Peripheral, uuid: Uuid) -> BBitResult<Characteristic> {
+async fn find_characteristic<T: Transport>(device: &T, uuid: Uuid) -> BBitResult<Characteristic> {
     device
         .characteristics()
         .iter()
@@ -0,0 +1,638 @@
+//! In-process simulated BrainBit headset.
+//!
+//! [`SimulatedBrainBit`] implements [`Transport`] so it can replace a real btleplug peripheral
+//! for tests and demos:
+//!
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! use brainbit::bbit::device::BBitSensor;
+//! use brainbit::bbit::uuids::PERIPHERAL_NAME_MATCH_FILTER;
+//! use brainbit::sim::SimulatedBrainBit;
+//!
+//! let sensor = BBitSensor::with_transport(SimulatedBrainBit::default())
+//!     .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+use std::collections::BTreeSet;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use btleplug::api::{
+    BDAddr, CharPropFlags, Characteristic, PeripheralProperties, ValueNotification,
+};
+use futures::StreamExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::BroadcastStream;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::bbit::control::{ControlCommandType, ControlPointCommand};
+use crate::bbit::errors::Error;
+use crate::bbit::internals::{ChannelConfig, ChannelInput, ChannelPower};
+use crate::bbit::responses::{
+    encode_counts, microvolts_to_counts, CommandExecutionState, DeviceStatusData, Nss2Status,
+    DEFAULT_LEAD_OFF_CURRENT_A, PACKET_COUNTER_MODULO, SAMPLES_PER_PACKET, SAMPLE_RATE_HZ,
+};
+use crate::bbit::results::BBitResult;
+use crate::bbit::transport::{DisconnectionStream, NotificationStream, Transport};
+use crate::bbit::uuids::{
+    DEVICE_STATE_NOTIFY_CHARACTERISTIC_UUID, EEG_DATA_NOTIFY_CHARACTERISTIC_UUID,
+    FIRMWARE_REVISION_STRING_UUID, GENERIC_ATTRIBUTE_SERVICE_UUID, HARDWARE_REVISION_STRING_UUID,
+    MODEL_NUMBER_STRING_UUID, NSS2_SERVICE_UUID, SERIAL_NUMBER_STRING_UUID, WRITE_COMMAN_UUID,
+};
+
+/// Amplitude of ADS1294 internal test signal in microvolts
+const TEST_SIGNAL_AMPLITUDE_UV: f64 = 1_000.0;
+
+/// Parameters of the simulated headset
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Advertised local name
+    pub local_name: String,
+    /// Advertised address
+    pub address: BDAddr,
+    /// Advertised RSSI
+    pub rssi: i16,
+    /// Model number string, "65" is BrainBit
+    pub model_number: String,
+    /// Serial number string
+    pub serial_number: String,
+    /// HW revision string
+    pub hardware_revision: String,
+    /// FW revision string
+    pub firmware_revision: String,
+    /// Raw battery level byte reported in device status
+    pub battery_level: u8,
+    /// Electrode to skin impedance in ohms for O1, T3, T4, O2
+    pub electrode_resistance: [f64; 4],
+    /// Amplitude of 10 Hz alpha rhythm in microvolts for O1, T3, T4, O2
+    pub alpha_amplitude_uv: [f64; 4],
+    /// Amplitude of random background noise in microvolts
+    pub noise_amplitude_uv: f64,
+    /// How often device status is notified while nothing changes
+    pub status_interval: Duration,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            local_name: "BrainBit".to_string(),
+            address: BDAddr::from([0xD1, 0x97, 0x4B, 0x0D, 0xC7, 0xF1]),
+            rssi: -55,
+            model_number: "65".to_string(),
+            serial_number: "130101".to_string(),
+            hardware_revision: "6".to_string(),
+            firmware_revision: "1".to_string(),
+            battery_level: 0x51,
+            electrode_resistance: [150_000.0, 220_000.0, 180_000.0, 160_000.0],
+            alpha_amplitude_uv: [20.0, 8.0, 8.0, 20.0],
+            noise_amplitude_uv: 3.0,
+            status_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Virtual BrainBit headset implementing [`Transport`].
+///
+/// Cloned instances share the same device, so a clone can be kept to inspect the simulated
+/// state after the original is moved into a [`crate::bbit::device::BBitSensor`].
+#[derive(Clone)]
+pub struct SimulatedBrainBit {
+    inner: Arc<SimState>,
+}
+
+struct SimState {
+    config: SimConfig,
+    connected: AtomicBool,
+    discovered: AtomicBool,
+    status: Mutex<DeviceStatusData>,
+    /// Current amplitude of alpha rhythm for O1, T3, T4, O2, it starts from configured one
+    alpha_amplitude_uv: Mutex<[f64; 4]>,
+    /// Last command which started a transmission
+    active_command: Mutex<Option<ControlPointCommand>>,
+    /// Written commands are dropped without status notification
+    commands_ignored: AtomicBool,
+    /// Device is reachable by BLE link
+    in_range: AtomicBool,
+    disconnect_tx: broadcast::Sender<()>,
+    /// Written commands are rejected with this error
+    command_error: Mutex<Option<CommandExecutionState>>,
+    /// Time between command write and its execution
+    command_delay: Mutex<Duration>,
+    subscribed: Mutex<BTreeSet<Uuid>>,
+    notify_tx: broadcast::Sender<ValueNotification>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Default for SimulatedBrainBit {
+    fn default() -> Self {
+        Self::new(SimConfig::default())
+    }
+}
+
+impl SimulatedBrainBit {
+    /// Construct simulated device with specified parameters
+    pub fn new(config: SimConfig) -> Self {
+        let (notify_tx, _) = broadcast::channel(1024);
+        let (disconnect_tx, _) = broadcast::channel(16);
+        let status = DeviceStatusData {
+            status_nss2: Nss2Status::Stopped,
+            battery_level: config.battery_level,
+            ..DeviceStatusData::default()
+        };
+        Self {
+            inner: Arc::new(SimState {
+                alpha_amplitude_uv: Mutex::new(config.alpha_amplitude_uv),
+                config,
+                connected: AtomicBool::new(false),
+                discovered: AtomicBool::new(false),
+                status: Mutex::new(status),
+                active_command: Mutex::new(None),
+                commands_ignored: AtomicBool::new(false),
+                in_range: AtomicBool::new(true),
+                disconnect_tx,
+                command_error: Mutex::new(None),
+                command_delay: Mutex::new(Duration::ZERO),
+                subscribed: Mutex::new(BTreeSet::new()),
+                notify_tx,
+                tasks: Mutex::new(vec![]),
+            }),
+        }
+    }
+
+    /// Current simulated device status
+    pub fn status(&self) -> DeviceStatusData {
+        *self.inner.status.lock().unwrap()
+    }
+
+    /// Characteristics with enabled notifications
+    pub fn subscriptions(&self) -> BTreeSet<Uuid> {
+        self.inner.subscribed.lock().unwrap().clone()
+    }
+
+    /// Change battery level byte, the next status notification reports it
+    pub fn set_battery_level(&self, battery_level: u8) {
+        self.inner.status.lock().unwrap().battery_level = battery_level;
+    }
+
+    /// Change amplitude of 10 Hz alpha rhythm for O1, T3, T4, O2, e.g. closed eyes raise it
+    pub fn set_alpha_amplitude(&self, alpha_amplitude_uv: [f64; 4]) {
+        *self.inner.alpha_amplitude_uv.lock().unwrap() = alpha_amplitude_uv;
+    }
+
+    /// Move device out of BLE range or back. Out of range device drops connection with its
+    /// subscriptions and running measurement, it's not found by scan until it's back in range.
+    pub fn set_in_range(&self, in_range: bool) {
+        self.inner.in_range.store(in_range, Ordering::SeqCst);
+        if !in_range && self.inner.connected.load(Ordering::SeqCst) {
+            debug!("simulated device lost BLE link");
+            self.inner.subscribed.lock().unwrap().clear();
+            self.drop_connection();
+        }
+    }
+
+    /// Drop written commands without any status notification, like a device that lost them
+    pub fn set_commands_ignored(&self, ignored: bool) {
+        self.inner.commands_ignored.store(ignored, Ordering::SeqCst);
+    }
+
+    /// Reject written commands reporting `error` in status, `None` restores normal execution
+    pub fn set_command_error(&self, error: Option<CommandExecutionState>) {
+        *self.inner.command_error.lock().unwrap() = error;
+    }
+
+    /// Execute written commands after `delay`, the previous transmission and its status are
+    /// notified meanwhile like by a device busy with the previous command
+    pub fn set_command_delay(&self, delay: Duration) {
+        *self.inner.command_delay.lock().unwrap() = delay;
+    }
+
+    fn characteristic(uuid: Uuid, service_uuid: Uuid, properties: CharPropFlags) -> Characteristic {
+        Characteristic {
+            uuid,
+            service_uuid,
+            properties,
+            descriptors: BTreeSet::new(),
+        }
+    }
+
+    fn drop_connection(&self) {
+        self.inner.connected.store(false, Ordering::SeqCst);
+        self.inner.discovered.store(false, Ordering::SeqCst);
+        *self.inner.active_command.lock().unwrap() = None;
+        self.inner.status.lock().unwrap().status_nss2 = Nss2Status::Stopped;
+        self.stop_tasks();
+        let _ = self.inner.disconnect_tx.send(());
+    }
+
+    fn stop_tasks(&self) {
+        for task in self.inner.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+
+    fn start_tasks(&self) {
+        let status_task = tokio::spawn(Self::status_task(Arc::downgrade(&self.inner)));
+        let data_task = tokio::spawn(Self::data_task(Arc::downgrade(&self.inner)));
+        self.inner
+            .tasks
+            .lock()
+            .unwrap()
+            .extend([status_task, data_task]);
+    }
+
+    /// Periodically notify device status
+    async fn status_task(state: Weak<SimState>) {
+        let Some(status_interval) = state.upgrade().map(|s| s.config.status_interval) else {
+            return;
+        };
+        let mut interval = tokio::time::interval(status_interval);
+        loop {
+            interval.tick().await;
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            state.notify_status();
+        }
+    }
+
+    /// Notify EEG or resistance packets at the device rate while transmission is active
+    async fn data_task(state: Weak<SimState>) {
+        let packet_period =
+            Duration::from_secs_f64(SAMPLES_PER_PACKET as f64 / SAMPLE_RATE_HZ as f64);
+        let mut interval = tokio::time::interval(packet_period);
+        let mut noise = NoiseGenerator::new(0x2545_F491_4F6C_DD1D);
+        let mut counter: u16 = 0;
+        let mut sample_index: u64 = 0;
+        loop {
+            interval.tick().await;
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            let command = state.active_command.lock().unwrap().clone();
+            let Some(command) = command else {
+                continue;
+            };
+            let config = command.data.unwrap_or_default();
+            let alpha_amplitude_uv = *state.alpha_amplitude_uv.lock().unwrap();
+            let mut samples = [[0i32; 4]; SAMPLES_PER_PACKET];
+            for sample in samples.iter_mut() {
+                let t = sample_index as f64 / SAMPLE_RATE_HZ as f64;
+                for (channel, value) in sample.iter_mut().enumerate() {
+                    let channel_config =
+                        ChannelConfig::try_from(config.get(channel).copied().unwrap_or(0))
+                            .unwrap_or_default();
+                    let gain = channel_config.gain;
+                    let noise = state.config.noise_amplitude_uv * noise.next();
+                    let microvolts = match command.cmd_type {
+                        ControlCommandType::StartEegSignal => match channel_config.input {
+                            _ if channel_config.power == ChannelPower::Down => 0.0,
+                            ChannelInput::Normal => {
+                                alpha_amplitude_uv[channel] * (2.0 * PI * 10.0 * t).sin() + noise
+                            }
+                            ChannelInput::Shorted => noise,
+                            ChannelInput::Test => {
+                                if t.fract() < 0.5 {
+                                    TEST_SIGNAL_AMPLITUDE_UV
+                                } else {
+                                    -TEST_SIGNAL_AMPLITUDE_UV
+                                }
+                            }
+                        },
+                        _ => state.resist_voltage_uv(&config, channel, counter) + noise,
+                    };
+                    *value = microvolts_to_counts(microvolts, gain);
+                }
+                sample_index += 1;
+            }
+            state.notify(
+                EEG_DATA_NOTIFY_CHARACTERISTIC_UUID,
+                encode_counts(counter, &samples),
+            );
+            counter = (counter + 1) % PACKET_COUNTER_MODULO;
+        }
+    }
+}
+
+impl SimState {
+    fn notify(&self, uuid: Uuid, value: Vec<u8>) {
+        if !self.connected.load(Ordering::SeqCst)
+            || !self.subscribed.lock().unwrap().contains(&uuid)
+        {
+            return;
+        }
+        let _ = self.notify_tx.send(ValueNotification { uuid, value });
+    }
+
+    fn notify_status(&self) {
+        let status = self.status_bytes();
+        self.notify(DEVICE_STATE_NOTIFY_CHARACTERISTIC_UUID, status);
+    }
+
+    fn status_bytes(&self) -> Vec<u8> {
+        let status = *self.status.lock().unwrap();
+        vec![
+            status.status_nss2.into(),
+            status.cmd_error as u8,
+            status.battery_level,
+            status.firmware_version,
+        ]
+    }
+
+    /// Voltage developed by lead-off current on the electrode under test, flipped current
+    /// changes polarity every packet
+    fn resist_voltage_uv(&self, config: &[u8], channel: usize, counter: u16) -> f64 {
+        let (Some(sensp), Some(flipp)) = (config.get(4), config.get(6)) else {
+            return 0.0;
+        };
+        if sensp & (1 << channel) == 0 {
+            return 0.0;
+        }
+        let sign = if flipp & (1 << channel) != 0 && counter % 2 == 1 {
+            -1.0
+        } else {
+            1.0
+        };
+        sign * DEFAULT_LEAD_OFF_CURRENT_A * self.config.electrode_resistance[channel] * 1e6
+    }
+
+    /// Apply command written into the control characteristic and notify new status
+    fn execute(&self, data: &[u8]) {
+        debug!("simulated device received command: {:02X?}", data);
+        if self.commands_ignored.load(Ordering::SeqCst) {
+            return;
+        }
+        {
+            let mut status = self.status.lock().unwrap();
+            let command_error = *self.command_error.lock().unwrap();
+            match ControlPointCommand::try_from(data) {
+                Ok(_) if command_error.is_some() => {
+                    status.cmd_error = command_error.unwrap();
+                }
+                Ok(command) => {
+                    status.cmd_error = CommandExecutionState::Ok;
+                    let mut active = self.active_command.lock().unwrap();
+                    match command.cmd_type {
+                        ControlCommandType::StopAll => {
+                            status.status_nss2 = Nss2Status::Stopped;
+                            *active = None;
+                        }
+                        ControlCommandType::StartEegSignal => {
+                            status.status_nss2 = Nss2Status::EegTransmission;
+                            *active = Some(command);
+                        }
+                        ControlCommandType::StartResist => {
+                            status.status_nss2 = Nss2Status::ResistTransmission;
+                            *active = Some(command);
+                        }
+                        ControlCommandType::StartDfu | ControlCommandType::Invalid => {
+                            status.cmd_error = CommandExecutionState::SwitchModeError;
+                        }
+                    }
+                }
+                Err(_) => status.cmd_error = CommandExecutionState::CommandLengthError,
+            }
+        }
+        self.notify_status();
+    }
+}
+
+#[async_trait]
+impl Transport for SimulatedBrainBit {
+    async fn scan(
+        &self,
+        service: Option<Uuid>,
+        duration: Duration,
+    ) -> BBitResult<Vec<PeripheralProperties>> {
+        debug!("simulated scan for {duration:?}...");
+        if service.is_some_and(|service| service != NSS2_SERVICE_UUID)
+            || !self.inner.in_range.load(Ordering::SeqCst)
+        {
+            return Ok(vec![]);
+        }
+        Ok(vec![PeripheralProperties {
+            address: self.inner.config.address,
+            local_name: Some(self.inner.config.local_name.clone()),
+            rssi: Some(self.inner.config.rssi),
+            services: vec![NSS2_SERVICE_UUID],
+            ..PeripheralProperties::default()
+        }])
+    }
+
+    async fn connect(&self, address: BDAddr) -> BBitResult<()> {
+        if address != self.inner.config.address || !self.inner.in_range.load(Ordering::SeqCst) {
+            return Err(Error::NoDevice);
+        }
+        if !self.inner.connected.swap(true, Ordering::SeqCst) {
+            self.start_tasks();
+        }
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> bool {
+        self.inner.connected.load(Ordering::SeqCst)
+    }
+
+    async fn discover_services(&self) -> BBitResult<()> {
+        if !self.is_connected().await {
+            return Err(Error::NotConnected);
+        }
+        self.inner.discovered.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        if !self.inner.discovered.load(Ordering::SeqCst) {
+            return BTreeSet::new();
+        }
+        BTreeSet::from([
+            Self::characteristic(
+                MODEL_NUMBER_STRING_UUID,
+                GENERIC_ATTRIBUTE_SERVICE_UUID,
+                CharPropFlags::READ,
+            ),
+            Self::characteristic(
+                SERIAL_NUMBER_STRING_UUID,
+                GENERIC_ATTRIBUTE_SERVICE_UUID,
+                CharPropFlags::READ,
+            ),
+            Self::characteristic(
+                HARDWARE_REVISION_STRING_UUID,
+                GENERIC_ATTRIBUTE_SERVICE_UUID,
+                CharPropFlags::READ,
+            ),
+            Self::characteristic(
+                FIRMWARE_REVISION_STRING_UUID,
+                GENERIC_ATTRIBUTE_SERVICE_UUID,
+                CharPropFlags::READ,
+            ),
+            Self::characteristic(
+                DEVICE_STATE_NOTIFY_CHARACTERISTIC_UUID,
+                NSS2_SERVICE_UUID,
+                CharPropFlags::READ | CharPropFlags::NOTIFY,
+            ),
+            Self::characteristic(WRITE_COMMAN_UUID, NSS2_SERVICE_UUID, CharPropFlags::WRITE),
+            Self::characteristic(
+                EEG_DATA_NOTIFY_CHARACTERISTIC_UUID,
+                NSS2_SERVICE_UUID,
+                CharPropFlags::NOTIFY,
+            ),
+        ])
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> BBitResult<Vec<u8>> {
+        if !self.is_connected().await {
+            return Err(Error::NotConnected);
+        }
+        let config = &self.inner.config;
+        let value = match characteristic.uuid {
+            MODEL_NUMBER_STRING_UUID => config.model_number.as_bytes().to_vec(),
+            SERIAL_NUMBER_STRING_UUID => config.serial_number.as_bytes().to_vec(),
+            HARDWARE_REVISION_STRING_UUID => config.hardware_revision.as_bytes().to_vec(),
+            FIRMWARE_REVISION_STRING_UUID => config.firmware_revision.as_bytes().to_vec(),
+            DEVICE_STATE_NOTIFY_CHARACTERISTIC_UUID => self.inner.status_bytes(),
+            _ => return Err(Error::CharacteristicNotFound),
+        };
+        Ok(value)
+    }
+
+    async fn write(&self, characteristic: &Characteristic, data: &[u8]) -> BBitResult<()> {
+        if !self.is_connected().await {
+            return Err(Error::NotConnected);
+        }
+        if characteristic.uuid != WRITE_COMMAN_UUID {
+            return Err(Error::CharacteristicNotFound);
+        }
+        let delay = *self.inner.command_delay.lock().unwrap();
+        if delay.is_zero() {
+            self.inner.execute(data);
+            return Ok(());
+        }
+        let state = Arc::downgrade(&self.inner);
+        let data = data.to_vec();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Some(state) = state.upgrade() {
+                state.execute(&data);
+            }
+        });
+        self.inner.tasks.lock().unwrap().push(task);
+        Ok(())
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> BBitResult<()> {
+        self.inner
+            .subscribed
+            .lock()
+            .unwrap()
+            .insert(characteristic.uuid);
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, characteristic: &Characteristic) -> BBitResult<()> {
+        self.inner
+            .subscribed
+            .lock()
+            .unwrap()
+            .remove(&characteristic.uuid);
+        Ok(())
+    }
+
+    async fn notifications(&self) -> BBitResult<NotificationStream> {
+        let stream = BroadcastStream::new(self.inner.notify_tx.subscribe())
+            .filter_map(|notification| async move { notification.ok() });
+        Ok(Box::pin(stream))
+    }
+
+    async fn disconnections(&self) -> BBitResult<DisconnectionStream> {
+        let stream = BroadcastStream::new(self.inner.disconnect_tx.subscribe())
+            .filter_map(|event| async move { event.ok() });
+        Ok(Box::pin(stream))
+    }
+
+    async fn disconnect(&self) -> BBitResult<()> {
+        if self.inner.connected.load(Ordering::SeqCst) {
+            self.drop_connection();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SimState {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// Tiny xorshift generator, so the simulation is reproducible without extra dependencies
+struct NoiseGenerator(u64);
+
+impl NoiseGenerator {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Next value uniformly distributed in [-1.0, 1.0)
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbit::device::measurement_command;
+    use crate::bbit::internals::{ChannelType, MeasurementType};
+    use crate::bbit::responses::{decode_counts, ResistanceDecoder};
+    use crate::find_characteristic;
+
+    #[tokio::test]
+    async fn test_resistance_packets() {
+        let device = SimulatedBrainBit::default();
+        device.connect(device.inner.config.address).await.unwrap();
+        device.discover_services().await.unwrap();
+        let data = find_characteristic(&device, EEG_DATA_NOTIFY_CHARACTERISTIC_UUID)
+            .await
+            .unwrap();
+        device.subscribe(&data).await.unwrap();
+        let mut notifications = device.notifications().await.unwrap();
+
+        let command = measurement_command(MeasurementType::Resistance(ChannelType::T3));
+        let decoder = ResistanceDecoder::from_command(&command).unwrap();
+        let control = find_characteristic(&device, WRITE_COMMAN_UUID)
+            .await
+            .unwrap();
+        let bytes: Vec<u8> = command.try_into().unwrap();
+        device.write(&control, &bytes).await.unwrap();
+
+        for _ in 0..10 {
+            let notification = tokio::time::timeout(Duration::from_secs(1), notifications.next())
+                .await
+                .unwrap()
+                .unwrap();
+            let (_, counts) = decode_counts(&notification.value).unwrap();
+            // lead-off current is injected into the channel under test only
+            for sample in counts {
+                for other in [sample[0], sample[2], sample[3]] {
+                    assert!(other.abs() < sample[1].abs(), "{sample:?}");
+                }
+            }
+            // simulated T3 electrode impedance is 220 kOhm
+            let reading = decoder.decode(&notification.value).unwrap();
+            assert!((reading.ohms - 220_000.0).abs() < 15_000.0, "{reading:?}");
+        }
+        device.disconnect().await.unwrap();
+    }
+}
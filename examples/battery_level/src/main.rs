@@ -10,10 +10,13 @@ use tokio::sync::oneshot;
 use tracing::{debug, instrument};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-use brainbit::bbit::device::BBitSensor;
+use brainbit::bbit::device::{BBitSensor, BleHandle};
 use brainbit::bbit::responses::DeviceStatusData;
-use brainbit::bbit::traits::EventHandler;
+use brainbit::bbit::sealed::Bluetooth;
+use brainbit::bbit::traits::{EventHandler, HandlerResult};
+use brainbit::bbit::transport::Transport;
 use brainbit::bbit::uuids::{EventType, PERIPHERAL_NAME_MATCH_FILTER};
+use brainbit::sim::SimulatedBrainBit;
 
 #[tokio::main]
 #[instrument]
@@ -26,16 +29,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
         )
         .init();
 
-    let connected = BBitSensor::new()
-        .await?
-        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
-        .await?
-        .listen(EventType::State) // subscribe to device status changes
-        // .listen(EventType::Resistance)
-        .build()
-        .await?
-        .event_loop(Handler::new().await?)
-        .await;
+    // '--sim' runs the example against in-process simulated headset
+    let connected = if std::env::args().any(|arg| arg == "--sim") {
+        start(BBitSensor::with_transport(SimulatedBrainBit::default())).await?
+    } else {
+        start(BBitSensor::new().await?).await?
+    };
     tracing::info!("BrainBit is connected, event loop is started");
     // connected.start();
 
@@ -47,6 +46,21 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+async fn start<T: Transport>(
+    sensor: BBitSensor<Bluetooth, T>,
+) -> Result<BleHandle, Box<dyn Error>> {
+    let connected = sensor
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await?
+        .listen(EventType::State) // subscribe to device status changes
+        // .listen(EventType::Resistance)
+        .build()
+        .await?
+        .event_loop(Handler::new().await?)
+        .await;
+    Ok(connected)
+}
+
 static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug)]
@@ -61,9 +75,10 @@ impl Handler {
 #[async_trait]
 impl EventHandler for Handler {
     #[instrument(skip(self))]
-    async fn device_status_update(&self, status_data: DeviceStatusData) {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
         debug!("received Status: {status_data}");
         COUNTER.fetch_add(1, Ordering::SeqCst);
+        Ok(())
     }
 }
 
@@ -78,7 +93,7 @@ async fn get_finish() -> color_eyre::Result<()> {
     );
     let task = tokio::task::spawn(async move {
         loop {
-            if let Ok(_) = rx.try_recv() {
+            if rx.try_recv().is_ok() {
                 return;
             }
             io::stdout().flush().unwrap();
@@ -88,7 +103,7 @@ async fn get_finish() -> color_eyre::Result<()> {
 
     loop {
         io::stdin().read_line(&mut buf)?;
-        if buf.trim().to_ascii_lowercase() == "y" {
+        if buf.trim().eq_ignore_ascii_case("y") {
             let _ = tx.send(());
             task.await?;
             return Ok(());
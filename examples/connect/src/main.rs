@@ -1,6 +1,10 @@
 use brainbit::bbit::device::BBitSensor;
+use brainbit::bbit::discovery::ConnectTarget;
 use brainbit::bbit::uuids::PERIPHERAL_NAME_MATCH_FILTER;
+use btleplug::api::BDAddr;
 use std::error::Error;
+use std::str::FromStr;
+use std::time::Duration;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 #[tokio::main]
@@ -13,12 +17,27 @@ async fn main() -> Result<(), Box<dyn Error>> {
         )
         .init();
 
-    let connected = BBitSensor::new()
-        .await
-        .unwrap()
-        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
-        .await
-        .unwrap();
+    let sensor = BBitSensor::new().await.unwrap();
+
+    // list nearby headsets, pass address of one of them as argument to connect to it
+    for device in sensor.scan(Duration::from_secs(3)).await? {
+        tracing::info!(
+            "found '{:?}' address = {}, rssi = {:?}",
+            device.local_name,
+            device.address,
+            device.rssi
+        );
+    }
+    let connected = match std::env::args().nth(1) {
+        Some(address) => {
+            let address = BDAddr::from_str(&address)?;
+            sensor.connect_to(ConnectTarget::Address(address)).await?
+        }
+        None => sensor
+            .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+            .await
+            .unwrap(),
+    };
 
     tracing::info!("BrainBit is connected");
 
@@ -0,0 +1,384 @@
+use std::ops::Range;
+use std::time::Duration;
+
+use brainbit::bbit::internals::{ChannelType, Gain};
+use brainbit::bbit::responses::{full_scale_microvolts, EegPacket, SAMPLE_RATE_HZ};
+use color_eyre::eyre::ensure;
+
+use crate::filter::{ChannelFilter, FilterConfig};
+
+/// Share of ADC input range treated as saturation
+const SATURATION_SHARE: f64 = 0.98;
+
+/// Kind of signal contamination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// Sample reached ADC input range, the signal is clipped
+    Saturation,
+    /// Signal doesn't change, the electrode is loose or the channel is powered down
+    FlatLine,
+    /// High frequency muscle activity, e.g. jaw clench picked up by T3/T4 electrodes
+    Emg,
+    /// Large low frequency transient of head motion, blink or electrode pop
+    Motion,
+}
+
+/// Contaminated part of one channel signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub channel: ChannelType,
+    pub kind: ArtifactKind,
+    /// Samples counted since detector start or reset
+    pub samples: Range<u64>,
+    /// Time span since detector start or reset
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// Thresholds of [`ArtifactDetector`], every window of samples is checked separately
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtifactConfig {
+    pub sample_rate_hz: f64,
+    /// Samples in one checked window
+    pub window: usize,
+    /// Absolute value in uV treated as saturation
+    pub saturation_uv: f64,
+    /// Peak-to-peak value in uV below which the window is flat
+    pub flat_uv: f64,
+    /// Lower edge of muscle activity band
+    pub emg_cutoff_hz: f64,
+    /// RMS in uV of muscle activity band treated as EMG burst
+    pub emg_rms_uv: f64,
+    /// Upper edge of motion band, it starts from 0.5 Hz
+    pub motion_cutoff_hz: f64,
+    /// Peak-to-peak value in uV of motion band treated as motion transient
+    pub motion_uv: f64,
+    /// Mains frequency removed from muscle activity band
+    pub mains_hz: f64,
+    /// Samples after start or reset which are not checked for EMG and motion while the band
+    /// filters settle
+    pub settle: usize,
+}
+
+impl Default for ArtifactConfig {
+    /// Half a second windows of signal measured with default gain
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: SAMPLE_RATE_HZ as f64,
+            window: SAMPLE_RATE_HZ as usize / 2,
+            saturation_uv: full_scale_microvolts(Gain::default()) * SATURATION_SHARE,
+            flat_uv: 1.0,
+            emg_cutoff_hz: 30.0,
+            emg_rms_uv: 20.0,
+            motion_cutoff_hz: 4.0,
+            motion_uv: 200.0,
+            mains_hz: 50.0,
+            settle: SAMPLE_RATE_HZ as usize,
+        }
+    }
+}
+
+impl ArtifactConfig {
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Saturation level of signal measured with the gain
+    pub fn gain(mut self, gain: Gain) -> Self {
+        self.saturation_uv = full_scale_microvolts(gain) * SATURATION_SHARE;
+        self
+    }
+
+    pub fn flat_line(mut self, flat_uv: f64) -> Self {
+        self.flat_uv = flat_uv;
+        self
+    }
+
+    pub fn emg(mut self, cutoff_hz: f64, rms_uv: f64) -> Self {
+        self.emg_cutoff_hz = cutoff_hz;
+        self.emg_rms_uv = rms_uv;
+        self
+    }
+
+    pub fn motion(mut self, cutoff_hz: f64, peak_to_peak_uv: f64) -> Self {
+        self.motion_cutoff_hz = cutoff_hz;
+        self.motion_uv = peak_to_peak_uv;
+        self
+    }
+
+    pub fn mains(mut self, mains_hz: f64) -> Self {
+        self.mains_hz = mains_hz;
+        self
+    }
+
+    pub fn settle(mut self, settle: usize) -> Self {
+        self.settle = settle;
+        self
+    }
+}
+
+/// Signal statistics of the current window of one channel
+#[derive(Debug, Clone)]
+struct ChannelWindow {
+    emg_filter: ChannelFilter,
+    motion_filter: ChannelFilter,
+    /// Received samples, dropped (NaN) ones are not counted
+    count: usize,
+    min: f64,
+    max: f64,
+    max_abs: f64,
+    emg_square_sum: f64,
+    motion_min: f64,
+    motion_max: f64,
+}
+
+impl ChannelWindow {
+    fn new(config: &ArtifactConfig) -> color_eyre::Result<Self> {
+        let emg = FilterConfig::default()
+            .sample_rate(config.sample_rate_hz)
+            .high_pass(config.emg_cutoff_hz)
+            .notch(config.mains_hz, 2);
+        let motion = FilterConfig::default()
+            .sample_rate(config.sample_rate_hz)
+            .order(2)
+            .band_pass(0.5, config.motion_cutoff_hz);
+        Ok(Self {
+            emg_filter: ChannelFilter::new(&emg)?,
+            motion_filter: ChannelFilter::new(&motion)?,
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            max_abs: 0.0,
+            emg_square_sum: 0.0,
+            motion_min: f64::INFINITY,
+            motion_max: f64::NEG_INFINITY,
+        })
+    }
+
+    fn clear(&mut self) {
+        self.count = 0;
+        self.min = f64::INFINITY;
+        self.max = f64::NEG_INFINITY;
+        self.max_abs = 0.0;
+        self.emg_square_sum = 0.0;
+        self.motion_min = f64::INFINITY;
+        self.motion_max = f64::NEG_INFINITY;
+    }
+
+    fn push(&mut self, x: f64) {
+        if x.is_nan() {
+            return;
+        }
+        let emg = self.emg_filter.process(x);
+        let motion = self.motion_filter.process(x);
+        self.count += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.max_abs = self.max_abs.max(x.abs());
+        self.emg_square_sum += emg * emg;
+        self.motion_min = self.motion_min.min(motion);
+        self.motion_max = self.motion_max.max(motion);
+    }
+
+    /// Artifacts of the finished window, band checks are skipped until filters settle
+    fn check(&self, config: &ArtifactConfig, settled: bool) -> Vec<ArtifactKind> {
+        let mut kinds = vec![];
+        if self.count == 0 {
+            return kinds;
+        }
+        if self.max_abs >= config.saturation_uv {
+            kinds.push(ArtifactKind::Saturation);
+        }
+        if self.max - self.min < config.flat_uv {
+            kinds.push(ArtifactKind::FlatLine);
+        }
+        if settled {
+            if (self.emg_square_sum / self.count as f64).sqrt() > config.emg_rms_uv {
+                kinds.push(ArtifactKind::Emg);
+            }
+            if self.motion_max - self.motion_min > config.motion_uv {
+                kinds.push(ArtifactKind::Motion);
+            }
+        }
+        kinds
+    }
+}
+
+/// Checks consecutive windows of every channel for saturation, flat line, EMG bursts and
+/// motion transients
+#[derive(Debug, Clone)]
+pub struct ArtifactDetector {
+    config: ArtifactConfig,
+    /// Windows of O1, T3, T4, O2 channels
+    channels: [ChannelWindow; 4],
+    /// Samples received since start or reset
+    received: u64,
+    /// The first sample of the current window
+    window_start: u64,
+}
+
+impl ArtifactDetector {
+    pub fn new(config: ArtifactConfig) -> color_eyre::Result<Self> {
+        ensure!(
+            config.sample_rate_hz > 0.0,
+            "Sample rate {} Hz must be positive",
+            config.sample_rate_hz
+        );
+        ensure!(
+            config.window >= 2,
+            "Window of {} samples is too short",
+            config.window
+        );
+        let window = ChannelWindow::new(&config)?;
+        Ok(Self {
+            config,
+            channels: std::array::from_fn(|_| window.clone()),
+            received: 0,
+            window_start: 0,
+        })
+    }
+
+    pub fn config(&self) -> &ArtifactConfig {
+        &self.config
+    }
+
+    /// Forget received samples, sample counting starts again
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.emg_filter.reset();
+            channel.motion_filter.reset();
+            channel.clear();
+        }
+        self.received = 0;
+        self.window_start = 0;
+    }
+
+    /// Add samples of the packet, returns artifacts of windows finished by them
+    pub fn push_packet(&mut self, packet: &EegPacket) -> Vec<Artifact> {
+        let mut artifacts = vec![];
+        for sample in &packet.samples {
+            for (window, channel) in self.channels.iter_mut().zip(ChannelType::ALL) {
+                window.push(sample.channel(channel));
+            }
+            self.received += 1;
+            if self.received - self.window_start == self.config.window as u64 {
+                self.finish_window(&mut artifacts);
+            }
+        }
+        artifacts
+    }
+
+    fn finish_window(&mut self, artifacts: &mut Vec<Artifact>) {
+        let samples = self.window_start..self.received;
+        let time =
+            |sample: u64| Duration::from_secs_f64(sample as f64 / self.config.sample_rate_hz);
+        let settled = samples.start >= self.config.settle as u64;
+        for (window, channel) in self.channels.iter_mut().zip(ChannelType::ALL) {
+            for kind in window.check(&self.config, settled) {
+                artifacts.push(Artifact {
+                    channel,
+                    kind,
+                    samples: samples.clone(),
+                    start: time(samples.start),
+                    end: time(samples.end),
+                });
+            }
+            window.clear();
+        }
+        self.window_start = self.received;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use brainbit::bbit::responses::EegSample;
+
+    const FS: f64 = SAMPLE_RATE_HZ as f64;
+
+    /// 4 s of signal: O1 is clean alpha rhythm, T3 is flat, T4 has EMG burst at 2.0-2.5 s,
+    /// O2 is saturated at 1.5-2.0 s and has blink at 3.0-3.5 s
+    fn packets() -> Vec<EegPacket> {
+        let sample = |n: usize| {
+            let t = n as f64 / FS;
+            let alpha = 20.0 * (2.0 * PI * 10.0 * t).sin();
+            let emg = if (2.0..2.5).contains(&t) {
+                80.0 * (2.0 * PI * 70.0 * t).sin()
+            } else {
+                0.0
+            };
+            let o2 = if (1.5..2.0).contains(&t) {
+                400_000.0
+            } else if (3.0..3.5).contains(&t) {
+                alpha + 300.0 * (PI * (t - 3.0) / 0.5).sin()
+            } else {
+                alpha
+            };
+            EegSample {
+                o1: alpha,
+                t3: 0.0,
+                t4: alpha + emg,
+                o2,
+            }
+        };
+        (0..(4.0 * FS) as usize / 2)
+            .map(|i| EegPacket {
+                counter: i as u16,
+                samples: [sample(2 * i), sample(2 * i + 1)],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_artifact_detection() {
+        let mut detector = ArtifactDetector::new(ArtifactConfig::default()).unwrap();
+        let artifacts: Vec<Artifact> = packets()
+            .iter()
+            .flat_map(|packet| detector.push_packet(packet))
+            .collect();
+        let find = |channel: ChannelType| -> Vec<(ArtifactKind, u64)> {
+            artifacts
+                .iter()
+                .filter(|artifact| artifact.channel == channel)
+                .map(|artifact| (artifact.kind, artifact.samples.start))
+                .collect()
+        };
+
+        assert!(find(ChannelType::O1).is_empty(), "{artifacts:?}");
+        // T3 is flat in all 8 windows
+        let flat = find(ChannelType::T3);
+        assert_eq!(8, flat.len());
+        assert!(flat.iter().all(|(kind, _)| *kind == ArtifactKind::FlatLine));
+        assert_eq!(vec![(ArtifactKind::Emg, 500)], find(ChannelType::T4));
+
+        let o2 = find(ChannelType::O2);
+        assert!(o2.contains(&(ArtifactKind::Saturation, 375)));
+        // clipped window is flat as well
+        assert!(o2.contains(&(ArtifactKind::FlatLine, 375)));
+        assert!(o2.contains(&(ArtifactKind::Motion, 750)));
+        assert!(o2.iter().all(|(_, start)| *start >= 375));
+
+        let blink = artifacts
+            .iter()
+            .find(|artifact| artifact.kind == ArtifactKind::Motion && artifact.samples.start == 750)
+            .unwrap();
+        assert_eq!(Duration::from_secs(3), blink.start);
+        assert_eq!(Duration::from_millis(3500), blink.end);
+
+        detector.reset();
+        let packet = &packets()[0];
+        assert!(detector.push_packet(packet).is_empty());
+    }
+
+    #[test]
+    fn test_saturation_by_gain() {
+        let config = ArtifactConfig::default().gain(Gain::X12);
+        assert!(config.saturation_uv < ArtifactConfig::default().saturation_uv);
+        assert!((config.saturation_uv - 0.98 * 200_000.0).abs() < 10.0);
+        assert!(ArtifactDetector::new(ArtifactConfig::default().window(1)).is_err());
+        assert!(ArtifactDetector::new(ArtifactConfig::default().emg(200.0, 10.0)).is_err());
+    }
+}
@@ -0,0 +1,556 @@
+use async_trait::async_trait;
+use brainbit::bbit::battery::BatteryStatus;
+use brainbit::bbit::device::{CommandData, ConnectionEvent};
+use brainbit::bbit::internals::ChannelType;
+use brainbit::bbit::packet_loss::PacketGap;
+use brainbit::bbit::responses::{DeviceStatusData, EegPacket, ResistanceReading};
+use brainbit::bbit::traits::{EventHandler, HandlerResult};
+use color_eyre::eyre::ensure;
+
+use crate::artifact::Artifact;
+use crate::spectrum::{Band, BandPowerHandler, BandPowers};
+
+/// State of the eyes told by occipital alpha rhythm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EyeState {
+    Open,
+    Closed,
+}
+
+/// Step of guided calibration routine, the user is asked to follow it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationStep {
+    /// Keep eyes open and look at one point
+    EyesOpen,
+    /// Keep eyes closed and relax
+    EyesClosed,
+}
+
+/// Mean relative alpha power of one user measured during calibration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlphaBaseline {
+    pub open: f64,
+    pub closed: f64,
+}
+
+impl AlphaBaseline {
+    /// Alpha power halfway between both baselines on logarithmic scale
+    pub fn threshold(&self) -> f64 {
+        (self.open * self.closed).sqrt()
+    }
+
+    /// Position of `alpha` between baselines, -1 at open eyes, 0 at threshold and 1 at closed eyes
+    pub fn score(&self, alpha: f64) -> f64 {
+        let alpha = alpha.max(f64::MIN_POSITIVE);
+        (alpha / self.threshold()).ln() / (0.5 * (self.closed / self.open).ln())
+    }
+}
+
+/// Detected change of eye state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EyeTransition {
+    pub state: EyeState,
+    /// 0.5 at threshold growing to 1 at baseline of the state
+    pub confidence: f64,
+    /// Smoothed relative alpha power
+    pub alpha: f64,
+    /// Counter of the last EEG packet of band powers
+    pub counter: u16,
+}
+
+/// Progress of [`EyeStateDetector`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EyeStateEvent {
+    /// The user should follow the next step of calibration routine
+    Calibration(CalibrationStep),
+    /// Alpha powers of both steps are too close, calibration starts again
+    CalibrationFailed {
+        open: f64,
+        closed: f64,
+    },
+    /// Calibration is finished, transitions follow
+    Calibrated(AlphaBaseline),
+    Transition(EyeTransition),
+}
+
+/// Parameters of [`EyeStateDetector`], counts are in band power updates of
+/// [`crate::spectrum::SpectrumConfig`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EyeStateConfig {
+    /// Occipital channels averaged, channels with artifacts are skipped
+    pub channels: Vec<ChannelType>,
+    /// Updates ignored after a calibration step is asked, while the user follows it and the
+    /// spectrum window refills
+    pub settle_updates: usize,
+    /// Updates averaged in one calibration step
+    pub calibration_updates: usize,
+    /// Minimal ratio of closed to open eyes alpha power accepted by calibration
+    pub min_ratio: f64,
+    /// Weight of the new update in exponential smoothing of alpha power
+    pub smoothing: f64,
+    /// Score distance from threshold needed to change state, it prevents flapping
+    pub hysteresis: f64,
+}
+
+impl Default for EyeStateConfig {
+    fn default() -> Self {
+        Self {
+            channels: vec![ChannelType::O1, ChannelType::O2],
+            // 3 s and 10 s with default update rate of 2 Hz
+            settle_updates: 6,
+            calibration_updates: 20,
+            min_ratio: 1.5,
+            smoothing: 0.5,
+            hysteresis: 0.2,
+        }
+    }
+}
+
+impl EyeStateConfig {
+    pub fn channels(mut self, channels: &[ChannelType]) -> Self {
+        self.channels = channels.to_vec();
+        self
+    }
+
+    /// Updates skipped and averaged in every calibration step
+    pub fn calibration(mut self, settle_updates: usize, calibration_updates: usize) -> Self {
+        self.settle_updates = settle_updates;
+        self.calibration_updates = calibration_updates;
+        self
+    }
+
+    pub fn min_ratio(mut self, min_ratio: f64) -> Self {
+        self.min_ratio = min_ratio;
+        self
+    }
+
+    pub fn smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+}
+
+/// Learns alpha baseline of the user by guided routine, eyes open then closed, and tracks eye
+/// state by relative alpha power of occipital channels afterwards
+#[derive(Debug, Clone)]
+pub struct EyeStateDetector {
+    config: EyeStateConfig,
+    /// Current calibration step, `None` when calibrated
+    step: Option<CalibrationStep>,
+    /// Updates received since the step was asked
+    step_updates: usize,
+    collected: Vec<f64>,
+    /// Mean alpha power of finished eyes open step
+    open: f64,
+    baseline: Option<AlphaBaseline>,
+    smoothed: Option<f64>,
+    state: Option<EyeState>,
+}
+
+impl EyeStateDetector {
+    pub fn new(config: EyeStateConfig) -> color_eyre::Result<Self> {
+        ensure!(!config.channels.is_empty(), "no channels for eye state");
+        ensure!(
+            config.calibration_updates > 0,
+            "calibration step must average some updates"
+        );
+        ensure!(
+            config.min_ratio > 1.0,
+            "closed eyes alpha power must exceed open eyes one, ratio {}",
+            config.min_ratio
+        );
+        ensure!(
+            config.smoothing > 0.0 && config.smoothing <= 1.0,
+            "smoothing weight {} is out of (0, 1]",
+            config.smoothing
+        );
+        ensure!(
+            (0.0..1.0).contains(&config.hysteresis),
+            "hysteresis {} is out of [0, 1)",
+            config.hysteresis
+        );
+        Ok(Self {
+            config,
+            step: Some(CalibrationStep::EyesOpen),
+            step_updates: 0,
+            collected: vec![],
+            open: 0.0,
+            baseline: None,
+            smoothed: None,
+            state: None,
+        })
+    }
+
+    /// Skip calibration using baseline of the same user from earlier session
+    pub fn with_baseline(mut self, baseline: AlphaBaseline) -> color_eyre::Result<Self> {
+        ensure!(
+            baseline.open > 0.0 && baseline.closed / baseline.open >= self.config.min_ratio,
+            "alpha baseline {baseline:?} doesn't separate eye states"
+        );
+        self.step = None;
+        self.baseline = Some(baseline);
+        Ok(self)
+    }
+
+    pub fn config(&self) -> &EyeStateConfig {
+        &self.config
+    }
+
+    /// Current calibration step, `None` when calibrated
+    pub fn step(&self) -> Option<CalibrationStep> {
+        self.step
+    }
+
+    pub fn baseline(&self) -> Option<AlphaBaseline> {
+        self.baseline
+    }
+
+    /// Last detected state, `None` before calibration or after reset
+    pub fn state(&self) -> Option<EyeState> {
+        self.state
+    }
+
+    /// Forget baseline and start calibration routine again
+    pub fn recalibrate(&mut self) {
+        self.baseline = None;
+        self.step = Some(CalibrationStep::EyesOpen);
+        self.reset();
+    }
+
+    /// Start after interrupted signal, current calibration step is asked again
+    pub fn reset(&mut self) {
+        self.step_updates = 0;
+        self.collected.clear();
+        self.smoothed = None;
+        self.state = None;
+    }
+
+    /// Feed band powers, calibration prompts and eye state transitions are returned
+    pub fn push(&mut self, powers: &BandPowers) -> Vec<EyeStateEvent> {
+        let alpha = self.alpha(powers);
+        match self.step {
+            Some(step) => self.calibrate(step, alpha),
+            None => self.track(alpha, powers.counter).into_iter().collect(),
+        }
+    }
+
+    /// Mean relative alpha power of configured channels, masked channels are missing
+    fn alpha(&self, powers: &BandPowers) -> Option<f64> {
+        let alphas: Vec<f64> = self
+            .config
+            .channels
+            .iter()
+            .filter_map(|channel| powers.channel(*channel))
+            .filter(|channel| channel.total > 0.0)
+            .map(|channel| channel.relative(Band::Alpha))
+            .collect();
+        if alphas.is_empty() {
+            return None;
+        }
+        Some(alphas.iter().sum::<f64>() / alphas.len() as f64)
+    }
+
+    fn calibrate(&mut self, step: CalibrationStep, alpha: Option<f64>) -> Vec<EyeStateEvent> {
+        let mut events = vec![];
+        if self.step_updates == 0 {
+            events.push(EyeStateEvent::Calibration(step));
+        }
+        self.step_updates += 1;
+        if self.step_updates <= self.config.settle_updates {
+            return events;
+        }
+        self.collected.extend(alpha);
+        if self.collected.len() < self.config.calibration_updates {
+            return events;
+        }
+        let mean = self.collected.iter().sum::<f64>() / self.collected.len() as f64;
+        self.collected.clear();
+        self.step_updates = 0;
+        match step {
+            CalibrationStep::EyesOpen => {
+                self.open = mean;
+                self.step = Some(CalibrationStep::EyesClosed);
+            }
+            CalibrationStep::EyesClosed => {
+                let baseline = AlphaBaseline {
+                    open: self.open,
+                    closed: mean,
+                };
+                if self.open > 0.0 && mean / self.open >= self.config.min_ratio {
+                    self.step = None;
+                    self.baseline = Some(baseline);
+                    events.push(EyeStateEvent::Calibrated(baseline));
+                } else {
+                    self.step = Some(CalibrationStep::EyesOpen);
+                    events.push(EyeStateEvent::CalibrationFailed {
+                        open: baseline.open,
+                        closed: baseline.closed,
+                    });
+                }
+            }
+        }
+        events
+    }
+
+    fn track(&mut self, alpha: Option<f64>, counter: u16) -> Option<EyeStateEvent> {
+        let (alpha, baseline) = alpha.zip(self.baseline)?;
+        let smoothed = match self.smoothed {
+            Some(previous) => previous + self.config.smoothing * (alpha - previous),
+            None => alpha,
+        };
+        self.smoothed = Some(smoothed);
+        let score = baseline.score(smoothed);
+        let hysteresis = self.config.hysteresis;
+        let state = match self.state {
+            Some(EyeState::Open) if score > hysteresis => EyeState::Closed,
+            Some(EyeState::Closed) if score < -hysteresis => EyeState::Open,
+            Some(_) => return None,
+            None if score > 0.0 => EyeState::Closed,
+            None => EyeState::Open,
+        };
+        self.state = Some(state);
+        Some(EyeStateEvent::Transition(EyeTransition {
+            state,
+            confidence: (0.5 + 0.5 * score.abs()).min(1.0),
+            alpha: smoothed,
+            counter,
+        }))
+    }
+}
+
+/// [`BandPowerHandler`] receiving calibration prompts and eye state transitions found by
+/// [`EyeStateTracker`]
+#[async_trait]
+pub trait EyeStateHandler: BandPowerHandler {
+    async fn eye_state_update(&mut self, _event: EyeStateEvent) -> HandlerResult {
+        Ok(())
+    }
+}
+
+/// Passes all events to the inner handler and adds eye state found by band powers.
+/// Detection starts again after resistance measurement.
+#[derive(Debug)]
+pub struct EyeStateTracker<H> {
+    inner: H,
+    detector: EyeStateDetector,
+}
+
+impl<H: EyeStateHandler> EyeStateTracker<H> {
+    pub fn new(inner: H, config: EyeStateConfig) -> color_eyre::Result<Self> {
+        Ok(Self {
+            inner,
+            detector: EyeStateDetector::new(config)?,
+        })
+    }
+
+    /// Skip calibration using baseline of the same user from earlier session
+    pub fn with_baseline(mut self, baseline: AlphaBaseline) -> color_eyre::Result<Self> {
+        self.detector = self.detector.with_baseline(baseline)?;
+        Ok(self)
+    }
+
+    pub fn detector(&self) -> &EyeStateDetector {
+        &self.detector
+    }
+
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<H: EyeStateHandler + Send + Sync> EventHandler for EyeStateTracker<H> {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
+        self.inner.device_status_update(status_data).await
+    }
+
+    async fn eeg_update(&mut self, eeg_data: Vec<u8>) -> HandlerResult {
+        self.inner.eeg_update(eeg_data).await
+    }
+
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        self.inner.eeg_packet_update(packet).await
+    }
+
+    async fn packet_loss_update(&mut self, gap: PacketGap) -> HandlerResult {
+        self.inner.packet_loss_update(gap).await
+    }
+
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        // resistance packets interrupt EEG signal
+        self.detector.reset();
+        self.inner.resistance_update(reading).await
+    }
+
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        self.inner.connection_update(event).await
+    }
+
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        self.inner.battery_update(battery).await
+    }
+
+    async fn send_command(&self, command_data: CommandData) -> HandlerResult {
+        self.inner.send_command(command_data).await
+    }
+
+    async fn flush(&mut self) -> HandlerResult {
+        self.inner.flush().await
+    }
+
+    async fn should_continue(&self) -> bool {
+        self.inner.should_continue().await
+    }
+}
+
+#[async_trait]
+impl<H: EyeStateHandler + Send + Sync> BandPowerHandler for EyeStateTracker<H> {
+    async fn band_power_update(&mut self, powers: BandPowers) -> HandlerResult {
+        let events = self.detector.push(&powers);
+        self.inner.band_power_update(powers).await?;
+        for event in events {
+            self.inner.eye_state_update(event).await?;
+        }
+        Ok(())
+    }
+
+    async fn artifact_update(&mut self, artifacts: Vec<Artifact>) -> HandlerResult {
+        self.inner.artifact_update(artifacts).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::ChannelBandPowers;
+
+    /// Band powers with relative alpha `o1` and `o2`, `None` channel is masked
+    fn powers(counter: u16, o1: Option<f64>, o2: Option<f64>) -> BandPowers {
+        let channel = |channel, alpha: f64| ChannelBandPowers {
+            channel,
+            absolute: [0.0, 0.0, alpha, 1.0 - alpha, 0.0],
+            relative: [0.0, 0.0, alpha, 1.0 - alpha, 0.0],
+            total: 1.0,
+        };
+        BandPowers {
+            counter,
+            channels: [(ChannelType::O1, o1), (ChannelType::O2, o2)]
+                .into_iter()
+                .filter_map(|(ch, alpha)| alpha.map(|alpha| channel(ch, alpha)))
+                .collect(),
+        }
+    }
+
+    fn run(detector: &mut EyeStateDetector, alpha: f64, updates: usize) -> Vec<EyeStateEvent> {
+        (0..updates)
+            .flat_map(|i| detector.push(&powers(i as u16, Some(alpha), Some(alpha))))
+            .collect()
+    }
+
+    #[test]
+    fn test_calibration() {
+        let config = EyeStateConfig::default().calibration(2, 4);
+        let mut detector = EyeStateDetector::new(config).unwrap();
+        // two settle and four averaged updates per step
+        let events = run(&mut detector, 0.25, 6);
+        assert_eq!(
+            vec![EyeStateEvent::Calibration(CalibrationStep::EyesOpen)],
+            events
+        );
+        assert_eq!(Some(CalibrationStep::EyesClosed), detector.step());
+        // not separated from open eyes
+        let events = run(&mut detector, 0.3125, 6);
+        assert_eq!(
+            vec![
+                EyeStateEvent::Calibration(CalibrationStep::EyesClosed),
+                EyeStateEvent::CalibrationFailed {
+                    open: 0.25,
+                    closed: 0.3125
+                }
+            ],
+            events
+        );
+
+        run(&mut detector, 0.25, 6);
+        // alpha is missing while the closed eyes are settling or channels are masked
+        detector.push(&powers(0, None, None));
+        detector.push(&powers(0, None, None));
+        detector.push(&powers(0, Some(0.625), None));
+        let events = run(&mut detector, 0.75, 3);
+        let baseline = AlphaBaseline {
+            open: 0.25,
+            closed: 0.71875,
+        };
+        assert_eq!(vec![EyeStateEvent::Calibrated(baseline)], events);
+        assert_eq!(None, detector.step());
+        assert!((baseline.score(0.25) + 1.0).abs() < 1e-9);
+        assert!((baseline.score(0.71875) - 1.0).abs() < 1e-9);
+        assert!(baseline.score(baseline.threshold()).abs() < 1e-9);
+
+        detector.recalibrate();
+        assert_eq!(None, detector.baseline());
+        assert_eq!(Some(CalibrationStep::EyesOpen), detector.step());
+        assert!(EyeStateDetector::new(EyeStateConfig::default().min_ratio(1.0)).is_err());
+    }
+
+    #[test]
+    fn test_transitions() {
+        let baseline = AlphaBaseline {
+            open: 0.2,
+            closed: 0.8,
+        };
+        let mut detector = EyeStateDetector::new(EyeStateConfig::default())
+            .unwrap()
+            .with_baseline(baseline)
+            .unwrap();
+        let transitions = |events: Vec<EyeStateEvent>| -> Vec<(EyeState, f64)> {
+            events
+                .into_iter()
+                .map(|event| match event {
+                    EyeStateEvent::Transition(transition) => {
+                        (transition.state, transition.confidence)
+                    }
+                    event => panic!("unexpected {event:?}"),
+                })
+                .collect()
+        };
+
+        let open = transitions(run(&mut detector, 0.2, 5));
+        assert_eq!(1, open.len());
+        assert_eq!(EyeState::Open, open[0].0);
+        assert!((open[0].1 - 1.0).abs() < 1e-9);
+        // smoothed alpha crosses threshold of 0.4 but stays within hysteresis
+        assert!(run(&mut detector, 0.7, 1).is_empty());
+        let closed = transitions(run(&mut detector, 0.8, 5));
+        assert_eq!(1, closed.len());
+        assert_eq!(EyeState::Closed, closed[0].0);
+        assert!(closed[0].1 > 0.6 && closed[0].1 < 1.0, "{closed:?}");
+        assert_eq!(Some(EyeState::Closed), detector.state());
+        // a single masked channel doesn't stop tracking
+        let events = (0..5)
+            .flat_map(|_| detector.push(&powers(0, None, Some(0.2))))
+            .collect();
+        assert_eq!(EyeState::Open, transitions(events)[0].0);
+
+        detector.reset();
+        assert_eq!(None, detector.state());
+        assert_eq!(Some(baseline), detector.baseline());
+        let weak = AlphaBaseline {
+            open: 0.3,
+            closed: 0.35,
+        };
+        assert!(EyeStateDetector::new(EyeStateConfig::default())
+            .unwrap()
+            .with_baseline(weak)
+            .is_err());
+    }
+}
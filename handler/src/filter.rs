@@ -0,0 +1,544 @@
+use std::f64::consts::PI;
+
+use brainbit::bbit::internals::ChannelType;
+use brainbit::bbit::responses::{EegPacket, EegSample, SAMPLE_RATE_HZ};
+use color_eyre::eyre::ensure;
+
+/// Order of Butterworth high-pass and low-pass stages by default
+pub const DEFAULT_FILTER_ORDER: usize = 4;
+
+/// Quality factor of mains notch, rejected band is `frequency / Q` wide
+pub const NOTCH_Q: f64 = 30.0;
+
+/// One stage of [`FilterConfig`], stages are applied in the order they are added
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterStage {
+    /// Butterworth high-pass, removes electrode drift
+    HighPass { cutoff_hz: f64 },
+    /// Butterworth low-pass, removes muscle and high frequency noise
+    LowPass { cutoff_hz: f64 },
+    /// Butterworth high-pass and low-pass pair
+    BandPass { low_hz: f64, high_hz: f64 },
+    /// Notch of mains frequency and its harmonics below Nyquist frequency
+    Notch { mains_hz: f64, harmonics: usize },
+    /// Linear phase FIR band-pass designed by Hamming windowed sinc, `low_hz` 0.0 makes it
+    /// low-pass
+    FirBandPass {
+        low_hz: f64,
+        high_hz: f64,
+        taps: usize,
+    },
+}
+
+/// Filter bank applied to every EEG channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterConfig {
+    pub sample_rate_hz: f64,
+    /// Order of Butterworth stages, it's rounded up to even number
+    pub order: usize,
+    pub stages: Vec<FilterStage>,
+}
+
+impl Default for FilterConfig {
+    /// Pass-through filter at BrainBit sample rate
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: SAMPLE_RATE_HZ as f64,
+            order: DEFAULT_FILTER_ORDER,
+            stages: vec![],
+        }
+    }
+}
+
+impl FilterConfig {
+    /// Usual EEG preprocessing: 1-45 Hz band and notch of 50 or 60 Hz mains
+    pub fn eeg(mains_hz: f64) -> Self {
+        Self::default().band_pass(1.0, 45.0).notch(mains_hz, 2)
+    }
+
+    pub fn sample_rate(mut self, sample_rate_hz: f64) -> Self {
+        self.sample_rate_hz = sample_rate_hz;
+        self
+    }
+
+    pub fn order(mut self, order: usize) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn high_pass(self, cutoff_hz: f64) -> Self {
+        self.stage(FilterStage::HighPass { cutoff_hz })
+    }
+
+    pub fn low_pass(self, cutoff_hz: f64) -> Self {
+        self.stage(FilterStage::LowPass { cutoff_hz })
+    }
+
+    pub fn band_pass(self, low_hz: f64, high_hz: f64) -> Self {
+        self.stage(FilterStage::BandPass { low_hz, high_hz })
+    }
+
+    /// Reject mains frequency and `harmonics - 1` of its multiples
+    pub fn notch(self, mains_hz: f64, harmonics: usize) -> Self {
+        self.stage(FilterStage::Notch {
+            mains_hz,
+            harmonics,
+        })
+    }
+
+    pub fn fir_band_pass(self, low_hz: f64, high_hz: f64, taps: usize) -> Self {
+        self.stage(FilterStage::FirBandPass {
+            low_hz,
+            high_hz,
+            taps,
+        })
+    }
+
+    pub fn stage(mut self, stage: FilterStage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+}
+
+/// Second order IIR section in transposed direct form II
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+/// Kind of second order section, coefficients follow RBJ audio EQ cookbook
+#[derive(Debug, Clone, Copy)]
+enum BiquadKind {
+    LowPass,
+    HighPass,
+    Notch,
+}
+
+impl Biquad {
+    fn new(kind: BiquadKind, sample_rate_hz: f64, frequency_hz: f64, q: f64) -> Self {
+        let w0 = 2.0 * PI * frequency_hz / sample_rate_hz;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = match kind {
+            // digital bandwidth is kept `frequency / q` wide close to Nyquist frequency too
+            BiquadKind::Notch => (PI * frequency_hz / q / sample_rate_hz).tan(),
+            BiquadKind::LowPass | BiquadKind::HighPass => sin / (2.0 * q),
+        };
+        let b = match kind {
+            BiquadKind::LowPass => [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            BiquadKind::HighPass => [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            BiquadKind::Notch => [1.0, -2.0 * cos, 1.0],
+        };
+        let a0 = 1.0 + alpha;
+        Self {
+            b: b.map(|b| b / a0),
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            state: [0.0; 2],
+        }
+    }
+
+    /// Butterworth filter of even `order` as cascade of sections
+    fn butterworth(
+        kind: BiquadKind,
+        sample_rate_hz: f64,
+        cutoff_hz: f64,
+        order: usize,
+    ) -> Vec<Self> {
+        let sections = order.div_ceil(2).max(1);
+        (0..sections)
+            .map(|k| {
+                let angle = (2 * k + 1) as f64 * PI / (4 * sections) as f64;
+                Self::new(kind, sample_rate_hz, cutoff_hz, 1.0 / (2.0 * angle.sin()))
+            })
+            .collect()
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.state = [0.0; 2];
+    }
+}
+
+/// FIR filter keeping the last input samples in a ring buffer
+#[derive(Debug, Clone)]
+struct Fir {
+    taps: Vec<f64>,
+    history: Vec<f64>,
+    position: usize,
+}
+
+impl Fir {
+    /// Hamming windowed sinc band-pass, band edges are in Hz
+    fn band_pass(sample_rate_hz: f64, low_hz: f64, high_hz: f64, taps: usize) -> Self {
+        // odd length keeps the delay an integer number of samples
+        let taps = taps | 1;
+        let middle = (taps / 2) as f64;
+        let low = low_hz / sample_rate_hz;
+        let high = high_hz / sample_rate_hz;
+        let sinc = |x: f64| {
+            if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            }
+        };
+        let coefficients = (0..taps)
+            .map(|n| {
+                let m = n as f64 - middle;
+                let window = 0.54 - 0.46 * (2.0 * PI * n as f64 / (taps - 1) as f64).cos();
+                window * (2.0 * high * sinc(2.0 * high * m) - 2.0 * low * sinc(2.0 * low * m))
+            })
+            .collect();
+        Self {
+            taps: coefficients,
+            history: vec![0.0; taps],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.history[self.position] = x;
+        let len = self.taps.len();
+        let y = self
+            .taps
+            .iter()
+            .enumerate()
+            .map(|(k, tap)| tap * self.history[(self.position + len - k) % len])
+            .sum();
+        self.position = (self.position + 1) % len;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Section {
+    Biquad(Biquad),
+    Fir(Fir),
+}
+
+/// Filter bank of one channel
+#[derive(Debug, Clone)]
+pub struct ChannelFilter {
+    sections: Vec<Section>,
+    /// Samples added at both ends of offline signal to settle the filter
+    padding: usize,
+}
+
+impl ChannelFilter {
+    /// Design filter sections, cutoff frequencies must be below Nyquist frequency
+    pub fn new(config: &FilterConfig) -> color_eyre::Result<Self> {
+        let fs = config.sample_rate_hz;
+        ensure!(fs > 0.0, "Sample rate {fs} Hz must be positive");
+        let nyquist = fs / 2.0;
+        let check = |hz: f64| {
+            ensure!(
+                hz > 0.0 && hz < nyquist,
+                "Filter frequency {hz} Hz is out of (0, {nyquist}) Hz"
+            );
+            Ok(())
+        };
+        let butterworth = |kind, hz| {
+            Biquad::butterworth(kind, fs, hz, config.order)
+                .into_iter()
+                .map(Section::Biquad)
+        };
+        let mut sections = vec![];
+        for stage in &config.stages {
+            match *stage {
+                FilterStage::HighPass { cutoff_hz } => {
+                    check(cutoff_hz)?;
+                    sections.extend(butterworth(BiquadKind::HighPass, cutoff_hz));
+                }
+                FilterStage::LowPass { cutoff_hz } => {
+                    check(cutoff_hz)?;
+                    sections.extend(butterworth(BiquadKind::LowPass, cutoff_hz));
+                }
+                FilterStage::BandPass { low_hz, high_hz } => {
+                    check(low_hz)?;
+                    check(high_hz)?;
+                    ensure!(low_hz < high_hz, "Empty band {low_hz}-{high_hz} Hz");
+                    sections.extend(butterworth(BiquadKind::HighPass, low_hz));
+                    sections.extend(butterworth(BiquadKind::LowPass, high_hz));
+                }
+                FilterStage::Notch {
+                    mains_hz,
+                    harmonics,
+                } => {
+                    check(mains_hz)?;
+                    let notches = (1..=harmonics)
+                        .map(|harmonic| harmonic as f64 * mains_hz)
+                        .take_while(|hz| *hz < nyquist)
+                        .map(|hz| Section::Biquad(Biquad::new(BiquadKind::Notch, fs, hz, NOTCH_Q)));
+                    sections.extend(notches);
+                }
+                FilterStage::FirBandPass {
+                    low_hz,
+                    high_hz,
+                    taps,
+                } => {
+                    if low_hz != 0.0 {
+                        check(low_hz)?;
+                    }
+                    check(high_hz)?;
+                    ensure!(low_hz < high_hz, "Empty band {low_hz}-{high_hz} Hz");
+                    ensure!(taps >= 3, "FIR filter needs at least 3 taps");
+                    sections.push(Section::Fir(Fir::band_pass(fs, low_hz, high_hz, taps)));
+                }
+            }
+        }
+        let lowest_hz = config
+            .stages
+            .iter()
+            .map(|stage| match *stage {
+                FilterStage::HighPass { cutoff_hz } | FilterStage::LowPass { cutoff_hz } => {
+                    cutoff_hz
+                }
+                FilterStage::BandPass { low_hz, .. } => low_hz,
+                // notch rings as long as a signal of its bandwidth
+                FilterStage::Notch { mains_hz, .. } => mains_hz / NOTCH_Q,
+                FilterStage::FirBandPass { .. } => nyquist,
+            })
+            .fold(nyquist, f64::min);
+        let fir_taps = sections
+            .iter()
+            .map(|section| match section {
+                Section::Fir(fir) => fir.taps.len(),
+                Section::Biquad(_) => 0,
+            })
+            .sum::<usize>();
+        // a few periods of the lowest design frequency settle IIR sections
+        let padding = (3.0 * fs / lowest_hz) as usize + fir_taps;
+        Ok(Self { sections, padding })
+    }
+
+    /// Filter the next sample of live signal. NaN of a dropped sample is passed through
+    /// and doesn't change the filter state.
+    pub fn process(&mut self, x: f64) -> f64 {
+        if x.is_nan() {
+            return x;
+        }
+        self.sections
+            .iter_mut()
+            .fold(x, |x, section| match section {
+                Section::Biquad(biquad) => biquad.process(x),
+                Section::Fir(fir) => fir.process(x),
+            })
+    }
+
+    /// Forget previous samples, e.g. when a new measurement is started
+    pub fn reset(&mut self) {
+        for section in &mut self.sections {
+            match section {
+                Section::Biquad(biquad) => biquad.reset(),
+                Section::Fir(fir) => fir.reset(),
+            }
+        }
+    }
+
+    /// Filter recorded signal forward and backward, so the result has no phase shift and
+    /// the magnitude response is squared. Live filter state is not changed.
+    pub fn filter_zero_phase(&self, signal: &[f64]) -> Vec<f64> {
+        let Some((&first, &last)) = signal.first().zip(signal.last()) else {
+            return vec![];
+        };
+        // odd extension keeps the signal and its slope continuous at the ends
+        let padding = self.padding.min(signal.len() - 1);
+        let mut padded = Vec::with_capacity(signal.len() + 2 * padding);
+        padded.extend(signal[1..=padding].iter().rev().map(|x| 2.0 * first - x));
+        padded.extend_from_slice(signal);
+        let end = signal.len() - 1;
+        padded.extend((1..=padding).map(|i| 2.0 * last - signal[end - i]));
+
+        let mut filter = self.clone();
+        filter.reset();
+        let mut forward: Vec<f64> = padded.iter().map(|x| filter.process(*x)).collect();
+        forward.reverse();
+        filter.reset();
+        let mut output: Vec<f64> = forward.iter().map(|x| filter.process(*x)).collect();
+        output.reverse();
+        output.drain(..padding);
+        output.truncate(signal.len());
+        output
+    }
+}
+
+/// Filters of O1, T3, T4 and O2 channels with the same config
+#[derive(Debug, Clone)]
+pub struct EegFilter {
+    channels: [ChannelFilter; 4],
+}
+
+impl EegFilter {
+    pub fn new(config: &FilterConfig) -> color_eyre::Result<Self> {
+        let filter = ChannelFilter::new(config)?;
+        Ok(Self {
+            channels: std::array::from_fn(|_| filter.clone()),
+        })
+    }
+
+    /// Filter the next sample of live signal
+    pub fn process_sample(&mut self, sample: &EegSample) -> EegSample {
+        let values =
+            std::array::from_fn(|i| self.channels[i].process(sample.channel(ChannelType::ALL[i])));
+        eeg_sample(values)
+    }
+
+    /// Filter samples of the next live packet, filter state is carried to the next packet
+    pub fn process_packet(&mut self, packet: &EegPacket) -> EegPacket {
+        EegPacket {
+            counter: packet.counter,
+            samples: packet.samples.map(|sample| self.process_sample(&sample)),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.channels.iter_mut().for_each(ChannelFilter::reset);
+    }
+
+    /// Zero phase filtering of recorded samples, see [`ChannelFilter::filter_zero_phase`]
+    pub fn filter_zero_phase(&self, samples: &[EegSample]) -> Vec<EegSample> {
+        let channels: [Vec<f64>; 4] = std::array::from_fn(|i| {
+            let signal: Vec<f64> = samples
+                .iter()
+                .map(|sample| sample.channel(ChannelType::ALL[i]))
+                .collect();
+            self.channels[i].filter_zero_phase(&signal)
+        });
+        (0..samples.len())
+            .map(|n| eeg_sample(std::array::from_fn(|i| channels[i][n])))
+            .collect()
+    }
+}
+
+/// Sample of O1, T3, T4, O2 values
+fn eeg_sample(values: [f64; 4]) -> EegSample {
+    EegSample {
+        o1: values[0],
+        t3: values[1],
+        t4: values[2],
+        o2: values[3],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f64 = SAMPLE_RATE_HZ as f64;
+
+    fn sine(hz: f64, seconds: f64) -> Vec<f64> {
+        (0..(seconds * FS) as usize)
+            .map(|n| (2.0 * PI * hz * n as f64 / FS).sin())
+            .collect()
+    }
+
+    /// Peak amplitude of the second half of signal, after the filter settles
+    fn amplitude(signal: &[f64]) -> f64 {
+        signal[signal.len() / 2..]
+            .iter()
+            .fold(0.0, |peak, x| f64::max(peak, x.abs()))
+    }
+
+    fn live(config: &FilterConfig, signal: &[f64]) -> Vec<f64> {
+        let mut filter = ChannelFilter::new(config).unwrap();
+        signal.iter().map(|x| filter.process(*x)).collect()
+    }
+
+    #[test]
+    fn test_frequency_response() {
+        let band = FilterConfig::default().band_pass(4.0, 20.0);
+        assert!((amplitude(&live(&band, &sine(10.0, 4.0))) - 1.0).abs() < 0.01);
+        assert!(amplitude(&live(&band, &sine(60.0, 4.0))) < 0.05);
+        assert!(amplitude(&live(&band, &sine(1.0, 4.0))) < 0.05);
+
+        let notch = FilterConfig::eeg(50.0);
+        assert!(amplitude(&live(&notch, &sine(50.0, 4.0))) < 0.05);
+        assert!((amplitude(&live(&notch, &sine(10.0, 4.0))) - 1.0).abs() < 0.05);
+        let dc = vec![100.0; 1000];
+        assert!(amplitude(&live(&notch, &dc)) < 0.5);
+        // harmonic of 60 Hz mains is below Nyquist, the third one is skipped
+        let notch60 = FilterConfig::default().notch(60.0, 3);
+        assert_eq!(2, ChannelFilter::new(&notch60).unwrap().sections.len());
+        assert!(amplitude(&live(&notch60, &sine(120.0, 4.0))) < 0.05);
+
+        let fir = FilterConfig::default().fir_band_pass(0.0, 20.0, 101);
+        assert!((amplitude(&live(&fir, &sine(5.0, 4.0))) - 1.0).abs() < 0.05);
+        assert!(amplitude(&live(&fir, &sine(40.0, 4.0))) < 0.05);
+
+        assert!(ChannelFilter::new(&FilterConfig::default().low_pass(125.0)).is_err());
+        assert!(ChannelFilter::new(&FilterConfig::default().band_pass(13.0, 8.0)).is_err());
+    }
+
+    #[test]
+    fn test_zero_phase_filtering() {
+        let config = FilterConfig::default().low_pass(30.0);
+        let signal = sine(10.0, 4.0);
+        let filter = ChannelFilter::new(&config).unwrap();
+        let filtered = filter.filter_zero_phase(&signal);
+        assert_eq!(signal.len(), filtered.len());
+        let max_error = signal
+            .iter()
+            .zip(&filtered)
+            .fold(0.0, |max, (x, y)| f64::max(max, (x - y).abs()));
+        assert!(max_error < 0.02, "{max_error}");
+        // causal filter delays the signal
+        let delayed = live(&config, &signal);
+        assert!(
+            amplitude(
+                &delayed
+                    .iter()
+                    .zip(&signal)
+                    .map(|(y, x)| y - x)
+                    .collect::<Vec<_>>()
+            ) > 0.2
+        );
+    }
+
+    #[test]
+    fn test_packet_filtering() {
+        let config = FilterConfig::eeg(50.0);
+        let signal = sine(10.0, 1.0);
+        let samples: Vec<EegSample> = signal.iter().map(|x| eeg_sample([*x; 4])).collect();
+
+        let mut by_samples = EegFilter::new(&config).unwrap();
+        let expected: Vec<EegSample> = samples
+            .iter()
+            .map(|s| by_samples.process_sample(s))
+            .collect();
+
+        let mut by_packets = EegFilter::new(&config).unwrap();
+        let filtered: Vec<EegSample> = samples
+            .chunks(2)
+            .enumerate()
+            .flat_map(|(counter, pair)| {
+                let packet = EegPacket {
+                    counter: counter as u16,
+                    samples: [pair[0], pair[1]],
+                };
+                by_packets.process_packet(&packet).samples
+            })
+            .collect();
+        assert_eq!(expected, filtered);
+
+        // dropped sample doesn't break the state
+        let nan = eeg_sample([f64::NAN; 4]);
+        assert!(by_packets.process_sample(&nan).o1.is_nan());
+        assert!(by_packets.process_sample(&samples[0]).o1.is_finite());
+
+        let offline = by_samples.filter_zero_phase(&samples);
+        assert_eq!(samples.len(), offline.len());
+        assert_eq!(offline[100].o1, offline[100].o2);
+    }
+}
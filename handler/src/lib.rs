@@ -1 +1,5 @@
+pub mod artifact;
+pub mod eyes;
+pub mod filter;
 pub mod main_handler;
+pub mod spectrum;
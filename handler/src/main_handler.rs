@@ -6,12 +6,22 @@ use std::sync::Mutex;
 use tracing::{debug, instrument};
 
 use async_trait::async_trait;
-use brainbit::bbit::resist::ResistState;
-use brainbit::bbit::responses::{DeviceStatusData, Nss2Status};
-use brainbit::bbit::traits::EventHandler;
+use brainbit::bbit::battery::BatteryStatus;
+use brainbit::bbit::control::ControlCommandType;
+use brainbit::bbit::device::{CommandData, ConnectionEvent};
+use brainbit::bbit::packet_loss::PacketGap;
+use brainbit::bbit::responses::{
+    DeviceStatusData, EegPacket, Nss2Status, ResistanceReading, PACKET_COUNTER_MODULO,
+};
+use brainbit::bbit::traits::{EventHandler, HandlerResult};
 
-const SKIP_FIRST_RESIST_RECORDS_NUMBER: usize = 20;
-const STORE_RESIST_RECORDS_NUMBER: usize = 20;
+use crate::artifact::Artifact;
+use crate::eyes::{CalibrationStep, EyeStateEvent, EyeStateHandler};
+use crate::filter::{EegFilter, FilterConfig};
+use crate::spectrum::{Band, BandPowerHandler, BandPowers};
+
+/// Power line frequency removed by the default filter
+const DEFAULT_MAINS_HZ: f64 = 50.0;
 
 #[derive(Debug)]
 pub struct BBitHandler {
@@ -21,20 +31,17 @@ pub struct BBitHandler {
     device_status: Mutex<DeviceStatusData>,
     /// data file written with device data
     output: Mutex<File>,
-    /// we skip 'SKIP_FIRST_RESIST_RECORDS_NUMBER' resist records on every channel
-    skipped_resist_records_number: AtomicUsize,
-    /// we have 4 channels, so we need to know which channel is being measured
-    current_chanel_number_resist_measure: AtomicUsize,
-    /// keep read records per channel measurement
-    resist_measure_records: Vec<u8>,
-    /// final measurement result on device after all channels are measured
-    final_resist_results: Mutex<ResistState>,
+    /// filter of decoded samples, it's reset when EEG measurement is started or packets
+    /// sequence is broken
+    filter: Mutex<Option<EegFilter>>,
+    /// counter of the last decoded packet
+    last_counter: Option<u16>,
 }
 
 #[async_trait]
 impl EventHandler for BBitHandler {
     #[instrument(skip(self))]
-    async fn device_status_update(&self, status_data: DeviceStatusData) {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
         let time = Utc::now();
         let formatted: String = time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
         // formatted = formatted.replace("\'", "");
@@ -43,7 +50,7 @@ impl EventHandler for BBitHandler {
         {
             // write eeg data to file
             let mut lock = self.output.lock().unwrap();
-            lock.write_all(msg.as_bytes()).unwrap();
+            lock.write_all(msg.as_bytes())?;
         }
         {
             // read and update local Device Status
@@ -53,10 +60,11 @@ impl EventHandler for BBitHandler {
             lock.cmd_error = status_data.cmd_error;
         }
         self.current_chanel_counter.fetch_add(1, Ordering::SeqCst);
+        Ok(())
     }
 
     #[instrument(skip_all)]
-    async fn eeg_update(self: &mut BBitHandler, eeg_data: Vec<u8>) {
+    async fn eeg_update(self: &mut BBitHandler, eeg_data: Vec<u8>) -> HandlerResult {
         let time = Utc::now();
         let mut _formatted: String = time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
         _formatted = _formatted.replace("\'", "");
@@ -64,31 +72,12 @@ impl EventHandler for BBitHandler {
         let msg = format!("{:>3?}\n", eeg_data);
         {
             let mut lock = self.output.lock().unwrap();
-            lock.write_all(msg.as_bytes()).expect("Can't write log...");
+            lock.write_all(msg.as_bytes())?;
         }
         let nss2status = self.device_status.lock().unwrap().status_nss2;
         match nss2status {
-            Nss2Status::ResistTransmission => {
-                debug!(msg);
-                let skipped_number = self.skipped_resist_records_number.load(Ordering::Relaxed);
-                if skipped_number > 0 {
-                    // skip 'SKIP_FIRST_RESIST_RECORDS_NUMBER' records
-                    debug!("Skipping = {:?} packet", skipped_number);
-                    self.decrease_skipped_resist_records_number();
-                    return;
-                }
-                let gathered_records_number = self.get_resist_measure_records_len();
-                if gathered_records_number >= STORE_RESIST_RECORDS_NUMBER {
-                    debug!(
-                        "Gathered = {:?} records for ch='{}'",
-                        gathered_records_number,
-                        self.current_chanel_number_resist_measure
-                            .load(Ordering::Relaxed)
-                    );
-                    // go to next channel
-                }
-            }
-            Nss2Status::EegTransmission => {
+            // resistance is averaged per electrode by 'BleHandle::measure_resistance'
+            Nss2Status::ResistTransmission | Nss2Status::EegTransmission => {
                 debug!(msg);
             }
             Nss2Status::Stopped => {
@@ -96,34 +85,309 @@ impl EventHandler for BBitHandler {
             }
             _ => {}
         }
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        let expected = self
+            .last_counter
+            .map(|counter| (counter + 1) % PACKET_COUNTER_MODULO);
+        if expected.is_some_and(|expected| expected != packet.counter) {
+            debug!("packets sequence is broken, resetting filter");
+            self.reset_filter();
+        }
+        self.last_counter = Some(packet.counter);
+        let packet = match self.filter.get_mut().unwrap() {
+            Some(filter) => filter.process_packet(&packet),
+            None => packet,
+        };
+        let mut lock = self.output.lock().unwrap();
+        for sample in packet.samples {
+            let msg = format!(
+                "#{:04} O1={:.2} T3={:.2} T4={:.2} O2={:.2}\n",
+                packet.counter, sample.o1, sample.t3, sample.t4, sample.o2
+            );
+            lock.write_all(msg.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        let msg = format!(
+            "#{:04} R {:?}={:.0}\n",
+            reading.counter, reading.channel, reading.ohms
+        );
+        let mut lock = self.output.lock().unwrap();
+        lock.write_all(msg.as_bytes())?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        let time = Utc::now();
+        let formatted: String = time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        let msg = format!("{formatted:?} - Connection='{event:?}'\n");
+        tracing::warn!(msg);
+        let mut lock = self.output.lock().unwrap();
+        lock.write_all(msg.as_bytes())?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        let remaining = battery.remaining.map_or_else(
+            || "unknown".to_string(),
+            |left| format!("{}m", left.as_secs() / 60),
+        );
+        let msg = format!(
+            "Battery={:.1}% charging={} remaining={remaining} alert={:?}\n",
+            battery.smoothed_level, battery.charging, battery.alert
+        );
+        if battery.alert.is_some() {
+            tracing::warn!(msg);
+        } else {
+            debug!(msg);
+        }
+        let mut lock = self.output.lock().unwrap();
+        lock.write_all(msg.as_bytes())?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn packet_loss_update(&mut self, gap: PacketGap) -> HandlerResult {
+        let msg = format!(
+            "{:?} {} packet(s) at #{:04}, lost ratio = {:.4}\n",
+            gap.kind,
+            gap.lost_packets,
+            gap.received_counter,
+            gap.stats.loss_ratio()
+        );
+        tracing::warn!(msg);
+        let mut lock = self.output.lock().unwrap();
+        lock.write_all(msg.as_bytes())?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn send_command(&self, command_data: CommandData) -> HandlerResult {
+        if command_data.command.cmd_type == ControlCommandType::StartEegSignal {
+            self.reset_filter();
+        }
+        let msg = format!(
+            "Command {:?} confirmed={}\n",
+            command_data.command.cmd_type, command_data.confirmed
+        );
+        debug!(msg);
+        let mut lock = self.output.lock().unwrap();
+        lock.write_all(msg.as_bytes())?;
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn flush(&mut self) -> HandlerResult {
+        debug!("flushing data file...");
+        self.output.lock().unwrap().sync_all()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BandPowerHandler for BBitHandler {
+    #[instrument(skip_all)]
+    async fn band_power_update(&mut self, powers: BandPowers) -> HandlerResult {
+        let mut lock = self.output.lock().unwrap();
+        for channel in &powers.channels {
+            let mut msg = format!("#{:04} Bands {:?}", powers.counter, channel.channel);
+            for band in Band::ALL {
+                msg += &format!(
+                    " {band:?}={:.2}({:.0}%)",
+                    channel.absolute(band),
+                    channel.relative(band) * 100.0
+                );
+            }
+            msg += "\n";
+            debug!(msg);
+            lock.write_all(msg.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn artifact_update(&mut self, artifacts: Vec<Artifact>) -> HandlerResult {
+        let mut lock = self.output.lock().unwrap();
+        for artifact in artifacts {
+            let msg = format!(
+                "Artifact {:?} {:?} {:.2}s-{:.2}s\n",
+                artifact.kind,
+                artifact.channel,
+                artifact.start.as_secs_f64(),
+                artifact.end.as_secs_f64()
+            );
+            debug!(msg);
+            lock.write_all(msg.as_bytes())?;
+        }
+        Ok(())
     }
 }
 
 impl BBitHandler {
+    /// Handler writing EEG samples filtered by [`FilterConfig::eeg`] with 50 Hz mains
     pub async fn new(log_file_name: &str) -> color_eyre::Result<Self> {
         Ok(Self {
             current_chanel_counter: AtomicUsize::new(0),
             device_status: Mutex::new(DeviceStatusData::default()),
             output: Mutex::new(File::create(log_file_name)?),
-            skipped_resist_records_number: AtomicUsize::new(
-                SKIP_FIRST_RESIST_RECORDS_NUMBER,
-            ),
-            current_chanel_number_resist_measure: AtomicUsize::new(0),
-            resist_measure_records: Vec::with_capacity(STORE_RESIST_RECORDS_NUMBER),
-            final_resist_results: Mutex::new(ResistState::default()),
+            filter: Mutex::new(Some(EegFilter::new(&FilterConfig::eeg(DEFAULT_MAINS_HZ))?)),
+            last_counter: None,
         })
     }
 
-    fn decrease_skipped_resist_records_number(&mut self) {
-        self.current_chanel_number_resist_measure
-            .fetch_sub(1, Ordering::Relaxed);
+    /// Filter EEG samples with another config
+    pub fn with_filter(self, config: &FilterConfig) -> color_eyre::Result<Self> {
+        *self.filter.lock().unwrap() = Some(EegFilter::new(config)?);
+        Ok(self)
+    }
+
+    /// Write raw EEG samples
+    pub fn without_filter(self) -> Self {
+        *self.filter.lock().unwrap() = None;
+        self
     }
 
-    fn push_resist_data(&mut self, new_data: u8) {
-        self.resist_measure_records.push(new_data);
+    fn reset_filter(&self) {
+        if let Some(filter) = self.filter.lock().unwrap().as_mut() {
+            filter.reset();
+        }
+    }
+}
+
+#[async_trait]
+impl EyeStateHandler for BBitHandler {
+    #[instrument(skip_all)]
+    async fn eye_state_update(&mut self, event: EyeStateEvent) -> HandlerResult {
+        let msg = match event {
+            EyeStateEvent::Calibration(step) => {
+                let prompt = match step {
+                    CalibrationStep::EyesOpen => "keep eyes open and look at one point",
+                    CalibrationStep::EyesClosed => "close eyes and relax",
+                };
+                let msg = format!("Calibration {step:?}: {prompt}");
+                tracing::info!(msg);
+                msg
+            }
+            EyeStateEvent::CalibrationFailed { open, closed } => {
+                let msg = format!(
+                    "Calibration failed, alpha open={:.0}% closed={:.0}% are too close",
+                    open * 100.0,
+                    closed * 100.0
+                );
+                tracing::warn!(msg);
+                msg
+            }
+            EyeStateEvent::Calibrated(baseline) => {
+                let msg = format!(
+                    "Calibrated alpha open={:.0}% closed={:.0}%",
+                    baseline.open * 100.0,
+                    baseline.closed * 100.0
+                );
+                tracing::info!(msg);
+                msg
+            }
+            EyeStateEvent::Transition(transition) => {
+                let msg = format!(
+                    "#{:04} Eyes {:?} confidence={:.2} alpha={:.0}%",
+                    transition.counter,
+                    transition.state,
+                    transition.confidence,
+                    transition.alpha * 100.0
+                );
+                tracing::info!(msg);
+                msg
+            }
+        };
+        self.output
+            .lock()
+            .unwrap()
+            .write_all((msg + "\n").as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use brainbit::bbit::control::ControlPointCommand;
+    use brainbit::bbit::responses::{EegSample, SAMPLES_PER_PACKET};
+
+    fn packet(counter: u16) -> EegPacket {
+        let sample = EegSample {
+            o1: 100.0,
+            t3: 100.0,
+            t4: 100.0,
+            o2: 100.0,
+        };
+        EegPacket {
+            counter,
+            samples: [sample; SAMPLES_PER_PACKET],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_reset() {
+        let log_file = std::env::temp_dir().join(format!(
+            "bbit_handler_filter_reset_{}.txt",
+            std::process::id()
+        ));
+        // constant signal passes the high-pass filter only right after reset
+        let mut handler = BBitHandler::new(log_file.to_str().unwrap())
+            .await
+            .unwrap()
+            .with_filter(&FilterConfig::default().high_pass(30.0))
+            .unwrap();
+        for counter in (0..20).chain(30..40) {
+            handler.eeg_packet_update(packet(counter)).await.unwrap();
+        }
+        let start = ControlPointCommand::new(ControlCommandType::StartEegSignal, Some(vec![0; 4]));
+        handler
+            .send_command(CommandData {
+                command: start,
+                confirmed: true,
+            })
+            .await
+            .unwrap();
+        handler.eeg_packet_update(packet(40)).await.unwrap();
+
+        let output = std::fs::read_to_string(&log_file).unwrap();
+        std::fs::remove_file(&log_file).unwrap();
+        let o1: Vec<f64> = output
+            .lines()
+            .filter_map(|line| line.split(' ').nth(1)?.strip_prefix("O1=")?.parse().ok())
+            .collect();
+        let packet_start = |index: usize| o1[index * SAMPLES_PER_PACKET];
+        assert!(packet_start(0) > 30.0, "{o1:?}");
+        assert!(packet_start(19).abs() < 1.0);
+        // counter discontinuity
+        assert!(packet_start(20) > 30.0);
+        assert!(packet_start(29).abs() < 1.0);
+        // start of EEG measurement
+        assert!(packet_start(30) > 30.0);
     }
 
-    fn get_resist_measure_records_len(&self) -> usize {
-        self.resist_measure_records.len()
+    #[tokio::test]
+    async fn test_raw_samples_without_filter() {
+        let log_file = std::env::temp_dir().join(format!(
+            "bbit_handler_raw_samples_{}.txt",
+            std::process::id()
+        ));
+        let mut handler = BBitHandler::new(log_file.to_str().unwrap())
+            .await
+            .unwrap()
+            .without_filter();
+        handler.eeg_packet_update(packet(0)).await.unwrap();
+        let output = std::fs::read_to_string(&log_file).unwrap();
+        std::fs::remove_file(&log_file).unwrap();
+        assert!(output.starts_with("#0000 O1=100.00 "), "{output}");
     }
 }
@@ -0,0 +1,633 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use brainbit::bbit::battery::BatteryStatus;
+use brainbit::bbit::device::{CommandData, ConnectionEvent};
+use brainbit::bbit::internals::ChannelType;
+use brainbit::bbit::packet_loss::PacketGap;
+use brainbit::bbit::responses::{DeviceStatusData, EegPacket, ResistanceReading, SAMPLE_RATE_HZ};
+use brainbit::bbit::traits::{EventHandler, HandlerResult};
+use color_eyre::eyre::ensure;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::artifact::{Artifact, ArtifactConfig, ArtifactDetector};
+
+/// EEG frequency band
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Band {
+    /// Deep sleep, 1-4 Hz by default
+    Delta,
+    /// Drowsiness, 4-8 Hz by default
+    Theta,
+    /// Relaxed wakefulness with closed eyes, 8-13 Hz by default
+    Alpha,
+    /// Active thinking, 13-30 Hz by default
+    Beta,
+    /// 30-45 Hz by default, the upper edge is below mains frequency
+    Gamma,
+}
+
+impl Band {
+    /// All bands in increasing frequency order
+    pub const ALL: [Band; 5] = [
+        Band::Delta,
+        Band::Theta,
+        Band::Alpha,
+        Band::Beta,
+        Band::Gamma,
+    ];
+
+    /// Default band edges in Hz, the lower edge is included
+    pub fn default_range(&self) -> (f64, f64) {
+        match self {
+            Band::Delta => (1.0, 4.0),
+            Band::Theta => (4.0, 8.0),
+            Band::Alpha => (8.0, 13.0),
+            Band::Beta => (13.0, 30.0),
+            Band::Gamma => (30.0, 45.0),
+        }
+    }
+}
+
+/// Window function applied to every Welch segment
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Taper {
+    /// No window, the best resolution and the worst leakage
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    /// The lowest leakage, main lobe is three times wider than rectangular one
+    Blackman,
+}
+
+impl Taper {
+    /// Periodic window of `len` samples
+    fn weights(&self, len: usize) -> Vec<f64> {
+        (0..len)
+            .map(|i| {
+                let phase = 2.0 * PI * i as f64 / len as f64;
+                match self {
+                    Taper::Rectangular => 1.0,
+                    Taper::Hann => 0.5 - 0.5 * phase.cos(),
+                    Taper::Hamming => 0.54 - 0.46 * phase.cos(),
+                    Taper::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Welch estimation of band powers over a sliding window
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumConfig {
+    pub sample_rate_hz: f64,
+    /// Samples in one segment, frequency resolution is `sample_rate_hz / window`
+    pub window: usize,
+    /// Overlap of neighbour segments, 0.0..1.0
+    pub overlap: f64,
+    /// Segments averaged by one estimate
+    pub segments: usize,
+    pub taper: Taper,
+    /// Samples between estimates
+    pub update_interval: usize,
+    /// Edges of bands in [`Band::ALL`] order
+    pub bands: [(f64, f64); 5],
+}
+
+impl Default for SpectrumConfig {
+    /// 1 s Hann segments with half overlap, 2.5 s of signal is estimated twice a second
+    fn default() -> Self {
+        Self {
+            sample_rate_hz: SAMPLE_RATE_HZ as f64,
+            window: SAMPLE_RATE_HZ as usize,
+            overlap: 0.5,
+            segments: 4,
+            taper: Taper::default(),
+            update_interval: SAMPLE_RATE_HZ as usize / 2,
+            bands: Band::ALL.map(|band| band.default_range()),
+        }
+    }
+}
+
+impl SpectrumConfig {
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn overlap(mut self, overlap: f64) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    pub fn segments(mut self, segments: usize) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    pub fn taper(mut self, taper: Taper) -> Self {
+        self.taper = taper;
+        self
+    }
+
+    /// Emit estimates `rate_hz` times per second
+    pub fn update_rate(mut self, rate_hz: f64) -> Self {
+        self.update_interval = (self.sample_rate_hz / rate_hz).round().max(1.0) as usize;
+        self
+    }
+
+    /// Change edges of the band
+    pub fn band(mut self, band: Band, low_hz: f64, high_hz: f64) -> Self {
+        self.bands[band as usize] = (low_hz, high_hz);
+        self
+    }
+
+    /// Distance between starts of neighbour segments
+    fn step(&self) -> usize {
+        ((self.window as f64 * (1.0 - self.overlap)).round() as usize).max(1)
+    }
+
+    /// Samples covered by one estimate
+    fn span(&self) -> usize {
+        self.window + (self.segments - 1) * self.step()
+    }
+}
+
+/// Band powers of one channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelBandPowers {
+    pub channel: ChannelType,
+    /// Power in uV² in [`Band::ALL`] order
+    pub absolute: [f64; 5],
+    /// Share of the total power in [`Band::ALL`] order
+    pub relative: [f64; 5],
+    /// Power in uV² from the lowest to the highest band edge
+    pub total: f64,
+}
+
+impl ChannelBandPowers {
+    pub fn absolute(&self, band: Band) -> f64 {
+        self.absolute[band as usize]
+    }
+
+    pub fn relative(&self, band: Band) -> f64 {
+        self.relative[band as usize]
+    }
+}
+
+/// Band powers of all channels passed to [`BandPowerHandler::band_power_update`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandPowers {
+    /// Counter of the last packet included into estimate
+    pub counter: u16,
+    /// Channels in O1, T3, T4, O2 order, channels without valid segment are missing
+    pub channels: Vec<ChannelBandPowers>,
+}
+
+impl BandPowers {
+    pub fn channel(&self, channel: ChannelType) -> Option<&ChannelBandPowers> {
+        self.channels
+            .iter()
+            .find(|powers| powers.channel == channel)
+    }
+}
+
+/// Sliding window Welch estimator of band powers of all channels
+pub struct SpectrumEstimator {
+    config: SpectrumConfig,
+    fft: Arc<dyn Fft<f64>>,
+    taper: Vec<f64>,
+    /// Last samples of O1, T3, T4, O2 channels
+    history: [VecDeque<f64>; 4],
+    /// Samples received since start or reset
+    received: u64,
+    /// Samples of O1, T3, T4, O2 channels excluded from estimation
+    masked: [Vec<Range<u64>>; 4],
+    /// Samples left until the next estimate, overshoot of packet granularity is carried
+    until_update: isize,
+}
+
+impl std::fmt::Debug for SpectrumEstimator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpectrumEstimator")
+            .field("config", &self.config)
+            .field("received", &self.received)
+            .field("masked", &self.masked)
+            .field("until_update", &self.until_update)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpectrumEstimator {
+    pub fn new(config: SpectrumConfig) -> color_eyre::Result<Self> {
+        let nyquist = config.sample_rate_hz / 2.0;
+        ensure!(
+            config.sample_rate_hz > 0.0,
+            "Sample rate {} Hz must be positive",
+            config.sample_rate_hz
+        );
+        ensure!(
+            config.window >= 8,
+            "Window of {} samples is too short",
+            config.window
+        );
+        ensure!(
+            (0.0..1.0).contains(&config.overlap),
+            "Overlap {} is out of [0, 1)",
+            config.overlap
+        );
+        ensure!(config.segments >= 1, "At least one segment is required");
+        ensure!(
+            config.update_interval >= 1,
+            "Update interval must be positive"
+        );
+        for (low_hz, high_hz) in config.bands {
+            ensure!(
+                0.0 <= low_hz && low_hz < high_hz && high_hz <= nyquist,
+                "Band {low_hz}-{high_hz} Hz is out of [0, {nyquist}] Hz"
+            );
+        }
+        let fft = FftPlanner::new().plan_fft_forward(config.window);
+        let taper = config.taper.weights(config.window);
+        let span = config.span();
+        Ok(Self {
+            config,
+            fft,
+            taper,
+            history: std::array::from_fn(|_| VecDeque::with_capacity(span)),
+            received: 0,
+            masked: Default::default(),
+            until_update: span as isize,
+        })
+    }
+
+    pub fn config(&self) -> &SpectrumConfig {
+        &self.config
+    }
+
+    /// Forget received samples, e.g. when a new measurement is started
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(VecDeque::clear);
+        self.masked.iter_mut().for_each(Vec::clear);
+        self.received = 0;
+        self.until_update = self.config.span() as isize;
+    }
+
+    /// Exclude segments overlapping the artifact from estimation. Sample counting of
+    /// [`ArtifactDetector`] must start together with the estimator.
+    pub fn mask(&mut self, artifact: &Artifact) {
+        self.masked[artifact.channel as usize].push(artifact.samples.clone());
+    }
+
+    /// Add samples of the packet, returns estimate when the window is filled and update
+    /// interval has passed since the previous one
+    pub fn push_packet(&mut self, packet: &EegPacket) -> Option<BandPowers> {
+        let span = self.config.span();
+        for sample in &packet.samples {
+            for (history, channel) in self.history.iter_mut().zip(ChannelType::ALL) {
+                if history.len() == span {
+                    history.pop_front();
+                }
+                history.push_back(sample.channel(channel));
+            }
+            self.received += 1;
+            self.until_update -= 1;
+        }
+        let first_sample = self.received - self.history[0].len() as u64;
+        for masked in &mut self.masked {
+            masked.retain(|samples| samples.end > first_sample);
+        }
+        if self.until_update > 0 {
+            return None;
+        }
+        self.until_update += self.config.update_interval as isize;
+        let channels = ChannelType::ALL
+            .into_iter()
+            .zip(self.history.iter().zip(&self.masked))
+            .filter_map(|(channel, (history, masked))| {
+                let signal: Vec<f64> = history.iter().copied().collect();
+                let psd = self.welch(&signal, first_sample, masked)?;
+                Some(self.band_powers(channel, &psd))
+            })
+            .collect();
+        Some(BandPowers {
+            counter: packet.counter,
+            channels,
+        })
+    }
+
+    /// One-sided power spectral density in uV²/Hz averaged over segments, segments with
+    /// dropped (NaN) or masked samples are skipped. `signal` starts from `first_sample`.
+    fn welch(&self, signal: &[f64], first_sample: u64, masked: &[Range<u64>]) -> Option<Vec<f64>> {
+        let window = self.config.window;
+        let step = self.config.step();
+        let mut psd = vec![0.0; window / 2 + 1];
+        let mut buffer = vec![Complex::default(); window];
+        let mut used = 0;
+        for start in (0..self.config.segments).map(|i| i * step) {
+            let segment = &signal[start..start + window];
+            let samples = first_sample + start as u64..first_sample + (start + window) as u64;
+            let is_masked = masked
+                .iter()
+                .any(|masked| masked.start < samples.end && samples.start < masked.end);
+            if is_masked || segment.iter().any(|x| !x.is_finite()) {
+                continue;
+            }
+            let mean = segment.iter().sum::<f64>() / window as f64;
+            for ((value, x), weight) in buffer.iter_mut().zip(segment).zip(&self.taper) {
+                *value = Complex::new((x - mean) * weight, 0.0);
+            }
+            self.fft.process(&mut buffer);
+            for (density, value) in psd.iter_mut().zip(&buffer) {
+                *density += value.norm_sqr();
+            }
+            used += 1;
+        }
+        if used == 0 {
+            return None;
+        }
+        let power: f64 = self.taper.iter().map(|w| w * w).sum();
+        let scale = 1.0 / (self.config.sample_rate_hz * power * used as f64);
+        let last = psd.len() - 1;
+        for (k, density) in psd.iter_mut().enumerate() {
+            // negative frequencies are folded, DC and Nyquist bins have no pair
+            let one_sided = if k == 0 || (k == last && window.is_multiple_of(2)) {
+                1.0
+            } else {
+                2.0
+            };
+            *density *= scale * one_sided;
+        }
+        Some(psd)
+    }
+
+    fn band_powers(&self, channel: ChannelType, psd: &[f64]) -> ChannelBandPowers {
+        let resolution = self.config.sample_rate_hz / self.config.window as f64;
+        let power = |low_hz: f64, high_hz: f64| {
+            psd.iter()
+                .enumerate()
+                .filter(|(k, _)| (low_hz..high_hz).contains(&(*k as f64 * resolution)))
+                .map(|(_, density)| density * resolution)
+                .sum::<f64>()
+        };
+        let absolute = self
+            .config
+            .bands
+            .map(|(low_hz, high_hz)| power(low_hz, high_hz));
+        let lowest = self
+            .config
+            .bands
+            .iter()
+            .map(|band| band.0)
+            .fold(f64::MAX, f64::min);
+        let highest = self
+            .config
+            .bands
+            .iter()
+            .map(|band| band.1)
+            .fold(0.0, f64::max);
+        let total = power(lowest, highest);
+        let relative = absolute.map(|band| if total > 0.0 { band / total } else { 0.0 });
+        ChannelBandPowers {
+            channel,
+            absolute,
+            relative,
+            total,
+        }
+    }
+}
+
+/// [`EventHandler`] receiving band powers and artifacts found by [`SpectrumHandler`]
+#[async_trait]
+pub trait BandPowerHandler: EventHandler {
+    /// Dispatched every update interval of [`SpectrumConfig`] after the window is filled
+    async fn band_power_update(&mut self, _powers: BandPowers) -> HandlerResult {
+        Ok(())
+    }
+
+    /// Dispatched when checked windows contain artifacts, they are excluded from band powers
+    async fn artifact_update(&mut self, _artifacts: Vec<Artifact>) -> HandlerResult {
+        Ok(())
+    }
+}
+
+/// Passes all events to the inner handler and adds band powers of EEG packets.
+/// Estimation starts again after resistance measurement.
+#[derive(Debug)]
+pub struct SpectrumHandler<H> {
+    inner: H,
+    estimator: SpectrumEstimator,
+    /// Artifacts detection, it masks band powers estimation
+    detector: Option<ArtifactDetector>,
+}
+
+impl<H: BandPowerHandler> SpectrumHandler<H> {
+    pub fn new(inner: H, config: SpectrumConfig) -> color_eyre::Result<Self> {
+        Ok(Self {
+            inner,
+            estimator: SpectrumEstimator::new(config)?,
+            detector: None,
+        })
+    }
+
+    /// Detect artifacts and exclude them from band powers
+    pub fn with_artifact_detection(mut self, config: ArtifactConfig) -> color_eyre::Result<Self> {
+        let mut detector = ArtifactDetector::new(config)?;
+        // both count samples from the same packet
+        detector.reset();
+        self.estimator.reset();
+        self.detector = Some(detector);
+        Ok(self)
+    }
+
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<H: BandPowerHandler + Send + Sync> EventHandler for SpectrumHandler<H> {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
+        self.inner.device_status_update(status_data).await
+    }
+
+    async fn eeg_update(&mut self, eeg_data: Vec<u8>) -> HandlerResult {
+        self.inner.eeg_update(eeg_data).await
+    }
+
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        let artifacts = match self.detector.as_mut() {
+            Some(detector) => detector.push_packet(&packet),
+            None => vec![],
+        };
+        for artifact in &artifacts {
+            self.estimator.mask(artifact);
+        }
+        let powers = self.estimator.push_packet(&packet);
+        self.inner.eeg_packet_update(packet).await?;
+        if !artifacts.is_empty() {
+            self.inner.artifact_update(artifacts).await?;
+        }
+        match powers {
+            Some(powers) => self.inner.band_power_update(powers).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn packet_loss_update(&mut self, gap: PacketGap) -> HandlerResult {
+        self.inner.packet_loss_update(gap).await
+    }
+
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        // resistance packets interrupt EEG signal
+        self.estimator.reset();
+        if let Some(detector) = self.detector.as_mut() {
+            detector.reset();
+        }
+        self.inner.resistance_update(reading).await
+    }
+
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        self.inner.connection_update(event).await
+    }
+
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        self.inner.battery_update(battery).await
+    }
+
+    async fn send_command(&self, command_data: CommandData) -> HandlerResult {
+        self.inner.send_command(command_data).await
+    }
+
+    async fn flush(&mut self) -> HandlerResult {
+        self.inner.flush().await
+    }
+
+    async fn should_continue(&self) -> bool {
+        self.inner.should_continue().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use brainbit::bbit::responses::EegSample;
+
+    /// Packets of 10 Hz sine with `amplitude` on O1, T3 is silent, O2 gets 20 Hz sine
+    fn packets(amplitude: f64, seconds: f64) -> Vec<EegPacket> {
+        let fs = SAMPLE_RATE_HZ as f64;
+        let sample = |n: usize| {
+            let t = n as f64 / fs;
+            EegSample {
+                o1: amplitude * (2.0 * PI * 10.0 * t).sin(),
+                t3: 0.0,
+                t4: f64::NAN,
+                o2: amplitude * (2.0 * PI * 20.0 * t).sin(),
+            }
+        };
+        (0..(seconds * fs) as usize / 2)
+            .map(|i| EegPacket {
+                counter: (i % 2048) as u16,
+                samples: [sample(2 * i), sample(2 * i + 1)],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_band_powers() {
+        let mut estimator = SpectrumEstimator::new(SpectrumConfig::default()).unwrap();
+        let updates: Vec<BandPowers> = packets(20.0, 10.0)
+            .iter()
+            .filter_map(|packet| estimator.push_packet(packet))
+            .collect();
+        // 2.5 s window is filled, then estimates come twice a second
+        assert_eq!(16, updates.len());
+
+        let last = updates.last().unwrap();
+        let o1 = last.channel(ChannelType::O1).unwrap();
+        // mean power of sine is A²/2
+        assert!((o1.absolute(Band::Alpha) - 200.0).abs() < 10.0, "{o1:?}");
+        assert!(o1.relative(Band::Alpha) > 0.99);
+        assert!((o1.total - 200.0).abs() < 10.0);
+        let o2 = last.channel(ChannelType::O2).unwrap();
+        assert!(o2.relative(Band::Beta) > 0.99);
+        let t3 = last.channel(ChannelType::T3).unwrap();
+        assert_eq!(0.0, t3.total);
+        assert_eq!([0.0; 5], t3.relative);
+        // dropped samples are not estimated
+        assert!(last.channel(ChannelType::T4).is_none());
+
+        estimator.reset();
+        assert!(estimator.push_packet(&packets(20.0, 0.1)[0]).is_none());
+    }
+
+    #[test]
+    fn test_masked_artifacts() {
+        use crate::artifact::ArtifactKind;
+
+        let mut estimator = SpectrumEstimator::new(SpectrumConfig::default()).unwrap();
+        let artifact = |channel, samples: Range<u64>| Artifact {
+            channel,
+            kind: ArtifactKind::Motion,
+            samples,
+            start: Duration::ZERO,
+            end: Duration::ZERO,
+        };
+        let packets = packets(20.0, 8.0);
+        // 624 samples don't fill the window yet
+        for packet in &packets[..312] {
+            assert!(estimator.push_packet(packet).is_none());
+        }
+        // all segments of O1 and the first segment of O2 are contaminated
+        estimator.mask(&artifact(ChannelType::O1, 0..625));
+        estimator.mask(&artifact(ChannelType::O2, 100..110));
+        let powers = estimator.push_packet(&packets[312]).unwrap();
+        assert!(powers.channel(ChannelType::O1).is_none());
+        let o2 = powers.channel(ChannelType::O2).unwrap();
+        assert!(o2.relative(Band::Beta) > 0.99);
+
+        // masks are dropped when they leave the window
+        for packet in &packets[313..] {
+            estimator.push_packet(packet);
+        }
+        assert!(estimator.masked.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn test_spectrum_config() {
+        let config = SpectrumConfig::default()
+            .window(128)
+            .overlap(0.75)
+            .segments(3)
+            .taper(Taper::Blackman)
+            .update_rate(10.0)
+            .band(Band::Alpha, 7.5, 12.5);
+        assert_eq!(32, config.step());
+        assert_eq!(192, config.span());
+        assert_eq!(25, config.update_interval);
+        let mut estimator = SpectrumEstimator::new(config).unwrap();
+        let updates = packets(10.0, 2.0)
+            .iter()
+            .filter_map(|packet| estimator.push_packet(packet))
+            .count();
+        assert_eq!((500 - 192) / 25 + 1, updates);
+
+        assert!(SpectrumEstimator::new(SpectrumConfig::default().overlap(1.0)).is_err());
+        assert!(
+            SpectrumEstimator::new(SpectrumConfig::default().band(Band::Gamma, 30.0, 200.0))
+                .is_err()
+        );
+        assert!(SpectrumEstimator::new(SpectrumConfig::default().window(4)).is_err());
+    }
+}
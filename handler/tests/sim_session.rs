@@ -0,0 +1,1095 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use brainbit::bbit::battery::{BatteryAlert, BatteryConfig, BatteryStatus};
+use brainbit::bbit::control::ControlCommandType;
+use brainbit::bbit::device::{BBitSensor, BleHandle, CommandData, ConnectionEvent, LoopOutcome};
+use brainbit::bbit::errors::Error;
+use brainbit::bbit::internals::{
+    ChannelConfig, ChannelInput, ChannelPower, ChannelType, EegConfig, Gain,
+};
+use brainbit::bbit::policy::{Backoff, ConnectPolicy};
+use brainbit::bbit::record::{RecordingTransport, ReplaySpeed, SessionReplay};
+use brainbit::bbit::resist::{ResistanceSweepConfig, ResistsMeasureResult};
+use brainbit::bbit::responses::{
+    CommandExecutionState, DeviceStatusData, EegPacket, Nss2Status, ResistanceReading,
+};
+use brainbit::bbit::sealed::Configure;
+use brainbit::bbit::stream::{EventSubscriber, Received, SensorEvent};
+use brainbit::bbit::traits::{EventHandler, HandlerErrorPolicy, HandlerResult};
+use brainbit::bbit::transport::Transport;
+use brainbit::bbit::uuids::{EventType, PERIPHERAL_NAME_MATCH_FILTER};
+use brainbit::sim::{SimConfig, SimulatedBrainBit};
+use handler::artifact::{Artifact, ArtifactConfig, ArtifactKind};
+use handler::eyes::{
+    CalibrationStep, EyeState, EyeStateConfig, EyeStateEvent, EyeStateHandler, EyeStateTracker,
+    EyeTransition,
+};
+use handler::filter::FilterConfig;
+use handler::main_handler::BBitHandler;
+use handler::spectrum::{Band, BandPowerHandler, BandPowers, SpectrumConfig, SpectrumHandler};
+use tokio::sync::{mpsc, Notify};
+
+/// Maximum time to wait for an expected event
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Temporary file named uniquely for the test run
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("bbit_handler_{}_{name}", std::process::id()))
+}
+
+/// Simulated device connected with device state and EEG streams listened
+async fn connect<T: Transport>(transport: T) -> BBitSensor<Configure, T> {
+    BBitSensor::with_transport(transport)
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await
+        .unwrap()
+        .listen(EventType::State)
+        .listen(EventType::EegOrResistance)
+}
+
+/// Event loop of the simulated device with default configuration
+async fn start<T: Transport, H: EventHandler + Send + Sync + 'static>(
+    transport: T,
+    handler: H,
+) -> BleHandle {
+    connect(transport)
+        .await
+        .build()
+        .await
+        .unwrap()
+        .event_loop(handler)
+        .await
+}
+
+/// Event passed to [`Recorder`]
+#[derive(Debug, Clone)]
+enum Recorded {
+    Status(DeviceStatusData),
+    Raw(Vec<u8>),
+    Packet(EegPacket),
+    Resistance(ResistanceReading),
+    Battery(BatteryStatus),
+    Connection(ConnectionEvent),
+    Command(CommandData),
+    Bands(BandPowers),
+    Artifact(Artifact),
+    Eyes(EyeStateEvent),
+}
+
+fn packet(event: &Recorded) -> Option<EegPacket> {
+    match event {
+        Recorded::Packet(packet) => Some(packet.clone()),
+        _ => None,
+    }
+}
+
+fn status(event: &Recorded) -> Option<DeviceStatusData> {
+    match event {
+        Recorded::Status(status) => Some(*status),
+        _ => None,
+    }
+}
+
+fn transition(event: &Recorded) -> Option<EyeTransition> {
+    match event {
+        Recorded::Eyes(EyeStateEvent::Transition(transition)) => Some(*transition),
+        _ => None,
+    }
+}
+
+/// Handler passing typed events to the test
+struct Recorder(mpsc::UnboundedSender<Recorded>);
+
+/// Handler recording events and the events recorded by it
+fn recording() -> (Recorder, Events) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let events = Events {
+        rx,
+        received: vec![],
+    };
+    (Recorder(tx), events)
+}
+
+impl Recorder {
+    fn record(&self, event: Recorded) -> HandlerResult {
+        // the test may stop listening before the event loop is finished
+        let _ = self.0.send(event);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for Recorder {
+    async fn device_status_update(&self, status_data: DeviceStatusData) -> HandlerResult {
+        self.record(Recorded::Status(status_data))
+    }
+
+    async fn eeg_update(&mut self, eeg_data: Vec<u8>) -> HandlerResult {
+        self.record(Recorded::Raw(eeg_data))
+    }
+
+    async fn eeg_packet_update(&mut self, packet: EegPacket) -> HandlerResult {
+        self.record(Recorded::Packet(packet))
+    }
+
+    async fn resistance_update(&mut self, reading: ResistanceReading) -> HandlerResult {
+        self.record(Recorded::Resistance(reading))
+    }
+
+    async fn battery_update(&mut self, battery: BatteryStatus) -> HandlerResult {
+        self.record(Recorded::Battery(battery))
+    }
+
+    async fn connection_update(&mut self, event: ConnectionEvent) -> HandlerResult {
+        self.record(Recorded::Connection(event))
+    }
+
+    async fn send_command(&self, command_data: CommandData) -> HandlerResult {
+        self.record(Recorded::Command(command_data))
+    }
+}
+
+#[async_trait]
+impl BandPowerHandler for Recorder {
+    async fn band_power_update(&mut self, powers: BandPowers) -> HandlerResult {
+        self.record(Recorded::Bands(powers))
+    }
+
+    async fn artifact_update(&mut self, artifacts: Vec<Artifact>) -> HandlerResult {
+        for artifact in artifacts {
+            self.record(Recorded::Artifact(artifact))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EyeStateHandler for Recorder {
+    async fn eye_state_update(&mut self, event: EyeStateEvent) -> HandlerResult {
+        self.record(Recorded::Eyes(event))
+    }
+}
+
+/// Events recorded by [`Recorder`] in order of dispatching
+struct Events {
+    rx: mpsc::UnboundedReceiver<Recorded>,
+    received: Vec<Recorded>,
+}
+
+impl Events {
+    /// Wait for the next event found by `f`, skipped events are kept
+    async fn wait_for<T>(&mut self, mut f: impl FnMut(&Recorded) -> Option<T>) -> T {
+        let wait = async {
+            loop {
+                let event = self.rx.recv().await.expect("event loop is finished");
+                let found = f(&event);
+                self.received.push(event);
+                if let Some(found) = found {
+                    return found;
+                }
+            }
+        };
+        tokio::time::timeout(EVENT_TIMEOUT, wait)
+            .await
+            .expect("expected event is not recorded")
+    }
+
+    /// Wait for the next `count` events found by `f`
+    async fn collect<T>(
+        &mut self,
+        count: usize,
+        mut f: impl FnMut(&Recorded) -> Option<T>,
+    ) -> Vec<T> {
+        let mut found = Vec::with_capacity(count);
+        while found.len() < count {
+            found.push(self.wait_for(&mut f).await);
+        }
+        found
+    }
+
+    /// Wait until all events recorded so far satisfy `f`
+    async fn wait_until(&mut self, f: impl Fn(&[Recorded]) -> bool) {
+        while !f(&self.received) {
+            self.wait_for(|_| Some(())).await;
+        }
+    }
+
+    /// All recorded events, called when the event loop is finished
+    fn finish(mut self) -> Vec<Recorded> {
+        while let Ok(event) = self.rx.try_recv() {
+            self.received.push(event);
+        }
+        self.received
+    }
+}
+
+/// Wait for the next event found by `f` in the broadcast subscription
+async fn next_event<T>(
+    subscriber: &mut EventSubscriber,
+    mut f: impl FnMut(SensorEvent) -> Option<T>,
+) -> T {
+    let wait = async {
+        loop {
+            match subscriber.recv().await {
+                Some(Received::Event(event)) => {
+                    if let Some(found) = f(event) {
+                        return found;
+                    }
+                }
+                Some(Received::Lagged(_)) => {}
+                None => panic!("event loop is finished"),
+            }
+        }
+    };
+    tokio::time::timeout(EVENT_TIMEOUT, wait)
+        .await
+        .expect("expected event is not published")
+}
+
+fn battery(event: SensorEvent) -> Option<BatteryStatus> {
+    match event {
+        SensorEvent::Battery(battery) => Some(battery),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn test_resistance_session_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let (recorder, mut events) = recording();
+    let handle = start(device.clone(), recorder).await;
+
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    handle.start().await.unwrap().unwrap();
+    events
+        .wait_for(|event| match event {
+            Recorded::Status(status) => {
+                (status.status_nss2 == Nss2Status::ResistTransmission).then_some(())
+            }
+            _ => None,
+        })
+        .await;
+    assert_eq!(Nss2Status::ResistTransmission, device.status().status_nss2);
+    let readings = events
+        .collect(10, |event| match event {
+            Recorded::Resistance(reading) => Some(*reading),
+            _ => None,
+        })
+        .await;
+
+    handle.stop().await;
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+
+    // simulated O1 electrode impedance is 150 kOhm
+    assert!(readings
+        .iter()
+        .all(|reading| reading.channel == ChannelType::O1
+            && (reading.ohms - 150_000.0).abs() < 15_000.0));
+    // 20 bytes data packets are passed to handler as well
+    let raw: Vec<Vec<u8>> = events
+        .finish()
+        .into_iter()
+        .filter_map(|event| match event {
+            Recorded::Raw(data) => Some(data),
+            _ => None,
+        })
+        .collect();
+    assert!(raw.len() >= 10);
+    assert!(raw.iter().all(|data| data.len() == 20));
+}
+
+#[tokio::test]
+async fn test_resistance_sweep_on_simulated_device() {
+    // T3 electrode is detached from the head
+    let device = SimulatedBrainBit::new(SimConfig {
+        electrode_resistance: [150_000.0, 5_000_000.0, 180_000.0, 160_000.0],
+        ..Default::default()
+    });
+    let (recorder, events) = recording();
+    let handle = start(device.clone(), recorder).await;
+
+    let state = handle
+        .measure_resistance(ResistanceSweepConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(ResistsMeasureResult::GOOD, state.ch_o1);
+    assert_eq!(ResistsMeasureResult::BAD, state.ch_t3);
+    assert_eq!(ResistsMeasureResult::GOOD, state.ch_t4);
+    assert_eq!(ResistsMeasureResult::GOOD, state.ch_o2);
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    handle.stop().await;
+
+    let events = events.finish();
+    for channel in [
+        ChannelType::O1,
+        ChannelType::T3,
+        ChannelType::T4,
+        ChannelType::O2,
+    ] {
+        assert!(events.iter().any(
+            |event| matches!(event, Recorded::Resistance(reading) if reading.channel == channel)
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_resistance_sweep_skips_stale_packets_on_simulated_device() {
+    // device executes commands late, reporting resistance transmission of the previous
+    // channel meanwhile
+    let device = SimulatedBrainBit::new(SimConfig {
+        electrode_resistance: [50_000.0, 400_000.0, 50_000.0, 400_000.0],
+        status_interval: Duration::from_millis(20),
+        ..Default::default()
+    });
+    device.set_command_delay(Duration::from_millis(200));
+    let (recorder, events) = recording();
+    let handle = start(device.clone(), recorder).await;
+
+    let config = ResistanceSweepConfig {
+        skip_readings: 0,
+        average_readings: 5,
+        max_good_ohms: 200_000.0,
+        ..Default::default()
+    };
+    let state = handle.measure_resistance(config).await.unwrap().unwrap();
+    assert_eq!(ResistsMeasureResult::GOOD, state.ch_o1);
+    assert_eq!(ResistsMeasureResult::BAD, state.ch_t3);
+    assert_eq!(ResistsMeasureResult::GOOD, state.ch_t4);
+    assert_eq!(ResistsMeasureResult::BAD, state.ch_o2);
+    handle.stop().await;
+
+    // the channel is switched through stopped transmission
+    let commands: Vec<ControlCommandType> = events
+        .finish()
+        .into_iter()
+        .filter_map(|event| match event {
+            Recorded::Command(command) => Some(command.command.cmd_type.clone()),
+            _ => None,
+        })
+        .collect();
+    let switch = [ControlCommandType::StopAll, ControlCommandType::StartResist];
+    assert_eq!(
+        3,
+        commands.windows(2).filter(|pair| *pair == switch).count()
+    );
+}
+
+#[tokio::test]
+async fn test_switching_eeg_and_resistance_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let (recorder, mut events) = recording();
+    let handle = start(device.clone(), recorder).await;
+
+    let config = EegConfig::new(Gain::X12)
+        .with_channel(
+            ChannelType::T3,
+            ChannelConfig::new(Gain::X12).with_power(ChannelPower::Down),
+        )
+        .with_channel(
+            ChannelType::O2,
+            ChannelConfig::new(Gain::X1).with_input(ChannelInput::Test),
+        );
+    for _ in 0..2 {
+        handle.start_eeg(config).await.unwrap().unwrap();
+        let packets = events.collect(25, packet).await;
+        assert_eq!(Nss2Status::EegTransmission, device.status().status_nss2);
+        // T3 is powered down, O2 measures test signal
+        for sample in packets.iter().flat_map(|packet| packet.samples) {
+            assert_eq!(0.0, sample.t3);
+            assert!(sample.o2.abs() > 900.0, "{sample:?}");
+            assert!(sample.o1.abs() < 100.0, "{sample:?}");
+        }
+        handle.stop_measurement().await.unwrap().unwrap();
+        assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+
+        let state = handle
+            .measure_resistance(ResistanceSweepConfig::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(state.is_good());
+    }
+    handle.stop().await;
+}
+
+#[tokio::test]
+async fn test_command_acknowledgement_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let (recorder, events) = recording();
+    let handle = connect(device.clone())
+        .await
+        .command_timeout(Duration::from_millis(300))
+        .build()
+        .await
+        .unwrap()
+        .event_loop(recorder)
+        .await;
+
+    device.set_command_error(Some(CommandExecutionState::SwitchModeError));
+    let res = handle.start_eeg(EegConfig::default()).await.unwrap();
+    assert!(matches!(
+        res,
+        Err(Error::CommandRejected(
+            CommandExecutionState::SwitchModeError
+        ))
+    ));
+    device.set_command_error(None);
+
+    device.set_commands_ignored(true);
+    let res = handle.start_eeg(EegConfig::default()).await.unwrap();
+    assert!(matches!(res, Err(Error::NoControlPointResponse)));
+    device.set_commands_ignored(false);
+
+    // confirmed command means device is already in the requested state
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Nss2Status::EegTransmission, device.status().status_nss2);
+    handle.stop_measurement().await.unwrap().unwrap();
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    handle.stop().await;
+
+    // written commands are passed to handler
+    let commands: Vec<(ControlCommandType, bool)> = events
+        .finish()
+        .into_iter()
+        .filter_map(|event| match event {
+            Recorded::Command(data) => Some((data.command.cmd_type, data.confirmed)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (ControlCommandType::StartEegSignal, false),
+            (ControlCommandType::StartEegSignal, false),
+            (ControlCommandType::StartEegSignal, true),
+            (ControlCommandType::StopAll, true),
+        ],
+        commands[..4]
+    );
+}
+
+#[tokio::test]
+async fn test_reconnection_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let (recorder, mut events) = recording();
+    let handle = start(device.clone(), recorder).await;
+
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    device.set_in_range(false);
+    events
+        .wait_for(|event| {
+            matches!(event, Recorded::Connection(ConnectionEvent::Disconnected)).then_some(())
+        })
+        .await;
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    device.set_in_range(true);
+
+    // supervisor reconnects, subscribes streams and restarts EEG measurement
+    events
+        .wait_for(|event| {
+            matches!(event, Recorded::Connection(ConnectionEvent::Reconnected)).then_some(())
+        })
+        .await;
+    // decoded EEG samples are received after reconnection
+    events.wait_for(packet).await;
+    assert_eq!(Nss2Status::EegTransmission, device.status().status_nss2);
+    handle.stop().await;
+}
+
+#[tokio::test]
+async fn test_reconnection_limits_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let (recorder, mut events) = recording();
+    let handle = BBitSensor::with_transport(device.clone())
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await
+        .unwrap()
+        .listen(EventType::State)
+        .reconnect(false)
+        .build()
+        .await
+        .unwrap()
+        .event_loop(recorder)
+        .await;
+    events.wait_for(status).await;
+    device.set_in_range(false);
+    events
+        .wait_for(|event| {
+            matches!(event, Recorded::Connection(ConnectionEvent::Disconnected)).then_some(())
+        })
+        .await;
+    handle.stop().await;
+
+    // reconnection gives up after the policy attempts
+    device.set_in_range(true);
+    let policy = ConnectPolicy::default()
+        .scan_duration(Duration::ZERO)
+        .backoff(Backoff::Fixed(Duration::from_millis(10)))
+        .max_attempts(Some(3));
+    let (recorder, mut events) = recording();
+    let handle = BBitSensor::with_transport(device.clone())
+        .connect_policy(policy)
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await
+        .unwrap()
+        .listen(EventType::State)
+        .build()
+        .await
+        .unwrap()
+        .event_loop(recorder)
+        .await;
+    events.wait_for(status).await;
+    device.set_in_range(false);
+    events
+        .wait_for(|event| {
+            matches!(
+                event,
+                Recorded::Connection(ConnectionEvent::ReconnectFailed)
+            )
+            .then_some(())
+        })
+        .await;
+    handle.stop().await;
+    let connection: Vec<ConnectionEvent> = events
+        .finish()
+        .into_iter()
+        .filter_map(|event| match event {
+            Recorded::Connection(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ConnectionEvent::Disconnected,
+            ConnectionEvent::ReconnectFailed
+        ],
+        connection
+    );
+}
+
+#[tokio::test]
+async fn test_connect_policy_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    device.set_in_range(false);
+    let policy = ConnectPolicy::default()
+        .scan_duration(Duration::ZERO)
+        .backoff(Backoff::Fixed(Duration::from_millis(10)));
+
+    let mut attempts = 0;
+    let res = BBitSensor::with_transport(device.clone())
+        .connect_policy(policy.clone().max_attempts(Some(3)))
+        .map_connect(PERIPHERAL_NAME_MATCH_FILTER, |_| {
+            attempts += 1;
+            Ok(())
+        })
+        .await;
+    assert!(matches!(res, Err(Error::ConnectAttemptsExhausted(3))));
+    assert_eq!(3, attempts);
+
+    let res = BBitSensor::with_transport(device.clone())
+        .connect_policy(
+            policy
+                .clone()
+                .max_attempts(None)
+                .deadline(Duration::from_millis(100)),
+        )
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await;
+    assert!(matches!(res, Err(Error::ConnectTimeout)));
+
+    let cancelled = policy.clone().max_attempts(None);
+    let token = cancelled.cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        token.cancel();
+    });
+    let res = BBitSensor::with_transport(device.clone())
+        .connect_policy(cancelled)
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await;
+    assert!(matches!(res, Err(Error::ConnectCancelled)));
+
+    // clones of the policy share cancelled token, connect with a new one
+    device.set_in_range(true);
+    BBitSensor::with_transport(device.clone())
+        .connect_policy(ConnectPolicy::default().scan_duration(Duration::ZERO))
+        .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
+        .await
+        .unwrap();
+    assert!(device.is_connected().await);
+}
+
+/// Handler failing after some EEG packets and finishing after some more ones
+struct FailingHandler {
+    packets: usize,
+    fail_after: usize,
+    finish_after: usize,
+    /// Notified on the first failure
+    failed: Arc<Notify>,
+}
+
+#[async_trait]
+impl EventHandler for FailingHandler {
+    async fn eeg_packet_update(&mut self, _packet: EegPacket) -> HandlerResult {
+        self.packets += 1;
+        if self.packets > self.fail_after {
+            self.failed.notify_one();
+            return Err(format!("packet #{} is not stored", self.packets).into());
+        }
+        Ok(())
+    }
+
+    async fn should_continue(&self) -> bool {
+        self.packets < self.finish_after
+    }
+}
+
+#[tokio::test]
+async fn test_handler_errors_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let failed = Arc::new(Notify::new());
+    let start = |policy, fail_after, finish_after| {
+        let device = device.clone();
+        let failed = failed.clone();
+        async move {
+            let handle = connect(device)
+                .await
+                .on_handler_error(policy)
+                .build()
+                .await
+                .unwrap()
+                .event_loop(FailingHandler {
+                    packets: 0,
+                    fail_after,
+                    finish_after,
+                    failed,
+                })
+                .await;
+            handle
+                .start_eeg(EegConfig::default())
+                .await
+                .unwrap()
+                .unwrap();
+            handle
+        }
+    };
+
+    // handler error stops measurement and disconnects
+    let handle = start(HandlerErrorPolicy::Stop, 5, usize::MAX).await;
+    let outcome = tokio::time::timeout(EVENT_TIMEOUT, handle.join())
+        .await
+        .unwrap();
+    assert!(
+        matches!(outcome, LoopOutcome::HandlerError(ref e) if matches!(**e, Error::HandlerError(_)))
+    );
+    assert!(handle.is_finished());
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    assert!(!device.is_connected().await);
+
+    // errors are logged, the loop is finished by the handler
+    let handle = start(HandlerErrorPolicy::LogAndContinue, 5, 20).await;
+    let outcome = tokio::time::timeout(EVENT_TIMEOUT, handle.join())
+        .await
+        .unwrap();
+    assert!(matches!(outcome, LoopOutcome::Finished));
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    assert!(!device.is_connected().await);
+
+    // handling is paused on error until resumed
+    let failed = Arc::new(Notify::new());
+    let handle = connect(device.clone())
+        .await
+        .on_handler_error(HandlerErrorPolicy::Pause)
+        .build()
+        .await
+        .unwrap()
+        .event_loop(FailingHandler {
+            packets: 0,
+            fail_after: 5,
+            finish_after: usize::MAX,
+            failed: failed.clone(),
+        })
+        .await;
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::timeout(EVENT_TIMEOUT, failed.notified())
+        .await
+        .unwrap();
+    assert!(!handle.is_finished());
+    assert_eq!(Nss2Status::EegTransmission, device.status().status_nss2);
+    let joined = handle.clone();
+    handle.stop().await;
+    let outcome = tokio::time::timeout(EVENT_TIMEOUT, joined.join())
+        .await
+        .unwrap();
+    assert!(matches!(outcome, LoopOutcome::Stopped));
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown_on_simulated_device() {
+    let device = SimulatedBrainBit::default();
+    let (recorder, mut events) = recording();
+    let handle = start(device.clone(), recorder).await;
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(2, device.subscriptions().len());
+    events.wait_for(packet).await;
+    let joined = handle.clone();
+    assert!(matches!(handle.stop().await, LoopOutcome::Stopped));
+    assert!(joined.is_finished());
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    assert!(device.subscriptions().is_empty());
+    // explicit disconnection is not restored, as reconnection is supervised by the finished loop
+    assert!(!device.is_connected().await);
+
+    // dropping all handles shuts down the event loop too
+    let (recorder, mut events) = recording();
+    let handle = start(device.clone(), recorder).await;
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut subscriber = handle.subscribe();
+    drop(handle);
+    // the subscription is closed when the event loop is finished
+    let closed = async { while subscriber.recv().await.is_some() {} };
+    tokio::time::timeout(EVENT_TIMEOUT, closed).await.unwrap();
+    assert!(!device.is_connected().await);
+    assert_eq!(Nss2Status::Stopped, device.status().status_nss2);
+    assert!(device.subscriptions().is_empty());
+    let commands: Vec<ControlCommandType> = events
+        .collect(2, |event| match event {
+            Recorded::Command(data) => Some(data.command.cmd_type.clone()),
+            _ => None,
+        })
+        .await;
+    assert_eq!(
+        vec![
+            ControlCommandType::StartEegSignal,
+            ControlCommandType::StopAll
+        ],
+        commands
+    );
+}
+
+#[tokio::test]
+async fn test_critical_battery_on_simulated_device() {
+    let device = SimulatedBrainBit::new(SimConfig {
+        status_interval: Duration::from_millis(50),
+        ..Default::default()
+    });
+    let (recorder, events) = recording();
+    let handle = connect(device.clone())
+        .await
+        .battery(
+            BatteryConfig::default()
+                .smoothing(1.0)
+                .stop_on_critical(true),
+        )
+        .build()
+        .await
+        .unwrap()
+        .event_loop(recorder)
+        .await;
+    let mut subscriber = handle.subscribe();
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // the charging flag is not counted in level
+    device.set_battery_level(0x80 | 0x51);
+    let charging = next_event(&mut subscriber, |event| {
+        battery(event).filter(|battery| battery.charging)
+    })
+    .await;
+    assert!(charging.level < 100.0);
+
+    device.set_battery_level(0x03);
+    let discharging = next_event(&mut subscriber, |event| {
+        battery(event).filter(|battery| !battery.charging)
+    })
+    .await;
+    assert_eq!(Some(BatteryAlert::Critical), discharging.alert);
+    // measurement is stopped before the device powers down, the event loop keeps running
+    next_event(&mut subscriber, |event| match event {
+        SensorEvent::DeviceStatus(status) => {
+            (status.status_nss2 == Nss2Status::Stopped).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+    assert!(!handle.is_finished());
+    assert!(matches!(handle.stop().await, LoopOutcome::Stopped));
+    // the alert is passed to handler as well
+    assert!(events.finish().iter().any(|event| matches!(
+        event,
+        Recorded::Battery(battery) if battery.alert == Some(BatteryAlert::Critical)
+    )));
+}
+
+#[tokio::test]
+async fn test_replay_of_recorded_session() {
+    let session_file = temp_file("recorded.bbrs");
+    let transport = RecordingTransport::new(SimulatedBrainBit::default(), &session_file).unwrap();
+    let (recorder, mut live) = recording();
+    let handle = start(transport, recorder).await;
+    handle
+        .start_eeg(EegConfig::new(Gain::X12))
+        .await
+        .unwrap()
+        .unwrap();
+    live.collect(20, packet).await;
+    handle.stop().await;
+
+    let (mut replayed_recorder, replayed) = recording();
+    SessionReplay::open(&session_file)
+        .unwrap()
+        .speed(ReplaySpeed::Scaled(10.0))
+        .run(&mut replayed_recorder)
+        .await
+        .unwrap();
+    std::fs::remove_file(&session_file).unwrap();
+
+    // decoded packets are the same as handled during the live session. Packets recorded
+    // but not handled live (on shutdown or on channel overflow) are replayed as well, so
+    // only packets present in both sessions are compared.
+    let packets = |events: Events| -> BTreeMap<u16, EegPacket> {
+        events
+            .finish()
+            .iter()
+            .filter_map(packet)
+            .map(|packet| (packet.counter, packet))
+            .collect()
+    };
+    let live = packets(live);
+    let replayed = packets(replayed);
+    let common: Vec<_> = live
+        .iter()
+        .filter(|(counter, _)| replayed.contains_key(*counter))
+        .collect();
+    assert!(common.len() >= 20);
+    for (counter, packet) in common {
+        assert_eq!(packet, &replayed[counter]);
+    }
+}
+
+#[tokio::test]
+async fn test_filtered_eeg_on_simulated_device() {
+    let log_file = temp_file("filtered.txt");
+    let device = SimulatedBrainBit::new(SimConfig {
+        noise_amplitude_uv: 0.0,
+        ..Default::default()
+    });
+    // simulated alpha rhythm is 10 Hz, it's removed by the high-pass filter
+    let handler = BBitHandler::new(log_file.to_str().unwrap())
+        .await
+        .unwrap()
+        .with_filter(&FilterConfig::default().high_pass(30.0))
+        .unwrap();
+
+    let handle = start(device, handler).await;
+    let mut subscriber = handle.subscribe();
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    for _ in 0..40 {
+        next_event(&mut subscriber, |event| {
+            matches!(event, SensorEvent::EegPacket(_)).then_some(())
+        })
+        .await;
+    }
+    handle.stop().await;
+
+    // filtered samples are only written by the handler, as '#0001 O1=.. T3=.. T4=.. O2=..'
+    let output = std::fs::read_to_string(&log_file).unwrap();
+    std::fs::remove_file(&log_file).unwrap();
+    let o1: Vec<f64> = output
+        .lines()
+        .filter(|line| line.starts_with('#'))
+        .filter_map(|line| line.split(' ').nth(1)?.strip_prefix("O1=")?.parse().ok())
+        .collect();
+    assert!(o1.len() > 50);
+    // the filter settles in a few samples
+    assert!(o1[20..].iter().all(|uv| uv.abs() < 2.0), "{o1:?}");
+}
+
+#[tokio::test]
+async fn test_band_powers_on_simulated_device() {
+    // 0.75 s window is estimated 10 times per second
+    let config = SpectrumConfig::default()
+        .window(125)
+        .segments(2)
+        .update_rate(10.0);
+    let (recorder, mut events) = recording();
+    let handler = SpectrumHandler::new(recorder, config).unwrap();
+
+    let handle = start(SimulatedBrainBit::default(), handler).await;
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    let alpha_shares = events
+        .collect(5, |event| match event {
+            Recorded::Bands(powers) => powers
+                .channel(ChannelType::O1)
+                .map(|channel| channel.relative(Band::Alpha)),
+            _ => None,
+        })
+        .await;
+    handle.stop().await;
+
+    // simulated 20 uV alpha rhythm dominates 3 uV noise
+    assert!(
+        alpha_shares.iter().all(|share| *share > 0.8),
+        "{alpha_shares:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_artifacts_on_simulated_device() {
+    let config = SpectrumConfig::default()
+        .window(125)
+        .segments(2)
+        .update_rate(10.0);
+    let (recorder, mut events) = recording();
+    let handler = SpectrumHandler::new(recorder, config)
+        .unwrap()
+        .with_artifact_detection(ArtifactConfig::default().window(50).settle(50))
+        .unwrap();
+
+    let handle = start(SimulatedBrainBit::default(), handler).await;
+    // T3 is powered down and flat, O2 measures 1 Hz square test signal
+    let config = EegConfig::default()
+        .with_channel(
+            ChannelType::T3,
+            ChannelConfig::default().with_power(ChannelPower::Down),
+        )
+        .with_channel(
+            ChannelType::O2,
+            ChannelConfig::default().with_input(ChannelInput::Test),
+        );
+    handle.start_eeg(config).await.unwrap().unwrap();
+    let artifact = |events: &[Recorded], channel, kind| {
+        events.iter().any(|event| {
+            matches!(event, Recorded::Artifact(artifact)
+                if artifact.channel == channel && artifact.kind == kind)
+        })
+    };
+    let bands = |events: &[Recorded], channel| {
+        events.iter().any(
+            |event| matches!(event, Recorded::Bands(powers) if powers.channel(channel).is_some()),
+        )
+    };
+    events
+        .wait_until(|events| {
+            artifact(events, ChannelType::T3, ArtifactKind::FlatLine)
+                && artifact(events, ChannelType::O2, ArtifactKind::Motion)
+                && bands(events, ChannelType::O1)
+        })
+        .await;
+    handle.stop().await;
+
+    let events = events.finish();
+    assert!(
+        !events.iter().any(
+            |event| matches!(event, Recorded::Artifact(artifact) if artifact.channel == ChannelType::O1)
+        ),
+        "{events:?}"
+    );
+    // flagged channels are excluded from band powers, clean O1 is estimated
+    assert!(!bands(&events, ChannelType::T3));
+}
+
+#[tokio::test]
+async fn test_eye_state_on_simulated_device() {
+    // weak alpha rhythm of open eyes
+    let open = [0.5, 0.5, 0.5, 0.5];
+    let closed = [20.0, 8.0, 8.0, 20.0];
+    let device = SimulatedBrainBit::new(SimConfig {
+        alpha_amplitude_uv: open,
+        ..SimConfig::default()
+    });
+    // 0.75 s window is estimated 10 times per second, calibration steps are 1.3 s long
+    let config = SpectrumConfig::default()
+        .window(125)
+        .segments(2)
+        .update_rate(10.0);
+    let (recorder, mut events) = recording();
+    let handler = SpectrumHandler::new(
+        EyeStateTracker::new(recorder, EyeStateConfig::default().calibration(8, 5)).unwrap(),
+        config,
+    )
+    .unwrap();
+
+    let handle = start(device.clone(), handler).await;
+    handle
+        .start_eeg(EegConfig::default())
+        .await
+        .unwrap()
+        .unwrap();
+    // the simulated user follows calibration prompts
+    events
+        .wait_for(|event| {
+            matches!(
+                event,
+                Recorded::Eyes(EyeStateEvent::Calibration(CalibrationStep::EyesClosed))
+            )
+            .then_some(())
+        })
+        .await;
+    device.set_alpha_amplitude(closed);
+    assert_eq!(EyeState::Closed, events.wait_for(transition).await.state);
+    device.set_alpha_amplitude(open);
+    assert_eq!(EyeState::Open, events.wait_for(transition).await.state);
+    handle.stop().await;
+
+    let eyes: Vec<EyeStateEvent> = events
+        .finish()
+        .into_iter()
+        .filter_map(|event| match event {
+            Recorded::Eyes(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(5, eyes.len(), "{eyes:?}");
+    assert_eq!(
+        EyeStateEvent::Calibration(CalibrationStep::EyesOpen),
+        eyes[0]
+    );
+    assert_eq!(
+        EyeStateEvent::Calibration(CalibrationStep::EyesClosed),
+        eyes[1]
+    );
+    assert!(matches!(eyes[2], EyeStateEvent::Calibrated(_)));
+    for (event, state) in eyes[3..].iter().zip([EyeState::Closed, EyeState::Open]) {
+        let EyeStateEvent::Transition(transition) = event else {
+            panic!("{event:?} is not a transition");
+        };
+        assert_eq!(state, transition.state);
+        assert!((0.5..=1.0).contains(&transition.confidence), "{event:?}");
+    }
+}
@@ -1,15 +1,28 @@
 use std::{
     io::{self, Write},
     sync::atomic::{AtomicUsize, Ordering},
-    time::Duration,
 };
 
 use tokio::sync::oneshot;
 use tracing::{debug, instrument};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
-use brainbit::bbit::device::BBitSensor;
+use brainbit::bbit::device::{BBitSensor, BleHandle};
+use brainbit::bbit::internals::EegConfig;
+use brainbit::bbit::record::{RecordingTransport, ReplaySpeed, SessionReplay};
+use brainbit::bbit::resist::ResistanceSweepConfig;
+use brainbit::bbit::sealed::Bluetooth;
+use brainbit::bbit::transport::{AdapterSelector, BtleplugTransport, Transport};
 use brainbit::bbit::uuids::{EventType, PERIPHERAL_NAME_MATCH_FILTER};
+use brainbit::sim::SimulatedBrainBit;
+use handler::artifact::ArtifactConfig;
+use handler::eyes::{EyeStateConfig, EyeStateTracker};
+use handler::filter::FilterConfig;
+use handler::main_handler::BBitHandler;
+use handler::spectrum::{SpectrumConfig, SpectrumHandler};
+
+/// Power line frequency removed from EEG samples written by the app
+const MAINS_HZ: f64 = 50.0;
 
 #[tokio::main]
 #[instrument]
@@ -35,57 +48,165 @@ async fn main() -> color_eyre::Result<()> {
         )
         .init();
 
-    let connected = BBitSensor::new()
-        .await?
+    let args: Vec<String> = std::env::args().collect();
+    // '--list-adapters' prints Bluetooth adapters available for '--adapter'
+    if args.iter().any(|arg| arg == "--list-adapters") {
+        for adapter in BBitSensor::adapters().await? {
+            println!("{}: {}", adapter.index, adapter.info);
+        }
+        return Ok(());
+    }
+    // '--adapter <index|name>' selects Bluetooth adapter, the first one is used by default
+    let adapter = args
+        .iter()
+        .position(|arg| arg == "--adapter")
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.parse::<AdapterSelector>())
+        .transpose()?
+        .unwrap_or_default();
+
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    // '--replay <file>' passes recorded session to the handler instead of connecting,
+    // '--speed <factor|max>' changes its pace
+    if let Some(session_file) = arg_value("--replay") {
+        let speed = arg_value("--speed")
+            .map(|value| value.parse::<ReplaySpeed>())
+            .transpose()?
+            .unwrap_or_default();
+        tracing::info!("Replaying '{session_file}' session at {speed:?} speed");
+        let mut handler = SpectrumHandler::new(
+            EyeStateTracker::new(
+                BBitHandler::new("main_app_replay_output.txt")
+                    .await?
+                    .with_filter(&FilterConfig::eeg(MAINS_HZ))?,
+                EyeStateConfig::default(),
+            )?,
+            SpectrumConfig::default(),
+        )?
+        .with_artifact_detection(ArtifactConfig::default().mains(MAINS_HZ))?;
+        SessionReplay::open(session_file)?
+            .speed(speed)
+            .run(&mut handler)
+            .await?;
+        return Ok(());
+    }
+    // '--record <file>' captures notifications and commands of the session into the file
+    let record = arg_value("--record");
+
+    // '--sim' runs the app against in-process simulated headset
+    let handler = if args.iter().any(|arg| arg == "--sim") {
+        tracing::info!("Using simulated BrainBit device");
+        let device = SimulatedBrainBit::default();
+        match record {
+            Some(file) => {
+                start(BBitSensor::with_transport(RecordingTransport::new(
+                    device, file,
+                )?))
+                .await?
+            }
+            None => start(BBitSensor::with_transport(device)).await?,
+        }
+    } else {
+        tracing::info!("Using '{adapter}' Bluetooth adapter");
+        match record {
+            Some(file) => {
+                let transport = BtleplugTransport::with_adapter(adapter).await?;
+                start(BBitSensor::with_transport(RecordingTransport::new(
+                    transport, file,
+                )?))
+                .await?
+            }
+            None => start(BBitSensor::with_adapter(adapter).await?).await?,
+        }
+    };
+    tracing::info!("BrainBit is connected, event loop is started");
+    let fit = handler
+        .measure_resistance(ResistanceSweepConfig::default())
+        .await;
+    tracing::info!("Electrodes contact: {fit:?}");
+    let started = handler.start_eeg(EegConfig::default()).await;
+    tracing::info!("EEG measurement is started: {started:?}");
+
+    // Ctrl-C shuts down the event loop and flushes the handler, return then instead of
+    // waiting for the answer
+    let events = AtomicUsize::default();
+    tokio::select! {
+        outcome = handler.join() => {
+            tracing::info!("event loop is finished ({outcome:?}), the device is disconnected");
+        }
+        finish = get_finish(&events) => {
+            finish?;
+            let outcome = handler.stop().await;
+            tracing::info!("stopped the event loop ({outcome:?}), finishing");
+        }
+    }
+
+    Ok(())
+}
+
+async fn start<T: Transport>(sensor: BBitSensor<Bluetooth, T>) -> color_eyre::Result<BleHandle> {
+    let connected = sensor
         .block_connect(PERIPHERAL_NAME_MATCH_FILTER)
         .await?
         .listen(EventType::State)
         .listen(EventType::EegOrResistance)
+        .stop_on_ctrl_c(true)
         .build()
         .await?;
 
     let log_file_name = "main_app_output.txt";
     let handler = connected
-        .event_loop(handler::main_handler::BBitHandler::new(log_file_name).await?)
+        .event_loop(
+            SpectrumHandler::new(
+                EyeStateTracker::new(
+                    BBitHandler::new(log_file_name)
+                        .await?
+                        .with_filter(&FilterConfig::eeg(MAINS_HZ))?,
+                    EyeStateConfig::default(),
+                )?,
+                SpectrumConfig::default(),
+            )?
+            .with_artifact_detection(ArtifactConfig::default().mains(MAINS_HZ))?,
+        )
         .await;
-    tracing::info!("BrainBit is connected, event loop is started");
-    handler.start().await;
-
-    get_finish(&AtomicUsize::default()).await?;
-    handler.stop().await;
-
-    tracing::info!("stopped the event loop, finishing");
-
-    Ok(())
+    Ok(handler)
 }
 
+/// Resolves when 'y' is entered, never resolves when stdin is closed
 async fn get_finish(counter: &AtomicUsize) -> color_eyre::Result<()> {
-    let mut buf = String::new();
-    let (tx, mut rx) = oneshot::channel();
+    let (tx, rx) = oneshot::channel();
 
     println!();
     print!(
         "\r({} events received) Would you like to stop? (y/N) ",
         counter.load(Ordering::SeqCst)
     );
-    let task = tokio::task::spawn(async move {
+    io::stdout().flush()?;
+    // stdin is read on a detached thread, so returning from main doesn't wait for the input
+    std::thread::spawn(move || {
+        let mut buf = String::new();
         loop {
-            if let Ok(_) = rx.try_recv() {
+            buf.clear();
+            match io::stdin().read_line(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let control_letter = buf.trim().to_ascii_lowercase();
+            if control_letter == "y" {
+                debug!("entered letter: {control_letter:?}");
+                let _ = tx.send(());
                 return;
             }
-            io::stdout().flush().unwrap();
-            tokio::time::sleep(Duration::from_millis(200)).await;
         }
     });
 
-    loop {
-        io::stdin().read_line(&mut buf)?;
-        let control_letter = buf.trim().to_ascii_lowercase();
-        if control_letter == "y" {
-            debug!("entered letter: {control_letter:?}");
-            let _ = tx.send(());
-            task.await?;
-            return Ok(());
-        }
+    if rx.await.is_err() {
+        debug!("stdin is closed, waiting for Ctrl-C");
+        std::future::pending::<()>().await;
     }
+    Ok(())
 }